@@ -0,0 +1,121 @@
+use crate::api_server::{errors::ApiServerError, state::ApiServerState};
+
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+
+//
+// Endpoints that change what the indexer does take this extractor, which only
+// lets requests through carrying `Authorization: Bearer <SOLFORGE_ADMIN_TOKEN>`.
+// Without a token configured they are turned off altogether.
+//
+
+pub struct Admin;
+
+#[async_trait]
+impl FromRequestParts<ApiServerState> for Admin {
+    type Rejection = ApiServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &ApiServerState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(token) = state.admin_token() else {
+            return Err(ApiServerError::Forbidden(
+                "Admin endpoints are disabled, set SOLFORGE_ADMIN_TOKEN to enable them".to_string(),
+            ));
+        };
+
+        let presented = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "));
+
+        match presented {
+            Some(presented) if tokens_match(presented, token) => Ok(Admin),
+            _ => Err(ApiServerError::Unauthorized),
+        }
+    }
+}
+
+// Looks at every byte regardless of where the first mismatch is, so timing
+// doesn't give away how much of a guess was right
+fn tokens_match(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::server::DbConnection,
+        events::bus::EventBus,
+        indexer::{cluster::ClusterState, filter::FilterState},
+        webhooks::registry::WebhookRegistry,
+    };
+
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn state(admin_token: Option<&str>) -> ApiServerState {
+        ApiServerState::new(
+            Arc::new(Mutex::new(DbConnection::new())),
+            Arc::new(FilterState::new(Default::default())),
+            Arc::new(ClusterState::new()),
+            Arc::new(EventBus::new()),
+            Arc::new(WebhookRegistry::new(false)),
+            admin_token.map(String::from),
+        )
+    }
+
+    async fn authorize(
+        state: &ApiServerState,
+        authorization: Option<&str>,
+    ) -> Result<Admin, ApiServerError> {
+        let mut request = Request::builder();
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+
+        Admin::from_request_parts(&mut parts, state).await
+    }
+
+    #[tokio::test]
+    async fn requires_the_configured_token() {
+        let state = state(Some("hunter2"));
+
+        assert!(authorize(&state, Some("Bearer hunter2")).await.is_ok());
+
+        for authorization in [
+            None,
+            Some("Bearer hunter"),
+            Some("Bearer hunter22"),
+            Some("hunter2"),
+        ] {
+            assert!(matches!(
+                authorize(&state, authorization).await,
+                Err(ApiServerError::Unauthorized)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_without_a_token() {
+        let state = state(None);
+
+        assert!(matches!(
+            authorize(&state, Some("Bearer ")).await,
+            Err(ApiServerError::Forbidden(_))
+        ));
+    }
+}
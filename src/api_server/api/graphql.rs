@@ -0,0 +1,682 @@
+use crate::{
+    api_server::{errors::ApiServerError, extract::Json, state::ApiServerState},
+    common::{
+        account::Account,
+        block::Block,
+        instruction::Instruction,
+        reward::Reward,
+        transaction::{Transaction, TransactionStatus, TransactionSummary},
+    },
+    database::{
+        common::{DbQuery, DbResponse, TransactionFilter},
+        errors::DbError,
+    },
+};
+
+use async_graphql::{
+    connection::{Connection, Edge},
+    http::GraphiQLSource,
+    BatchRequest, BatchResponse, ComplexObject, Context, EmptyMutation, EmptySubscription, Enum,
+    Object, OutputType, Result, Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    response::Html,
+    routing::{get, Router},
+};
+use serde_json::Value;
+
+//
+// GraphQL over the same store as the REST API, for questions that would
+// otherwise take several round trips, like a block's failed transactions along
+// with their fee payers' balances. Lists that can grow without bound are
+// cursor paginated connections, and queries are limited in depth and
+// complexity, and batches in length, so a single request can't walk the whole
+// index.
+//
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 2000;
+const MAX_BATCH_SIZE: usize = 10;
+
+type GraphqlSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn routes() -> Router<ApiServerState> {
+    let schema = schema();
+
+    Router::new().route(
+        "/",
+        get(graphiql).post(
+            move |State(state): State<ApiServerState>, Json(request): Json<BatchRequest>| {
+                graphql(schema.clone(), state, request)
+            },
+        ),
+    )
+}
+
+fn schema() -> GraphqlSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// The limits apply to each query in a batch, so batches are kept short too
+async fn graphql(
+    schema: GraphqlSchema,
+    state: ApiServerState,
+    request: BatchRequest,
+) -> Result<Json<BatchResponse>, ApiServerError> {
+    if let BatchRequest::Batch(requests) = &request {
+        if requests.is_empty() || requests.len() > MAX_BATCH_SIZE {
+            return Err(ApiServerError::BadRequest(format!(
+                "Batches take between 1 and {MAX_BATCH_SIZE} queries"
+            )));
+        }
+    }
+
+    Ok(Json(schema.execute_batch(request.data(state)).await))
+}
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+//
+// Store access
+//
+
+async fn query(ctx: &Context<'_>, query: DbQuery) -> Result<DbResponse, DbError> {
+    ctx.data_unchecked::<ApiServerState>()
+        .db_connection()
+        .await?
+        .query(&query)
+        .await
+}
+
+// Missing entities resolve to null rather than an error
+async fn find<T: From<DbResponse>>(ctx: &Context<'_>, db_query: DbQuery) -> Result<Option<T>> {
+    match query(ctx, db_query).await {
+        Ok(response) => Ok(Some(response.into())),
+        Err(
+            DbError::BlockNotFound
+            | DbError::SlotNotFound
+            | DbError::TransactionNotFound
+            | DbError::AccountNotFound,
+        ) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn account(ctx: &Context<'_>, address: &str) -> Result<AccountNode> {
+    Ok(AccountNode {
+        address: address.to_string(),
+        account: find(ctx, DbQuery::GetAccount(address.to_string())).await?,
+    })
+}
+
+fn page_size(first: Option<i32>) -> usize {
+    first
+        .map_or(DEFAULT_PAGE_SIZE, |first| first.max(0) as usize)
+        .min(MAX_PAGE_SIZE)
+}
+
+fn complexity(first: Option<i32>, child_complexity: usize) -> usize {
+    page_size(first) * child_complexity
+}
+
+// Takes one more than asked for to tell whether there's a next page
+fn paginate<T: OutputType>(
+    mut items: Vec<T>,
+    limit: usize,
+    has_previous_page: bool,
+    cursor: impl Fn(&T) -> String,
+) -> Connection<String, T> {
+    let has_next_page = items.len() > limit;
+    items.truncate(limit);
+
+    let mut connection = Connection::new(has_previous_page, has_next_page);
+    connection
+        .edges
+        .extend(items.into_iter().map(|item| Edge::new(cursor(&item), item)));
+    connection
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(name = "TransactionStatus")]
+enum Status {
+    Success,
+    Failed,
+}
+
+impl From<Status> for TransactionStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Success => TransactionStatus::Success,
+            Status::Failed => TransactionStatus::Failed,
+        }
+    }
+}
+
+//
+// Types
+//
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn block(&self, ctx: &Context<'_>, id: String) -> Result<Option<BlockNode>> {
+        Ok(find(ctx, DbQuery::GetBlock(id)).await?.map(BlockNode))
+    }
+
+    async fn block_by_slot(&self, ctx: &Context<'_>, slot: u64) -> Result<Option<BlockNode>> {
+        Ok(find(ctx, DbQuery::GetBlockBySlot(slot))
+            .await?
+            .map(BlockNode))
+    }
+
+    // Newest first, the cursor being the slot
+    #[graphql(complexity = "complexity(first, child_complexity)")]
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<String, BlockNode>> {
+        let limit = page_size(first);
+        let end = match &after {
+            Some(after) => match after.parse::<u64>()?.checked_sub(1) {
+                Some(end) => end,
+                None => return Ok(Connection::new(true, false)),
+            },
+            None => u64::MAX,
+        };
+
+        let slots: Vec<u64> = query(ctx, DbQuery::GetSlots(0, end)).await?.into();
+
+        let mut blocks = Vec::new();
+        for slot in slots.into_iter().rev().take(limit + 1) {
+            if let Some(block) = find(ctx, DbQuery::GetBlockBySlot(slot)).await? {
+                blocks.push(BlockNode(block));
+            }
+        }
+
+        Ok(paginate(blocks, limit, after.is_some(), |block| {
+            block.0.slot.to_string()
+        }))
+    }
+
+    async fn transaction(
+        &self,
+        ctx: &Context<'_>,
+        signature: String,
+    ) -> Result<Option<TransactionNode>> {
+        Ok(find(ctx, DbQuery::GetTransaction(signature))
+            .await?
+            .map(TransactionNode))
+    }
+
+    async fn account(&self, ctx: &Context<'_>, address: String) -> Result<AccountNode> {
+        account(ctx, &address).await
+    }
+}
+
+pub struct BlockNode(Block);
+
+#[Object(name = "Block")]
+impl BlockNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn slot(&self) -> u64 {
+        self.0.slot
+    }
+
+    async fn previous_blockhash(&self) -> &str {
+        &self.0.previous_blockhash
+    }
+
+    async fn block_time(&self) -> Option<i64> {
+        self.0.block_time
+    }
+
+    async fn block_height(&self) -> Option<u64> {
+        self.0.block_height
+    }
+
+    async fn transaction_count(&self) -> usize {
+        self.0.transaction_count
+    }
+
+    async fn vote_transaction_count(&self) -> usize {
+        self.0.vote_transaction_count
+    }
+
+    async fn rewards(&self) -> Vec<RewardNode> {
+        self.0
+            .rewards
+            .iter()
+            .cloned()
+            .map(RewardNode::from)
+            .collect()
+    }
+
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<BlockNode>> {
+        Ok(
+            find(ctx, DbQuery::GetBlock(self.0.previous_blockhash.clone()))
+                .await?
+                .map(BlockNode),
+        )
+    }
+
+    // Only the transactions that were indexed, in signature order
+    #[graphql(complexity = "complexity(first, child_complexity)")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<Status>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<String, TransactionNode>> {
+        let limit = page_size(first);
+        let transactions: Vec<Transaction> = query(ctx, DbQuery::GetSlotTransactions(self.0.slot))
+            .await?
+            .into();
+
+        let transactions = transactions
+            .into_iter()
+            .filter(|transaction| match status {
+                Some(Status::Success) => transaction.err.is_none(),
+                Some(Status::Failed) => transaction.err.is_some(),
+                None => true,
+            })
+            .filter(|transaction| after.as_ref().is_none_or(|after| transaction.id > *after))
+            .take(limit + 1)
+            .map(TransactionNode)
+            .collect();
+
+        Ok(paginate(transactions, limit, after.is_some(), |node| {
+            node.0.id.clone()
+        }))
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Reward")]
+pub struct RewardNode {
+    pubkey: String,
+    lamports: i64,
+    post_balance: u64,
+    reward_type: Option<String>,
+    commission: Option<u8>,
+}
+
+impl From<Reward> for RewardNode {
+    fn from(reward: Reward) -> Self {
+        RewardNode {
+            pubkey: reward.pubkey,
+            lamports: reward.lamports,
+            post_balance: reward.post_balance,
+            reward_type: reward.reward_type,
+            commission: reward.commission,
+        }
+    }
+}
+
+pub struct TransactionNode(Transaction);
+
+#[Object(name = "Transaction")]
+impl TransactionNode {
+    async fn signature(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn slot(&self) -> u64 {
+        self.0.slot
+    }
+
+    // None for legacy transactions
+    async fn version(&self) -> Option<u8> {
+        self.0.version
+    }
+
+    async fn success(&self) -> bool {
+        self.0.err.is_none()
+    }
+
+    async fn err(&self) -> Result<Option<async_graphql::Json<Value>>> {
+        Ok(match &self.0.err {
+            Some(err) => Some(async_graphql::Json(serde_json::to_value(err)?)),
+            None => None,
+        })
+    }
+
+    async fn fee(&self) -> u64 {
+        self.0.fees
+    }
+
+    async fn priority_fee(&self) -> u64 {
+        self.0.priority_fee
+    }
+
+    async fn compute_units_consumed(&self) -> Option<u64> {
+        self.0.compute_units_consumed
+    }
+
+    async fn compute_unit_price(&self) -> Option<u64> {
+        self.0.compute_unit_price
+    }
+
+    async fn signatures(&self) -> &[String] {
+        &self.0.signatures
+    }
+
+    async fn log_messages(&self) -> &[String] {
+        &self.0.log_messages
+    }
+
+    async fn block(&self, ctx: &Context<'_>) -> Result<Option<BlockNode>> {
+        Ok(find(ctx, DbQuery::GetBlockBySlot(self.0.slot))
+            .await?
+            .map(BlockNode))
+    }
+
+    async fn fee_payer(&self, ctx: &Context<'_>) -> Result<Option<AccountNode>> {
+        match self.0.accounts.first() {
+            Some(address) => Ok(Some(account(ctx, address).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn accounts(&self) -> Vec<TransactionAccountNode> {
+        self.0
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(index, address)| {
+                let flags = self.0.account_flags.get(index).cloned().unwrap_or_default();
+
+                TransactionAccountNode {
+                    address: address.clone(),
+                    signer: flags.signer,
+                    writable: flags.writable,
+                    pre_balance: self.0.pre_balances.get(index).copied(),
+                    post_balance: self.0.balances.get(index).copied(),
+                }
+            })
+            .collect()
+    }
+
+    async fn instructions(&self) -> Vec<InstructionNode> {
+        self.0
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| InstructionNode {
+                instruction: instruction.clone(),
+                stack_height: None,
+                inner_instructions: self
+                    .0
+                    .inner_instructions
+                    .iter()
+                    .filter(|inner| inner.index as usize == index)
+                    .map(|inner| InstructionNode {
+                        instruction: inner.instruction.clone(),
+                        stack_height: inner.stack_height,
+                        inner_instructions: Vec::new(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    async fn events(&self) -> Result<async_graphql::Json<Value>> {
+        Ok(async_graphql::Json(serde_json::to_value(&self.0.events)?))
+    }
+}
+
+// An account as it appears in a transaction, with its balance either side
+#[derive(SimpleObject)]
+#[graphql(name = "TransactionAccount", complex)]
+pub struct TransactionAccountNode {
+    address: String,
+    signer: bool,
+    writable: bool,
+    pre_balance: Option<u64>,
+    post_balance: Option<u64>,
+}
+
+#[ComplexObject]
+impl TransactionAccountNode {
+    async fn account(&self, ctx: &Context<'_>) -> Result<AccountNode> {
+        account(ctx, &self.address).await
+    }
+}
+
+pub struct InstructionNode {
+    instruction: Instruction,
+    stack_height: Option<u32>,
+    // The CPIs made by a top-level instruction
+    inner_instructions: Vec<InstructionNode>,
+}
+
+#[Object(name = "Instruction")]
+impl InstructionNode {
+    async fn program_id(&self) -> &str {
+        &self.instruction.program_id
+    }
+
+    async fn program(&self, ctx: &Context<'_>) -> Result<AccountNode> {
+        account(ctx, &self.instruction.program_id).await
+    }
+
+    async fn accounts(&self, ctx: &Context<'_>) -> Result<Vec<AccountNode>> {
+        let mut accounts = Vec::with_capacity(self.instruction.accounts.len());
+        for address in &self.instruction.accounts {
+            accounts.push(account(ctx, address).await?);
+        }
+        Ok(accounts)
+    }
+
+    async fn data(&self) -> &str {
+        &self.instruction.data
+    }
+
+    async fn stack_height(&self) -> Option<u32> {
+        self.stack_height
+    }
+
+    // Set when the program's IDL or a built in decoder knew the instruction
+    async fn name(&self) -> Option<String> {
+        let (name, _) = self.instruction.decoded.as_ref()?.name_and_info()?;
+        Some(name)
+    }
+
+    async fn decoded(&self) -> Result<Option<async_graphql::Json<Value>>> {
+        Ok(match &self.instruction.decoded {
+            Some(decoded) => Some(async_graphql::Json(serde_json::to_value(decoded)?)),
+            None => None,
+        })
+    }
+
+    async fn inner_instructions(&self) -> &[InstructionNode] {
+        &self.inner_instructions
+    }
+}
+
+// Any address can be asked about, whether or not we've seen its state
+pub struct AccountNode {
+    address: String,
+    account: Option<Account>,
+}
+
+#[Object(name = "Account")]
+impl AccountNode {
+    async fn address(&self) -> &str {
+        &self.address
+    }
+
+    async fn indexed(&self) -> bool {
+        self.account.is_some()
+    }
+
+    async fn balance(&self) -> Option<u64> {
+        self.account.as_ref().map(|account| account.balance)
+    }
+
+    async fn owner(&self) -> Option<&str> {
+        let state = self.account.as_ref()?.state.as_ref()?;
+        Some(&state.owner)
+    }
+
+    async fn executable(&self) -> Option<bool> {
+        let state = self.account.as_ref()?.state.as_ref()?;
+        Some(state.executable)
+    }
+
+    // Base64 encoded
+    async fn data(&self) -> Option<&str> {
+        let state = self.account.as_ref()?.state.as_ref()?;
+        Some(&state.data)
+    }
+
+    async fn rent_epoch(&self) -> Option<u64> {
+        let state = self.account.as_ref()?.state.as_ref()?;
+        Some(state.rent_epoch)
+    }
+
+    // Newest first, the cursor being the signature
+    #[graphql(complexity = "complexity(first, child_complexity)")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<Status>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<String, TransactionNode>> {
+        let limit = page_size(first);
+        let filter = TransactionFilter {
+            status: status.map(Into::into),
+            before: after.clone(),
+            limit: Some(limit + 1),
+            ..Default::default()
+        };
+
+        let summaries: Vec<TransactionSummary> = query(
+            ctx,
+            DbQuery::GetAccountTransactions(self.address.clone(), filter),
+        )
+        .await?
+        .into();
+
+        let mut transactions = Vec::with_capacity(summaries.len());
+        for summary in summaries {
+            if let Some(transaction) = find(ctx, DbQuery::GetTransaction(summary.signature)).await?
+            {
+                transactions.push(TransactionNode(transaction));
+            }
+        }
+
+        Ok(paginate(transactions, limit, after.is_some(), |node| {
+            node.0.id.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::server::DbConnection,
+        events::bus::EventBus,
+        indexer::{cluster::ClusterState, filter::FilterState},
+        webhooks::registry::WebhookRegistry,
+    };
+
+    use async_graphql::Request;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn state() -> ApiServerState {
+        ApiServerState::new(
+            Arc::new(Mutex::new(DbConnection::new())),
+            Arc::new(FilterState::new(Default::default())),
+            Arc::new(ClusterState::new()),
+            Arc::new(EventBus::new()),
+            Arc::new(WebhookRegistry::new(false)),
+            None,
+        )
+    }
+
+    async fn errors(query: &str) -> Vec<String> {
+        schema()
+            .execute(Request::new(query).data(state()))
+            .await
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn limits_depth() {
+        let nested = |depth: usize| {
+            format!(
+                "{{ blockBySlot(slot: 1) {{ {} slot {} }} }}",
+                "parent { ".repeat(depth),
+                "} ".repeat(depth)
+            )
+        };
+
+        assert_eq!(errors(&nested(MAX_DEPTH - 2)).await, Vec::<String>::new());
+        assert_eq!(
+            errors(&nested(MAX_DEPTH)).await,
+            ["Query is nested too deep."]
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_complexity() {
+        let query = |first: usize| {
+            format!(
+                "{{ blocks(first: {first}) {{ edges {{ node {{ \
+                    transactions(first: {first}) {{ edges {{ node {{ signature }} }} }} \
+                }} }} }} }}"
+            )
+        };
+
+        assert_eq!(errors(&query(10)).await, Vec::<String>::new());
+        assert_eq!(errors(&query(100)).await, ["Query is too complex."]);
+    }
+
+    #[tokio::test]
+    async fn limits_batch_size() {
+        let batch = |size: usize| {
+            BatchRequest::Batch(
+                (0..size)
+                    .map(|_| Request::new("{ blocks { pageInfo { hasNextPage } } }"))
+                    .collect(),
+            )
+        };
+
+        for size in [1, MAX_BATCH_SIZE] {
+            let Json(response) = graphql(schema(), state(), batch(size)).await.unwrap();
+            let BatchResponse::Batch(responses) = response else {
+                panic!("Expected a batch response");
+            };
+            assert_eq!(responses.len(), size);
+            assert!(responses.iter().all(|response| response.is_ok()));
+        }
+
+        for size in [0, MAX_BATCH_SIZE + 1] {
+            assert!(matches!(
+                graphql(schema(), state(), batch(size)).await,
+                Err(ApiServerError::BadRequest(_))
+            ));
+        }
+    }
+}
@@ -1 +1,5 @@
+pub mod graphql;
+pub mod rpc;
+pub mod stream;
 pub mod v1;
+pub mod webhooks;
@@ -0,0 +1,894 @@
+use crate::{
+    api_server::{extract::Json, state::ApiServerState},
+    common::{
+        account::Account,
+        block::Block,
+        instruction::Instruction,
+        reward::Reward,
+        token::TokenBalance,
+        transaction::{Transaction, TransactionSummary},
+    },
+    database::{
+        common::{DbQuery, TransactionFilter},
+        errors::DbError,
+        server::DbConnection,
+    },
+    indexer::cluster::ClusterState,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    routing::{post, Router},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use solana_account_decoder::{
+    parse_account_data::SplTokenAdditionalDataV2, parse_token::token_amount_to_ui_amount_v3,
+};
+use solana_rpc_client_api::{
+    config::{
+        RpcBlockConfig, RpcBlocksConfigWrapper, RpcEncodingConfigWrapper,
+        RpcSignaturesForAddressConfig, RpcTransactionConfig,
+    },
+    custom_error::{
+        JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+        JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION,
+    },
+    request::{MAX_GET_CONFIRMED_BLOCKS_RANGE, MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT},
+    response::{Response, RpcConfirmedTransactionStatusWithSignature, RpcResponseContext},
+};
+use solana_sdk::{
+    message::MessageHeader,
+    pubkey::Pubkey,
+    reward_type::RewardType,
+    signature::Signature,
+    transaction::{TransactionError, TransactionVersion},
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionConfirmationStatus,
+    TransactionDetails, UiAddressTableLookup, UiCompiledInstruction, UiConfirmedBlock,
+    UiInnerInstructions, UiInstruction, UiLoadedAddresses, UiMessage, UiRawMessage, UiTransaction,
+    UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+use std::str::FromStr;
+
+//
+// A subset of Solana's JSON-RPC answered from the index, so existing clients
+// can be pointed at us rather than a cluster. Responses have the same shapes
+// as the real thing, but are rebuilt from what was indexed: only what the vote
+// policy and filters let through is there, transactions in a block come in
+// signature order, and only the json encoding is supported.
+//
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+// Most requests a batch takes at once, the same as the REST batch lookups
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError::new(INVALID_PARAMS, message)
+    }
+
+    fn block_not_available(slot: u64) -> Self {
+        RpcError::new(
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            format!("Block not available for slot {slot}"),
+        )
+    }
+}
+
+impl From<DbError> for RpcError {
+    fn from(error: DbError) -> Self {
+        RpcError::new(INTERNAL_ERROR, error.to_string())
+    }
+}
+
+pub fn routes() -> Router<ApiServerState> {
+    Router::new().route("/", post(rpc))
+}
+
+// Takes the raw body so malformed JSON gets a JSON-RPC error rather than a 400
+async fn rpc(State(state): State<ApiServerState>, body: Bytes) -> Json<Value> {
+    let response = match serde_json::from_slice(&body) {
+        Ok(Value::Array(requests)) if requests.len() > MAX_BATCH_SIZE => response(
+            Value::Null,
+            Err(RpcError::new(
+                INVALID_REQUEST,
+                format!("Batches take at most {MAX_BATCH_SIZE} requests"),
+            )),
+        ),
+        Ok(Value::Array(requests)) if !requests.is_empty() => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                responses.push(handle(&state, request).await);
+            }
+            Value::Array(responses)
+        }
+        Ok(request @ Value::Object(_)) => handle(&state, request).await,
+        Ok(_) => response(
+            Value::Null,
+            Err(RpcError::new(INVALID_REQUEST, "Invalid request")),
+        ),
+        Err(_) => response(Value::Null, Err(RpcError::new(PARSE_ERROR, "Parse error"))),
+    };
+
+    Json(response)
+}
+
+async fn handle(state: &ApiServerState, request: Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or_default();
+
+    let (Some("2.0"), Some(method)) = (request["jsonrpc"].as_str(), request["method"].as_str())
+    else {
+        return response(id, Err(RpcError::new(INVALID_REQUEST, "Invalid request")));
+    };
+
+    let params = match &request["params"] {
+        Value::Array(params) => params.as_slice(),
+        Value::Null => &[],
+        _ => return response(id, Err(RpcError::invalid_params("Invalid params"))),
+    };
+
+    let mut db_connection = match state.db_connection().await {
+        Ok(db_connection) => db_connection,
+        Err(error) => return response(id, Err(error.into())),
+    };
+
+    let result = match method {
+        "getBlock" => get_block(&mut db_connection, params).await,
+        "getTransaction" => get_transaction(&mut db_connection, params).await,
+        "getSignaturesForAddress" => {
+            get_signatures_for_address(&mut db_connection, &state.cluster(), params).await
+        }
+        "getBalance" => get_balance(&mut db_connection, params).await,
+        "getSlot" => get_slot(&mut db_connection).await,
+        "getBlockTime" => get_block_time(&mut db_connection, params).await,
+        "getBlocks" => get_blocks(&mut db_connection, params).await,
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+    };
+
+    response(id, result)
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "error": { "code": error.code, "message": error.message },
+            "id": id,
+        }),
+    }
+}
+
+//
+// Methods
+//
+
+async fn get_slot(db_connection: &mut DbConnection) -> Result<Value, RpcError> {
+    to_result(latest_slot(db_connection).await?)
+}
+
+async fn get_block_time(
+    db_connection: &mut DbConnection,
+    params: &[Value],
+) -> Result<Value, RpcError> {
+    let slot: u64 = required_param(params, 0)?;
+    let block = block_by_slot(db_connection, slot).await?;
+
+    to_result(block.block_time)
+}
+
+async fn get_blocks(db_connection: &mut DbConnection, params: &[Value]) -> Result<Value, RpcError> {
+    let start: u64 = required_param(params, 0)?;
+    let (end, _) = param::<RpcBlocksConfigWrapper>(params, 1)?
+        .map(|wrapper| wrapper.unzip())
+        .unwrap_or_default();
+
+    let end = match end {
+        Some(end) => end,
+        None => latest_slot(db_connection).await.unwrap_or(start),
+    };
+
+    if end.saturating_sub(start) > MAX_GET_CONFIRMED_BLOCKS_RANGE {
+        return Err(RpcError::invalid_params(format!(
+            "Slot range too large; max {MAX_GET_CONFIRMED_BLOCKS_RANGE}"
+        )));
+    }
+
+    if end < start {
+        return to_result(Vec::<u64>::new());
+    }
+
+    let slots: Vec<u64> = db_connection
+        .query(&DbQuery::GetSlots(start, end))
+        .await?
+        .into();
+
+    to_result(slots)
+}
+
+async fn get_balance(
+    db_connection: &mut DbConnection,
+    params: &[Value],
+) -> Result<Value, RpcError> {
+    let pubkey: String = required_param(params, 0)?;
+    validate(Pubkey::from_str(&pubkey))?;
+
+    let balance = match db_connection.query(&DbQuery::GetAccount(pubkey)).await {
+        Ok(account) => Account::from(account).balance,
+        // Anything we haven't seen is as good as empty
+        Err(DbError::AccountNotFound) => 0,
+        Err(error) => return Err(error.into()),
+    };
+
+    to_result(Response {
+        context: context(db_connection).await,
+        value: balance,
+    })
+}
+
+async fn get_signatures_for_address(
+    db_connection: &mut DbConnection,
+    cluster: &ClusterState,
+    params: &[Value],
+) -> Result<Value, RpcError> {
+    let address: String = required_param(params, 0)?;
+    validate(Pubkey::from_str(&address))?;
+
+    let config: RpcSignaturesForAddressConfig = param(params, 1)?.unwrap_or_default();
+    for signature in config.before.iter().chain(&config.until) {
+        validate(Signature::from_str(signature))?;
+    }
+
+    let limit = config
+        .limit
+        .unwrap_or(MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT);
+    if limit == 0 || limit > MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT {
+        return Err(RpcError::invalid_params(format!(
+            "Invalid limit; max {MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT}"
+        )));
+    }
+
+    let filter = TransactionFilter {
+        before: config.before,
+        limit: Some(limit),
+        ..Default::default()
+    };
+
+    let summaries: Vec<TransactionSummary> = db_connection
+        .query(&DbQuery::GetAccountTransactions(address, filter))
+        .await?
+        .into();
+
+    // Newest first, so everything from `until` on is older than asked for
+    let summaries = summaries
+        .into_iter()
+        .take_while(|summary| Some(&summary.signature) != config.until.as_ref());
+
+    // Blocks are fetched once confirmed, and only count as finalized once the
+    // cluster's root has caught up with them
+    let finalized_slot = cluster.tip().await.finalized_slot;
+
+    let mut signatures = Vec::new();
+    for summary in summaries {
+        let confirmation_status = match finalized_slot >= Some(summary.slot) {
+            true => TransactionConfirmationStatus::Finalized,
+            false => TransactionConfirmationStatus::Confirmed,
+        };
+
+        signatures.push(RpcConfirmedTransactionStatusWithSignature {
+            block_time: block_time(db_connection, summary.slot).await,
+            signature: summary.signature,
+            slot: summary.slot,
+            err: summary.err.map(Into::into),
+            memo: None,
+            confirmation_status: Some(confirmation_status),
+        });
+    }
+
+    to_result(signatures)
+}
+
+async fn get_transaction(
+    db_connection: &mut DbConnection,
+    params: &[Value],
+) -> Result<Value, RpcError> {
+    let signature: String = required_param(params, 0)?;
+    validate(Signature::from_str(&signature))?;
+
+    let config = param::<RpcEncodingConfigWrapper<RpcTransactionConfig>>(params, 1)?
+        .map(|wrapper| wrapper.convert_to_current())
+        .unwrap_or_default();
+    check_encoding(config.encoding)?;
+
+    let transaction = match db_connection
+        .query(&DbQuery::GetTransaction(signature))
+        .await
+    {
+        Ok(transaction) => Transaction::from(transaction),
+        Err(DbError::TransactionNotFound) => return Ok(Value::Null),
+        Err(error) => return Err(error.into()),
+    };
+
+    check_version(&transaction, config.max_supported_transaction_version)?;
+
+    to_result(EncodedConfirmedTransactionWithStatusMeta {
+        slot: transaction.slot,
+        block_time: block_time(db_connection, transaction.slot).await,
+        transaction: encode_transaction(
+            &transaction,
+            config.max_supported_transaction_version.is_some(),
+        )?,
+    })
+}
+
+async fn get_block(db_connection: &mut DbConnection, params: &[Value]) -> Result<Value, RpcError> {
+    let slot: u64 = required_param(params, 0)?;
+
+    let config = param::<RpcEncodingConfigWrapper<RpcBlockConfig>>(params, 1)?
+        .map(|wrapper| wrapper.convert_to_current())
+        .unwrap_or_default();
+    check_encoding(config.encoding)?;
+
+    let block = block_by_slot(db_connection, slot).await?;
+    let transactions: Vec<Transaction> = db_connection
+        .query(&DbQuery::GetSlotTransactions(slot))
+        .await?
+        .into();
+
+    let (transactions, signatures) = match config
+        .transaction_details
+        .unwrap_or(TransactionDetails::Full)
+    {
+        TransactionDetails::Full => {
+            let show_version = config.max_supported_transaction_version.is_some();
+            let transactions = transactions
+                .iter()
+                .map(|transaction| {
+                    check_version(transaction, config.max_supported_transaction_version)?;
+                    encode_transaction(transaction, show_version)
+                })
+                .collect::<Result<_, RpcError>>()?;
+
+            (Some(transactions), None)
+        }
+        TransactionDetails::Signatures => (
+            None,
+            Some(
+                transactions
+                    .into_iter()
+                    .map(|transaction| transaction.id)
+                    .collect(),
+            ),
+        ),
+        TransactionDetails::None => (None, None),
+        TransactionDetails::Accounts => {
+            return Err(RpcError::invalid_params(
+                "Unsupported transactionDetails: accounts",
+            ))
+        }
+    };
+
+    let rewards = config
+        .rewards
+        .unwrap_or(true)
+        .then(|| block.rewards.iter().map(encode_reward).collect());
+
+    to_result(UiConfirmedBlock {
+        previous_blockhash: block.previous_blockhash,
+        blockhash: block.id,
+        parent_slot: block.parent_slot,
+        transactions,
+        signatures,
+        rewards,
+        num_reward_partitions: None,
+        block_time: block.block_time,
+        block_height: block.block_height,
+    })
+}
+
+//
+// Helpers
+//
+
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|error| RpcError::invalid_params(format!("Invalid params: {error}"))),
+    }
+}
+
+fn required_param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, RpcError> {
+    param(params, index)?.ok_or_else(|| {
+        RpcError::invalid_params(format!(
+            "`params` should have at least {} argument(s)",
+            index + 1
+        ))
+    })
+}
+
+fn validate<T, E: std::fmt::Display>(result: Result<T, E>) -> Result<T, RpcError> {
+    result.map_err(|error| RpcError::invalid_params(format!("Invalid param: {error}")))
+}
+
+fn to_result<T: Serialize>(result: T) -> Result<Value, RpcError> {
+    serde_json::to_value(result).map_err(|error| RpcError::new(INTERNAL_ERROR, error.to_string()))
+}
+
+fn check_encoding(encoding: Option<UiTransactionEncoding>) -> Result<(), RpcError> {
+    match encoding {
+        None | Some(UiTransactionEncoding::Json) => Ok(()),
+        Some(encoding) => Err(RpcError::invalid_params(format!(
+            "Unsupported encoding: {encoding}, only json is available"
+        ))),
+    }
+}
+
+fn check_version(transaction: &Transaction, max_supported: Option<u8>) -> Result<(), RpcError> {
+    match transaction.version {
+        Some(version) if max_supported.is_none_or(|max| version > max) => Err(RpcError::new(
+            JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION,
+            format!(
+                "Transaction version ({version}) is not supported by the requesting client. \
+                     Please try the request again with the following configuration parameter: \
+                     \"maxSupportedTransactionVersion\": {version}"
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn latest_slot(db_connection: &mut DbConnection) -> Result<u64, RpcError> {
+    match db_connection.query(&DbQuery::GetLatestSlot).await {
+        Ok(slot) => Ok(slot.into()),
+        Err(DbError::SlotNotFound) => Err(RpcError::new(
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            "No blocks indexed yet",
+        )),
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn context(db_connection: &mut DbConnection) -> RpcResponseContext {
+    RpcResponseContext {
+        slot: latest_slot(db_connection).await.unwrap_or_default(),
+        api_version: None,
+    }
+}
+
+async fn block_by_slot(db_connection: &mut DbConnection, slot: u64) -> Result<Block, RpcError> {
+    match db_connection.query(&DbQuery::GetBlockBySlot(slot)).await {
+        Ok(block) => Ok(block.into()),
+        Err(DbError::SlotNotFound) => Err(RpcError::block_not_available(slot)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn block_time(db_connection: &mut DbConnection, slot: u64) -> Option<i64> {
+    block_by_slot(db_connection, slot).await.ok()?.block_time
+}
+
+//
+// Rebuilding the RPC's json encoding from what was indexed
+//
+
+fn encode_transaction(
+    transaction: &Transaction,
+    show_version: bool,
+) -> Result<EncodedTransactionWithStatusMeta, RpcError> {
+    // Addresses loaded from lookup tables come after the static keys, writable
+    // first
+    let loaded_writable: usize = transaction
+        .address_table_lookups
+        .iter()
+        .map(|lookup| lookup.writable_indexes.len())
+        .sum();
+    let loaded_readonly: usize = transaction
+        .address_table_lookups
+        .iter()
+        .map(|lookup| lookup.readonly_indexes.len())
+        .sum();
+    let static_count = transaction
+        .accounts
+        .len()
+        .saturating_sub(loaded_writable + loaded_readonly);
+    let (static_keys, loaded) = transaction.accounts.split_at(static_count);
+    let (writable, readonly) = loaded.split_at(loaded_writable.min(loaded.len()));
+
+    let static_flags =
+        &transaction.account_flags[..static_count.min(transaction.account_flags.len())];
+    let header = MessageHeader {
+        num_required_signatures: transaction.signer_count,
+        num_readonly_signed_accounts: static_flags
+            .iter()
+            .filter(|flags| flags.signer && !flags.writable)
+            .count() as u8,
+        num_readonly_unsigned_accounts: static_flags
+            .iter()
+            .filter(|flags| !flags.signer && !flags.writable)
+            .count() as u8,
+    };
+
+    // Anything indexed should be among the transaction's own accounts, so one
+    // that isn't means what was stored can't be turned back into indexes
+    let index_of = |address: &String| {
+        transaction
+            .accounts
+            .iter()
+            .position(|account| account == address)
+            .map(|index| index as u8)
+            .ok_or_else(|| {
+                RpcError::new(
+                    INTERNAL_ERROR,
+                    format!(
+                        "Account {address} missing from transaction {}",
+                        transaction.id
+                    ),
+                )
+            })
+    };
+    let compile = |instruction: &Instruction, stack_height: Option<u32>| {
+        Ok(UiCompiledInstruction {
+            program_id_index: index_of(&instruction.program_id)?,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(index_of)
+                .collect::<Result<_, RpcError>>()?,
+            data: instruction.data.clone(),
+            stack_height,
+        })
+    };
+
+    let mut inner_instructions: Vec<UiInnerInstructions> = Vec::new();
+    for inner in &transaction.inner_instructions {
+        let instruction = UiInstruction::Compiled(compile(&inner.instruction, inner.stack_height)?);
+
+        match inner_instructions.last_mut() {
+            Some(last) if last.index == inner.index => last.instructions.push(instruction),
+            _ => inner_instructions.push(UiInnerInstructions {
+                index: inner.index,
+                instructions: vec![instruction],
+            }),
+        }
+    }
+
+    // The index keeps a single entry per token account, so accounts opened or
+    // closed by the transaction show up on both sides with a zero balance
+    let token_balance = |amount: u64, balance: &TokenBalance| {
+        Ok(UiTransactionTokenBalance {
+            account_index: index_of(&balance.account)?,
+            mint: balance.mint.clone(),
+            ui_token_amount: token_amount_to_ui_amount_v3(
+                amount,
+                &SplTokenAdditionalDataV2::with_decimals(balance.decimals),
+            ),
+            owner: skip_if_none(balance.owner.clone()),
+            program_id: skip_if_none(balance.program_id.clone()),
+        })
+    };
+
+    let err: Option<TransactionError> = transaction.err.clone().map(Into::into);
+    let meta = UiTransactionStatusMeta {
+        status: match &err {
+            Some(err) => Err(err.clone()),
+            None => Ok(()),
+        },
+        err,
+        fee: transaction.fees,
+        pre_balances: transaction.pre_balances.clone(),
+        post_balances: transaction.balances.clone(),
+        inner_instructions: OptionSerializer::Some(inner_instructions),
+        log_messages: OptionSerializer::Some(transaction.log_messages.clone()),
+        pre_token_balances: OptionSerializer::Some(
+            transaction
+                .token_balances
+                .iter()
+                .map(|balance| token_balance(balance.pre_amount, balance))
+                .collect::<Result<_, RpcError>>()?,
+        ),
+        post_token_balances: OptionSerializer::Some(
+            transaction
+                .token_balances
+                .iter()
+                .map(|balance| token_balance(balance.amount, balance))
+                .collect::<Result<_, RpcError>>()?,
+        ),
+        // Transaction level rewards aren't indexed, and are always empty
+        rewards: OptionSerializer::Some(Vec::new()),
+        loaded_addresses: OptionSerializer::Some(UiLoadedAddresses {
+            writable: writable.to_vec(),
+            readonly: readonly.to_vec(),
+        }),
+        return_data: OptionSerializer::Skip,
+        compute_units_consumed: skip_if_none(transaction.compute_units_consumed),
+        cost_units: OptionSerializer::Skip,
+    };
+
+    let message = UiRawMessage {
+        header,
+        account_keys: static_keys.to_vec(),
+        recent_blockhash: transaction.recent_blockhash.clone(),
+        instructions: transaction
+            .instructions
+            .iter()
+            .map(|instruction| compile(instruction, None))
+            .collect::<Result<_, RpcError>>()?,
+        address_table_lookups: transaction.version.map(|_| {
+            transaction
+                .address_table_lookups
+                .iter()
+                .map(|lookup| UiAddressTableLookup {
+                    account_key: lookup.account_key.clone(),
+                    writable_indexes: lookup.writable_indexes.clone(),
+                    readonly_indexes: lookup.readonly_indexes.clone(),
+                })
+                .collect()
+        }),
+    };
+
+    Ok(EncodedTransactionWithStatusMeta {
+        transaction: EncodedTransaction::Json(UiTransaction {
+            signatures: transaction.signatures.clone(),
+            message: UiMessage::Raw(message),
+        }),
+        meta: Some(meta),
+        version: show_version.then_some(match transaction.version {
+            Some(version) => TransactionVersion::Number(version),
+            None => TransactionVersion::LEGACY,
+        }),
+    })
+}
+
+fn encode_reward(reward: &Reward) -> solana_transaction_status::Reward {
+    solana_transaction_status::Reward {
+        pubkey: reward.pubkey.clone(),
+        lamports: reward.lamports,
+        post_balance: reward.post_balance,
+        reward_type: reward
+            .reward_type
+            .as_deref()
+            .and_then(|reward_type| match reward_type {
+                "fee" => Some(RewardType::Fee),
+                "rent" => Some(RewardType::Rent),
+                "staking" => Some(RewardType::Staking),
+                "voting" => Some(RewardType::Voting),
+                _ => None,
+            }),
+        commission: reward.commission,
+    }
+}
+
+fn skip_if_none<T>(value: Option<T>) -> OptionSerializer<T> {
+    match value {
+        Some(value) => OptionSerializer::Some(value),
+        None => OptionSerializer::Skip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::bus::EventBus, indexer::filter::FilterState, webhooks::registry::WebhookRegistry,
+    };
+
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    const GET_TRANSACTION: &str =
+        include_str!("../../../tests/fixtures/rpc/get_transaction_v0.json");
+    const GET_BLOCK: &str = include_str!("../../../tests/fixtures/rpc/get_block_legacy.json");
+
+    fn result(fixture: &str) -> Value {
+        let mut response: Value = serde_json::from_str(fixture).unwrap();
+        response["result"].take()
+    }
+
+    // What the cluster returns that isn't indexed
+    fn strip_unindexed(transaction: &mut Value) {
+        transaction["meta"]
+            .as_object_mut()
+            .unwrap()
+            .remove("costUnits");
+    }
+
+    fn block(slot: u64, block_time: i64) -> Block {
+        Block {
+            id: format!("block-{slot}"),
+            previous_blockhash: format!("block-{}", slot - 1),
+            slot,
+            parent_slot: slot - 1,
+            block_time: Some(block_time),
+            block_height: None,
+            rewards: Vec::new(),
+            transaction_count: 1,
+            vote_transaction_count: 0,
+        }
+    }
+
+    async fn index_transaction(db_connection: &mut DbConnection, fixture: &Value) -> Transaction {
+        let encoded: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(fixture.clone()).unwrap();
+        let transaction = Transaction {
+            slot: encoded.slot,
+            ..Transaction::try_from(encoded.transaction).unwrap()
+        };
+
+        db_connection
+            .query(&DbQuery::InsertBlock(block(
+                encoded.slot,
+                encoded.block_time.unwrap(),
+            )))
+            .await
+            .unwrap();
+        db_connection
+            .query(&DbQuery::InsertTransaction(Box::new(transaction.clone())))
+            .await
+            .unwrap();
+
+        transaction
+    }
+
+    #[tokio::test]
+    async fn get_transaction_matches_the_rpc() {
+        let mut db_connection = DbConnection::new();
+        let mut expected = result(GET_TRANSACTION);
+        let transaction = index_transaction(&mut db_connection, &expected).await;
+
+        let params = [
+            json!(transaction.id),
+            json!({ "maxSupportedTransactionVersion": 0 }),
+        ];
+        let response = get_transaction(&mut db_connection, &params).await.unwrap();
+
+        strip_unindexed(&mut expected);
+        assert_eq!(response, expected);
+
+        // Without asking for versioned transactions, v0 ones are refused
+        let error = get_transaction(&mut db_connection, &params[..1])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.code,
+            JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION
+        );
+    }
+
+    #[tokio::test]
+    async fn get_block_matches_the_rpc() {
+        let mut db_connection = DbConnection::new();
+        let mut expected = result(GET_BLOCK);
+        let confirmed_block: UiConfirmedBlock = serde_json::from_value(expected.clone()).unwrap();
+        let slot = confirmed_block.parent_slot + 1;
+
+        let block = Block::new(slot, confirmed_block.clone());
+        db_connection
+            .query(&DbQuery::InsertBlock(block))
+            .await
+            .unwrap();
+        for transaction in confirmed_block.transactions.unwrap() {
+            let transaction = Transaction {
+                slot,
+                ..Transaction::try_from(transaction).unwrap()
+            };
+            db_connection
+                .query(&DbQuery::InsertTransaction(Box::new(transaction)))
+                .await
+                .unwrap();
+        }
+
+        let params = [json!(slot), json!({ "maxSupportedTransactionVersion": 0 })];
+        let response = get_block(&mut db_connection, &params).await.unwrap();
+
+        for transaction in expected["transactions"].as_array_mut().unwrap() {
+            strip_unindexed(transaction);
+        }
+        assert_eq!(response, expected);
+    }
+
+    #[tokio::test]
+    async fn get_signatures_for_address_reports_commitment() {
+        let mut db_connection = DbConnection::new();
+        let cluster = ClusterState::new();
+        let transaction = index_transaction(&mut db_connection, &result(GET_TRANSACTION)).await;
+
+        let params = [json!(transaction.accounts[0])];
+        let expected = |status: &str| {
+            json!([{
+                "blockTime": 1729339200,
+                "confirmationStatus": status,
+                "err": null,
+                "memo": null,
+                "signature": transaction.id,
+                "slot": transaction.slot,
+            }])
+        };
+
+        let response = get_signatures_for_address(&mut db_connection, &cluster, &params)
+            .await
+            .unwrap();
+        assert_eq!(response, expected("confirmed"));
+
+        cluster
+            .update(transaction.slot + 32, Some(transaction.slot))
+            .await;
+        let response = get_signatures_for_address(&mut db_connection, &cluster, &params)
+            .await
+            .unwrap();
+        assert_eq!(response, expected("finalized"));
+    }
+
+    #[tokio::test]
+    async fn batches_are_capped() {
+        let state = ApiServerState::new(
+            Arc::new(Mutex::new(DbConnection::new())),
+            Arc::new(FilterState::new(Default::default())),
+            Arc::new(ClusterState::new()),
+            Arc::new(EventBus::new()),
+            Arc::new(WebhookRegistry::new(false)),
+            None,
+        );
+        let batch = |size: usize| {
+            let requests: Vec<Value> = (0..size)
+                .map(|id| json!({ "jsonrpc": "2.0", "id": id, "method": "getSlot" }))
+                .collect();
+            Bytes::from(serde_json::to_vec(&requests).unwrap())
+        };
+
+        // Each request in a batch gets its own response
+        let Json(responses) = rpc(State(state.clone()), batch(MAX_BATCH_SIZE)).await;
+        let ids: Vec<Value> = responses
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|response| response["id"].clone())
+            .collect();
+        assert_eq!(
+            ids,
+            (0..MAX_BATCH_SIZE).map(|id| json!(id)).collect::<Vec<_>>()
+        );
+
+        // Too many, or none at all, is a single error for the whole batch
+        for size in [0, MAX_BATCH_SIZE + 1] {
+            let Json(response) = rpc(State(state.clone()), batch(size)).await;
+            assert_eq!(response["error"]["code"], INVALID_REQUEST);
+            assert_eq!(response["id"], Value::Null);
+        }
+    }
+
+    #[test]
+    fn unresolvable_accounts_are_an_error() {
+        let encoded: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(result(GET_TRANSACTION)).unwrap();
+        let mut transaction = Transaction::try_from(encoded.transaction).unwrap();
+        transaction.instructions[2].accounts[1] = "missing".to_string();
+
+        let error = encode_transaction(&transaction, true).unwrap_err();
+        assert_eq!(error.code, INTERNAL_ERROR);
+        assert!(error.message.contains("missing"));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{
+    api_server::{
+        errors::ApiServerError,
+        extract::{validate_pubkey, Query},
+        state::ApiServerState,
+    },
+    events::{
+        bus::{EventBus, Events},
+        event::IndexEvent,
+    },
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::BTreeSet, convert::Infallible};
+use tokio::{select, sync::broadcast::error::RecvError};
+
+//
+// Pushes events to clients as blocks are indexed, over a WebSocket when the
+// client asks to upgrade and as Server-Sent Events otherwise. What to send is
+// chosen in the query string:
+//
+//   blocks=true         every block
+//   addresses=A,B       transactions touching any of these accounts
+//   programs=P,Q        transactions invoking any of these programs
+//   balances=A,B        balance changes to any of these accounts, and their
+//                       full state if they're watched
+//   from_slot=N         replay recent events from slot N before going live
+//
+// Rollbacks of blocks that fell off the canonical chain go to everyone. SSE
+// clients that reconnect with Last-Event-ID resume from that slot. Events
+// from the resumed slot are sent again, so delivery is at least once. Resuming
+// from further back than the bus still holds starts with a gap event naming
+// the slot asked for, followed by whatever is still held, so the client knows
+// to catch up on the rest from the REST API.
+//
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Subscription {
+    #[serde(default)]
+    pub blocks: bool,
+    pub addresses: Option<String>,
+    pub programs: Option<String>,
+    pub balances: Option<String>,
+    pub from_slot: Option<u64>,
+}
+
+struct StreamFilter {
+    blocks: bool,
+    addresses: BTreeSet<String>,
+    programs: BTreeSet<String>,
+    balances: BTreeSet<String>,
+}
+
+impl StreamFilter {
+    fn new(subscription: &Subscription) -> Result<Self, ApiServerError> {
+        let filter = StreamFilter {
+            blocks: subscription.blocks,
+            addresses: pubkeys(&subscription.addresses)?,
+            programs: pubkeys(&subscription.programs)?,
+            balances: pubkeys(&subscription.balances)?,
+        };
+
+        if !filter.blocks
+            && filter.addresses.is_empty()
+            && filter.programs.is_empty()
+            && filter.balances.is_empty()
+        {
+            return Err(ApiServerError::BadRequest(
+                "Subscribe to at least one of blocks, addresses, programs or balances".to_string(),
+            ));
+        }
+
+        Ok(filter)
+    }
+
+    fn matches(&self, event: &IndexEvent) -> bool {
+        match event {
+            IndexEvent::Block { .. } => self.blocks,
+            IndexEvent::Transaction { transaction, .. } => {
+                transaction
+                    .accounts
+                    .iter()
+                    .any(|account| self.addresses.contains(account))
+                    || transaction
+                        .program_ids()
+                        .iter()
+                        .any(|program_id| self.programs.contains(*program_id))
+            }
+            IndexEvent::Balance { account, .. } => self.balances.contains(account),
+            IndexEvent::Account { account, .. } => self.balances.contains(&account.id),
+            // Anyone could have been sent something from the dropped block
+            IndexEvent::Rollback { .. } => true,
+        }
+    }
+}
+
+fn pubkeys(list: &Option<String>) -> Result<BTreeSet<String>, ApiServerError> {
+    let Some(list) = list else {
+        return Ok(BTreeSet::new());
+    };
+
+    list.split(',')
+        .map(str::trim)
+        .filter(|pubkey| !pubkey.is_empty())
+        .map(|pubkey| validate_pubkey(pubkey).map(|_| pubkey.to_string()))
+        .collect()
+}
+
+enum StreamItem {
+    // One of the events in a batch off the bus
+    Event(Events, usize),
+    // The subscriber fell too far behind and was cut off
+    Lagged(u64),
+    // Events from this slot on have already been let go of
+    Gap(u64),
+}
+
+impl StreamItem {
+    fn items(events: Events) -> Vec<StreamItem> {
+        (0..events.len())
+            .map(|index| StreamItem::Event(events.clone(), index))
+            .collect()
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            StreamItem::Event(events, index) => json!(events[*index]).to_string(),
+            StreamItem::Lagged(skipped) => {
+                json!({ "type": "lagged", "skipped": skipped }).to_string()
+            }
+            StreamItem::Gap(from_slot) => {
+                json!({ "type": "gap", "from_slot": from_slot }).to_string()
+            }
+        }
+    }
+}
+
+pub async fn stream(
+    State(state): State<ApiServerState>,
+    websocket: Option<WebSocketUpgrade>,
+    headers: HeaderMap,
+    Query(subscription): Query<Subscription>,
+) -> Result<Response, ApiServerError> {
+    let filter = StreamFilter::new(&subscription)?;
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok());
+    let from_slot = subscription.from_slot.or(last_event_id);
+
+    let items = items(&state.events(), from_slot).await.filter(move |item| {
+        let matches = match item {
+            StreamItem::Event(events, index) => filter.matches(&events[*index]),
+            StreamItem::Lagged(_) | StreamItem::Gap(_) => true,
+        };
+        async move { matches }
+    });
+
+    Ok(match websocket {
+        Some(websocket) => websocket
+            .on_upgrade(move |socket| send_websocket(socket, items))
+            .into_response(),
+        None => Sse::new(items.map(|item| Ok::<_, Infallible>(sse_event(&item))))
+            .keep_alive(KeepAlive::default())
+            .into_response(),
+    })
+}
+
+// Everything from `from_slot` on that the bus still holds, then live events
+async fn items(events: &EventBus, from_slot: Option<u64>) -> impl Stream<Item = StreamItem> {
+    let (gap, backlog, receiver) = match from_slot {
+        Some(from_slot) => match events.resume(from_slot).await {
+            Some((backlog, receiver)) => (None, backlog, receiver),
+            None => {
+                let (backlog, receiver) = events.subscribe(Some(from_slot)).await;
+                (Some(StreamItem::Gap(from_slot)), backlog, receiver)
+            }
+        },
+        None => {
+            let (backlog, receiver) = events.subscribe(None).await;
+            (None, backlog, receiver)
+        }
+    };
+
+    let live = stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        match receiver.recv().await {
+            Ok(events) => Some((StreamItem::items(events), Some(receiver))),
+            // Cut off, so the client picks up again from the last slot it saw
+            Err(RecvError::Lagged(skipped)) => Some((vec![StreamItem::Lagged(skipped)], None)),
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    stream::iter(gap)
+        .chain(stream::iter(
+            backlog.into_iter().flat_map(StreamItem::items),
+        ))
+        .chain(live.flat_map(stream::iter))
+}
+
+fn sse_event(item: &StreamItem) -> sse::Event {
+    let event = sse::Event::default().data(item.to_json());
+
+    match item {
+        StreamItem::Event(events, index) => event
+            .event(events[*index].kind())
+            .id(events[*index].slot().to_string()),
+        StreamItem::Lagged(_) => event.event("lagged"),
+        StreamItem::Gap(_) => event.event("gap"),
+    }
+}
+
+async fn send_websocket(mut socket: WebSocket, items: impl Stream<Item = StreamItem>) {
+    let mut items = Box::pin(items);
+
+    loop {
+        select! {
+            item = items.next() => {
+                let Some(item) = item else {
+                    break;
+                };
+                if socket.send(Message::Text(item.to_json())).await.is_err() {
+                    return;
+                }
+            }
+            // Nothing is expected from the client besides it hanging up
+            message = socket.recv() => {
+                if !matches!(message, Some(Ok(_))) {
+                    return;
+                }
+            }
+        }
+    }
+
+    let _ = socket.close().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::transaction::{Transaction, SYSTEM_PROGRAM_ID},
+        source::synthetic::{ChainGenerator, SyntheticConfig},
+    };
+
+    use serde_json::Value;
+
+    fn filter(subscription: Subscription) -> Result<StreamFilter, ApiServerError> {
+        StreamFilter::new(&subscription)
+    }
+
+    fn list(pubkey: &str) -> Option<String> {
+        Some(pubkey.to_string())
+    }
+
+    fn transaction() -> Transaction {
+        let config = "seed=1,fork_rate=0,v0_rate=0,blocks=1";
+        let (_, block) = ChainGenerator::new(config.parse::<SyntheticConfig>().unwrap())
+            .next()
+            .unwrap();
+        Transaction::try_from(block.transactions.unwrap().remove(0)).unwrap()
+    }
+
+    fn balance(account: &str) -> IndexEvent {
+        IndexEvent::Balance {
+            slot: 1,
+            account: account.to_string(),
+            signature: String::new(),
+            pre_balance: 1,
+            balance: 2,
+        }
+    }
+
+    fn rollback(slot: u64) -> IndexEvent {
+        IndexEvent::Rollback {
+            slot,
+            blockhash: format!("block-{slot}"),
+            signatures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn filters_by_subscription() {
+        let transaction = transaction();
+        let payer = transaction.accounts[0].clone();
+        let event = IndexEvent::Transaction {
+            slot: 1,
+            transaction: Box::new(transaction),
+        };
+
+        let by_address = filter(Subscription {
+            addresses: list(&payer),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(by_address.matches(&event));
+        assert!(!by_address.matches(&balance(&payer)));
+        assert!(by_address.matches(&rollback(1)));
+
+        let by_program = filter(Subscription {
+            programs: list(SYSTEM_PROGRAM_ID),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(by_program.matches(&event));
+        let by_other_program = filter(Subscription {
+            programs: list(&payer),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!by_other_program.matches(&event));
+
+        let by_balance = filter(Subscription {
+            balances: list(&payer),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(by_balance.matches(&balance(&payer)));
+        assert!(!by_balance.matches(&balance(SYSTEM_PROGRAM_ID)));
+        assert!(!by_balance.matches(&event));
+
+        assert!(filter(Subscription::default()).is_err());
+        assert!(filter(Subscription {
+            addresses: list("not-a-pubkey"),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    async fn first(events: &EventBus, from_slot: u64, count: usize) -> Vec<Value> {
+        items(events, Some(from_slot))
+            .await
+            .take(count)
+            .map(|item| serde_json::from_str(&item.to_json()).unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn resumes_or_reports_a_gap() {
+        let events = EventBus::new();
+        for slot in 1..=200 {
+            events.publish(vec![rollback(slot)]).await;
+        }
+
+        // Still held, so it picks up right where it was asked to
+        let resumed = first(&events, 100, 101).await;
+        let slots: Vec<u64> = resumed
+            .iter()
+            .map(|event| event["slot"].as_u64().unwrap())
+            .collect();
+        assert_eq!(slots, (100..=200).collect::<Vec<_>>());
+
+        // Gone, so it says so before sending what's left
+        let resumed = first(&events, 10, 2).await;
+        assert_eq!(resumed[0], json!({ "type": "gap", "from_slot": 10 }));
+        assert_eq!(resumed[1]["type"], "rollback");
+        assert!(resumed[1]["slot"].as_u64().unwrap() > 10);
+    }
+}
@@ -1,23 +1,108 @@
 use crate::{
-    api_server::{errors::ApiServerError, state::ApiServerState},
-    common::{account::Account, block::Block, transaction::Transaction},
-    database::common::DbQuery,
+    api_server::{
+        admin::Admin,
+        api::{stream, webhooks},
+        errors::ApiServerError,
+        extract::{validate_blockhash, validate_pubkey, validate_signature, Json, Path, Query},
+        state::ApiServerState,
+    },
+    common::{
+        account::Account,
+        block::{Block, BlockStats},
+        event::ProgramActivity,
+        reward::AccountReward,
+        token::{TokenAccountTransfers, TokenHolding},
+        transaction::{Transaction, TransactionSummary},
+        vote::VoteTransaction,
+    },
+    database::{
+        common::{DbQuery, DbResponse, Limit, LogSearch, SlotRange, TransactionFilter},
+        errors::DbError,
+    },
+    indexer::filter::{FilterCounters, IndexFilter},
 };
 
 use axum::{
-    extract::{Path, State},
-    routing::{get, Router},
-    Json,
+    extract::State,
+    routing::{get, post, Router},
 };
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Most ids a batch lookup takes at once
+const MAX_BATCH_SIZE: usize = 100;
+
+//
+// Batch lookups take a list of ids and answer with what was found keyed by id,
+// and {"error": "not_found"} for the rest, all out of a single query
+//
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchItem<T> {
+    Found(T),
+    NotFound { error: &'static str },
+}
+
+impl<T> From<Option<T>> for BatchItem<T> {
+    fn from(item: Option<T>) -> Self {
+        match item {
+            Some(item) => BatchItem::Found(item),
+            None => BatchItem::NotFound { error: "not_found" },
+        }
+    }
+}
+
+type BatchResponse<T> = Json<BTreeMap<String, BatchItem<T>>>;
+
+// What's been indexed so far, next to how far the cluster has got
+#[derive(Serialize)]
+struct ChainHead {
+    latest_slot: Option<u64>,
+    latest_block: Option<Block>,
+    lowest_slot: Option<u64>,
+    // Only known when the source is following a cluster
+    cluster_slot: Option<u64>,
+    finalized_slot: Option<u64>,
+    // Slots the index is behind the cluster
+    lag: Option<u64>,
+}
 
 pub fn routes() -> Router<ApiServerState> {
     // TODO: Handle backfilling missing data
 
     Router::new()
         .route("/block/:id", get(get_block))
+        .route("/block/latest", get(get_latest_block))
+        .route("/blocks", get(get_block_range).post(get_blocks))
+        .route("/chain/head", get(get_chain_head))
         .route("/block-by-slot/:slot", get(get_block_by_slot))
+        .route("/block/:id/stats", get(get_block_stats))
         .route("/transaction/:id", get(get_transaction))
+        .route("/transactions", post(get_transactions))
+        .route("/transactions/search", get(search_logs))
+        .route("/vote/:id", get(get_vote))
         .route("/account/:id", get(get_account))
+        .route("/accounts", post(get_accounts))
+        .route("/account/:id/rewards", get(get_account_rewards))
+        .route("/program/:id/transactions", get(get_program_transactions))
+        .route(
+            "/program/:id/instructions/:name",
+            get(get_program_instructions),
+        )
+        .route("/program/:id/events/:name", get(get_program_events))
+        .route("/owner/:id/tokens", get(get_token_holdings))
+        .route("/mint/:id/holders", get(get_token_holders))
+        .route("/token-account/:id/transfers", get(get_token_transfers))
+        .route("/indexer/filter", get(get_filter).put(set_filter))
+        .route("/indexer/filter/counters", get(get_filter_counters))
+        .route("/stream", get(stream::stream))
+        .nest("/webhooks", webhooks::routes())
 
     // TODO: Enable/disable via a config file
     //.route("/dump", get(dump)) // Used for debugging
@@ -27,62 +112,653 @@ async fn get_block(
     State(state): State<ApiServerState>,
     Path(id): Path<String>,
 ) -> Result<Json<Block>, ApiServerError> {
+    validate_blockhash(&id)?;
+
     let block = state
         .db_connection()
-        .lock()
-        .await
+        .await?
         .query(&DbQuery::GetBlock(id))
         .await?;
 
     Ok(Json(block.into()))
 }
 
+async fn get_blocks(
+    State(state): State<ApiServerState>,
+    Json(request): Json<BatchRequest>,
+) -> Result<BatchResponse<Block>, ApiServerError> {
+    validate_batch(&request, validate_blockhash)?;
+
+    let blocks: BTreeMap<String, Option<Block>> = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetBlocks(request.ids))
+        .await?
+        .into();
+
+    Ok(batch_response(blocks))
+}
+
+async fn get_latest_block(
+    State(state): State<ApiServerState>,
+) -> Result<Json<Block>, ApiServerError> {
+    let mut db_connection = state.db_connection().await?;
+
+    let slot = match db_connection.query(&DbQuery::GetLatestSlot).await {
+        Err(DbError::SlotNotFound) => {
+            return Err(ApiServerError::NotFound(
+                "No blocks indexed yet".to_string(),
+            ))
+        }
+        slot => slot?,
+    };
+    let block = db_connection
+        .query(&DbQuery::GetBlockBySlot(slot.into()))
+        .await?;
+
+    Ok(Json(block.into()))
+}
+
+async fn get_block_range(
+    State(state): State<ApiServerState>,
+    Query(range): Query<SlotRange>,
+) -> Result<Json<Vec<Block>>, ApiServerError> {
+    let blocks = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetBlockRange(range))
+        .await?;
+
+    Ok(Json(blocks.into()))
+}
+
+async fn get_chain_head(
+    State(state): State<ApiServerState>,
+) -> Result<Json<ChainHead>, ApiServerError> {
+    let (latest_block, lowest_slot) = {
+        let mut db_connection = state.db_connection().await?;
+
+        let latest_block: Option<Block> = match db_connection.query(&DbQuery::GetLatestSlot).await {
+            Ok(slot) => found(
+                db_connection
+                    .query(&DbQuery::GetBlockBySlot(slot.into()))
+                    .await,
+            )?,
+            Err(error) => found(Err(error))?,
+        };
+        let lowest_slot = found(db_connection.query(&DbQuery::GetLowestSlot).await)?;
+
+        (latest_block, lowest_slot)
+    };
+
+    let tip = state.cluster().tip().await;
+    let latest_slot = latest_block.as_ref().map(|block| block.slot);
+
+    Ok(Json(ChainHead {
+        latest_slot,
+        latest_block,
+        lowest_slot,
+        cluster_slot: tip.slot,
+        finalized_slot: tip.finalized_slot,
+        lag: tip
+            .slot
+            .zip(latest_slot)
+            .map(|(cluster_slot, latest_slot)| cluster_slot.saturating_sub(latest_slot)),
+    }))
+}
+
 async fn get_block_by_slot(
     State(state): State<ApiServerState>,
     Path(slot): Path<u64>,
 ) -> Result<Json<Block>, ApiServerError> {
     let block = state
         .db_connection()
-        .lock()
-        .await
+        .await?
         .query(&DbQuery::GetBlockBySlot(slot))
         .await?;
 
     Ok(Json(block.into()))
 }
 
+async fn get_block_stats(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+) -> Result<Json<BlockStats>, ApiServerError> {
+    validate_blockhash(&id)?;
+
+    let stats = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetBlockStats(id))
+        .await?;
+
+    Ok(Json(stats.into()))
+}
+
 async fn get_transaction(
     State(state): State<ApiServerState>,
     Path(id): Path<String>,
 ) -> Result<Json<Transaction>, ApiServerError> {
+    validate_signature(&id)?;
+
     let transaction = state
         .db_connection()
-        .lock()
-        .await
+        .await?
         .query(&DbQuery::GetTransaction(id))
         .await?;
 
     Ok(Json(transaction.into()))
 }
 
+async fn get_transactions(
+    State(state): State<ApiServerState>,
+    Json(request): Json<BatchRequest>,
+) -> Result<BatchResponse<Transaction>, ApiServerError> {
+    validate_batch(&request, validate_signature)?;
+
+    let transactions: BTreeMap<String, Option<Transaction>> = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetTransactions(request.ids))
+        .await?
+        .into();
+
+    Ok(batch_response(transactions))
+}
+
+async fn get_program_instructions(
+    State(state): State<ApiServerState>,
+    Path((id, name)): Path<(String, String)>,
+    Query(filter): Query<TransactionFilter>,
+) -> Result<Json<Vec<ProgramActivity>>, ApiServerError> {
+    validate_pubkey(&id)?;
+    validate_before(&filter.before)?;
+
+    let instructions = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetProgramInstructions(id, name, filter))
+        .await?;
+
+    Ok(Json(instructions.into()))
+}
+
+async fn get_program_events(
+    State(state): State<ApiServerState>,
+    Path((id, name)): Path<(String, String)>,
+    Query(filter): Query<TransactionFilter>,
+) -> Result<Json<Vec<ProgramActivity>>, ApiServerError> {
+    validate_pubkey(&id)?;
+    validate_before(&filter.before)?;
+
+    let events = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetProgramEvents(id, name, filter))
+        .await?;
+
+    Ok(Json(events.into()))
+}
+
+async fn search_logs(
+    State(state): State<ApiServerState>,
+    Query(search): Query<LogSearch>,
+) -> Result<Json<Vec<TransactionSummary>>, ApiServerError> {
+    if search.contains.is_none() && search.program.is_none() {
+        return Err(ApiServerError::BadRequest(
+            "Either contains or program is required".to_string(),
+        ));
+    }
+    if let Some(program) = &search.program {
+        validate_pubkey(program)?;
+    }
+    validate_before(&search.before)?;
+
+    let transactions = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::SearchLogs(search))
+        .await?;
+
+    Ok(Json(transactions.into()))
+}
+
+async fn get_vote(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+) -> Result<Json<VoteTransaction>, ApiServerError> {
+    validate_signature(&id)?;
+
+    let vote = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetVote(id))
+        .await?;
+
+    Ok(Json(vote.into()))
+}
+
 async fn get_account(
     State(state): State<ApiServerState>,
     Path(id): Path<String>,
 ) -> Result<Json<Account>, ApiServerError> {
+    validate_pubkey(&id)?;
+
     let account = state
         .db_connection()
-        .lock()
-        .await
+        .await?
         .query(&DbQuery::GetAccount(id))
         .await?;
 
     Ok(Json(account.into()))
 }
 
+async fn get_accounts(
+    State(state): State<ApiServerState>,
+    Json(request): Json<BatchRequest>,
+) -> Result<BatchResponse<Account>, ApiServerError> {
+    validate_batch(&request, validate_pubkey)?;
+
+    let accounts: BTreeMap<String, Option<Account>> = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetAccounts(request.ids))
+        .await?
+        .into();
+
+    Ok(batch_response(accounts))
+}
+
+async fn get_account_rewards(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+    Query(range): Query<SlotRange>,
+) -> Result<Json<Vec<AccountReward>>, ApiServerError> {
+    validate_pubkey(&id)?;
+
+    let rewards = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetAccountRewards(id, range))
+        .await?;
+
+    Ok(Json(rewards.into()))
+}
+
+async fn get_program_transactions(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+    Query(filter): Query<TransactionFilter>,
+) -> Result<Json<Vec<TransactionSummary>>, ApiServerError> {
+    validate_pubkey(&id)?;
+    validate_before(&filter.before)?;
+
+    let transactions = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetProgramTransactions(id, filter))
+        .await?;
+
+    Ok(Json(transactions.into()))
+}
+
+async fn get_token_holdings(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TokenHolding>>, ApiServerError> {
+    validate_pubkey(&id)?;
+
+    let holdings = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetTokenHoldings(id))
+        .await?;
+
+    Ok(Json(holdings.into()))
+}
+
+async fn get_token_holders(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+    Query(limit): Query<Limit>,
+) -> Result<Json<Vec<TokenHolding>>, ApiServerError> {
+    validate_pubkey(&id)?;
+
+    let holders = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetTokenHolders(id, limit))
+        .await?;
+
+    Ok(Json(holders.into()))
+}
+
+async fn get_token_transfers(
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+    Query(filter): Query<TransactionFilter>,
+) -> Result<Json<Vec<TokenAccountTransfers>>, ApiServerError> {
+    validate_pubkey(&id)?;
+    validate_before(&filter.before)?;
+
+    let transfers = state
+        .db_connection()
+        .await?
+        .query(&DbQuery::GetTokenTransfers(id, filter))
+        .await?;
+
+    Ok(Json(transfers.into()))
+}
+
 // use crate::database::server::DbConnection;
 //
 // async fn dump(State(state): State<ApiServerState>) -> Result<Json<DbConnection>, ApiServerError> {
 //     Ok(Json(
-//         state.db_connection().lock().await.dump().await.clone(),
+//         state.db_connection().await?.dump().await.clone(),
 //     ))
 // }
+
+async fn get_filter(State(state): State<ApiServerState>) -> Json<IndexFilter> {
+    Json(state.filter().filter().await)
+}
+
+async fn set_filter(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Json(filter): Json<IndexFilter>,
+) -> Result<Json<IndexFilter>, ApiServerError> {
+    filter
+        .include_accounts
+        .iter()
+        .chain(&filter.exclude_accounts)
+        .chain(&filter.include_programs)
+        .chain(&filter.exclude_programs)
+        .try_for_each(|address| validate_pubkey(address))?;
+
+    state.filter().set_filter(filter.clone()).await;
+
+    Ok(Json(filter))
+}
+
+async fn get_filter_counters(State(state): State<ApiServerState>) -> Json<FilterCounters> {
+    Json(state.filter().counters())
+}
+
+// Paging cursors are signatures
+fn validate_before(before: &Option<String>) -> Result<(), ApiServerError> {
+    match before {
+        Some(before) => validate_signature(before),
+        None => Ok(()),
+    }
+}
+
+fn validate_batch(
+    request: &BatchRequest,
+    validate: fn(&str) -> Result<(), ApiServerError>,
+) -> Result<(), ApiServerError> {
+    if request.ids.is_empty() || request.ids.len() > MAX_BATCH_SIZE {
+        return Err(ApiServerError::BadRequest(format!(
+            "Between 1 and {MAX_BATCH_SIZE} ids are required"
+        )));
+    }
+
+    request.ids.iter().try_for_each(|id| validate(id))
+}
+
+fn batch_response<T>(items: BTreeMap<String, Option<T>>) -> BatchResponse<T> {
+    Json(
+        items
+            .into_iter()
+            .map(|(id, item)| (id, item.into()))
+            .collect(),
+    )
+}
+
+// Nothing indexed yet isn't an error here
+fn found<T: From<DbResponse>>(
+    response: Result<DbResponse, DbError>,
+) -> Result<Option<T>, ApiServerError> {
+    match response {
+        Ok(response) => Ok(Some(response.into())),
+        Err(DbError::SlotNotFound | DbError::BlockNotFound) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api_server::request_id::{request_id, REQUEST_ID_HEADER},
+        database::server::DbConnection,
+        events::bus::EventBus,
+        indexer::{cluster::ClusterState, filter::FilterState},
+        webhooks::registry::WebhookRegistry,
+    };
+
+    use axum::{http::StatusCode, middleware};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::{net::TcpListener, spawn, sync::Mutex};
+
+    const BLOCKHASH: &str = "11111111111111111111111111111111";
+    const OTHER_BLOCKHASH: &str = "4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZAMdL4VZHirAn";
+
+    struct Api {
+        url: String,
+        db_connection: Arc<Mutex<DbConnection>>,
+        cluster: Arc<ClusterState>,
+        client: reqwest::Client,
+    }
+
+    impl Api {
+        async fn get(&self, path: &str) -> (StatusCode, Value) {
+            let response = self
+                .client
+                .get(format!("{}{path}", self.url))
+                .header(&REQUEST_ID_HEADER, "test-id")
+                .send()
+                .await
+                .unwrap();
+            (response.status(), response.json().await.unwrap())
+        }
+
+        async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+            let response = self
+                .client
+                .post(format!("{}{path}", self.url))
+                .header(&REQUEST_ID_HEADER, "test-id")
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            (response.status(), response.json().await.unwrap())
+        }
+    }
+
+    fn block(id: &str, slot: u64) -> Block {
+        Block {
+            id: id.to_string(),
+            previous_blockhash: String::new(),
+            slot,
+            parent_slot: slot - 1,
+            block_time: None,
+            block_height: None,
+            rewards: Vec::new(),
+            transaction_count: 0,
+            vote_transaction_count: 0,
+        }
+    }
+
+    async fn serve() -> Api {
+        let db_connection = Arc::new(Mutex::new(DbConnection::new()));
+        let cluster = Arc::new(ClusterState::new());
+        let state = ApiServerState::new(
+            db_connection.clone(),
+            Arc::new(FilterState::new(Default::default())),
+            cluster.clone(),
+            Arc::new(EventBus::new()),
+            Arc::new(WebhookRegistry::new(false)),
+            None,
+        );
+
+        let routes = Router::new()
+            .nest("/api/v1", routes())
+            .with_state(state)
+            .layer(middleware::from_fn(request_id));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/v1", listener.local_addr().unwrap());
+        spawn(async move { axum::serve(listener, routes).await });
+
+        Api {
+            url,
+            db_connection,
+            cluster,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_have_a_status_and_a_json_body() {
+        let api = serve().await;
+
+        assert_eq!(
+            api.get(&format!("/block/{BLOCKHASH}")).await,
+            (
+                StatusCode::NOT_FOUND,
+                json!({
+                    "code": "not_found",
+                    "message": "Block not found",
+                    "request_id": "test-id",
+                })
+            )
+        );
+
+        let (status, body) = api.get("/block/not-a-blockhash").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["request_id"], "test-id");
+
+        let (status, body) = api.get("/block-by-slot/minus-one").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+
+        // The store held for longer than a request will wait
+        let _held = api.db_connection.lock().await;
+        assert_eq!(
+            api.get(&format!("/block/{BLOCKHASH}")).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({
+                    "code": "service_unavailable",
+                    "message": "Service unavailable",
+                    "request_id": "test-id",
+                })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_mark_what_was_not_found() {
+        let api = serve().await;
+        {
+            let mut db_connection = api.db_connection.lock().await;
+            db_connection
+                .query(&DbQuery::InsertBlock(block(BLOCKHASH, 1000)))
+                .await
+                .unwrap();
+            db_connection
+                .query(&DbQuery::InsertAccount(Account::new(
+                    BLOCKHASH.to_string(),
+                    42,
+                    1000,
+                )))
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = api
+            .post("/blocks", json!({"ids": [BLOCKHASH, OTHER_BLOCKHASH]}))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[BLOCKHASH]["slot"], 1000);
+        assert_eq!(body[OTHER_BLOCKHASH], json!({"error": "not_found"}));
+
+        let (status, body) = api
+            .post("/accounts", json!({"ids": [BLOCKHASH, OTHER_BLOCKHASH]}))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[BLOCKHASH]["balance"], 42);
+        assert_eq!(body[OTHER_BLOCKHASH], json!({"error": "not_found"}));
+
+        // Between 1 and 100 ids
+        let ids = |count| json!({"ids": vec![BLOCKHASH; count]});
+        assert_eq!(api.post("/blocks", ids(100)).await.0, StatusCode::OK);
+        for count in [0, 101] {
+            let (status, body) = api.post("/blocks", ids(count)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["message"], "Between 1 and 100 ids are required");
+        }
+
+        // Every id is checked before anything is looked up
+        let (status, body) = api
+            .post("/transactions", json!({"ids": ["not-a-signature"]}))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn chain_head_reports_lag_and_lowest_slot() {
+        let api = serve().await;
+
+        // Nothing indexed, and no cluster to compare against
+        assert_eq!(
+            api.get("/chain/head").await,
+            (
+                StatusCode::OK,
+                json!({
+                    "latest_slot": null,
+                    "latest_block": null,
+                    "lowest_slot": null,
+                    "cluster_slot": null,
+                    "finalized_slot": null,
+                    "lag": null,
+                })
+            )
+        );
+
+        {
+            let mut db_connection = api.db_connection.lock().await;
+            for (id, slot) in [("a", 1002), ("b", 1000), ("c", 1005)] {
+                db_connection
+                    .query(&DbQuery::InsertBlock(block(id, slot)))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let (status, body) = api.get("/chain/head").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["latest_slot"], 1005);
+        assert_eq!(body["latest_block"]["id"], "c");
+        assert_eq!(body["lowest_slot"], 1000);
+        assert_eq!(body["lag"], Value::Null);
+
+        api.cluster.update(1012, Some(980)).await;
+        let (_, body) = api.get("/chain/head").await;
+        assert_eq!(body["cluster_slot"], 1012);
+        assert_eq!(body["finalized_slot"], 980);
+        assert_eq!(body["lag"], 7);
+
+        // Indexed ahead of what the cluster last said isn't negative lag
+        api.db_connection
+            .lock()
+            .await
+            .query(&DbQuery::InsertBlock(block("d", 1015)))
+            .await
+            .unwrap();
+        let (_, body) = api.get("/chain/head").await;
+        assert_eq!(body["lag"], 0);
+    }
+}
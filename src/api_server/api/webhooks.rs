@@ -0,0 +1,145 @@
+use crate::{
+    api_server::{
+        admin::Admin,
+        errors::ApiServerError,
+        extract::{validate_pubkey, Json, Path},
+        state::ApiServerState,
+    },
+    webhooks::{
+        registry::{DeadLetter, Delivery, DeliveryAttempt, Webhook, WebhookRegistration},
+        target::check_target,
+    },
+};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post, Router},
+};
+use reqwest::Url;
+use serde::Serialize;
+
+//
+// Admin endpoints for registering webhooks and seeing how deliveries to them
+// went, all of which need the admin token. Nested under /api/v1/webhooks.
+//
+
+pub fn routes() -> Router<ApiServerState> {
+    Router::new()
+        .route("/", get(get_webhooks).post(register_webhook))
+        .route("/:id", get(get_webhook).delete(unregister_webhook))
+        .route("/:id/deliveries", get(get_deliveries))
+        .route("/dead-letters", get(get_dead_letters))
+        .route("/dead-letters/:id/redeliver", post(redeliver))
+}
+
+// The secret is only ever handed back when registering
+#[derive(Serialize)]
+struct RegisteredWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    secret: String,
+}
+
+async fn register_webhook(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Json(registration): Json<WebhookRegistration>,
+) -> Result<(StatusCode, Json<RegisteredWebhook>), ApiServerError> {
+    let url = Url::parse(&registration.url).map_err(|_| {
+        ApiServerError::BadRequest(format!("Invalid webhook url: {}", registration.url))
+    })?;
+    check_target(&url, state.webhooks().allow_private_targets())
+        .await
+        .map_err(ApiServerError::BadRequest)?;
+
+    if registration.secret.is_empty() {
+        return Err(ApiServerError::BadRequest(
+            "A secret is required to sign payloads with".to_string(),
+        ));
+    }
+
+    let filter = &registration.filter;
+    if filter.addresses.is_empty() && filter.programs.is_empty() {
+        return Err(ApiServerError::BadRequest(
+            "The filter needs at least one address or program".to_string(),
+        ));
+    }
+    filter
+        .addresses
+        .iter()
+        .chain(&filter.programs)
+        .try_for_each(|address| validate_pubkey(address))?;
+
+    let webhook = state.webhooks().register(registration).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RegisteredWebhook {
+            secret: webhook.secret.clone(),
+            webhook,
+        }),
+    ))
+}
+
+async fn get_webhooks(_: Admin, State(state): State<ApiServerState>) -> Json<Vec<Webhook>> {
+    Json(state.webhooks().webhooks().await)
+}
+
+async fn get_webhook(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Webhook>, ApiServerError> {
+    state
+        .webhooks()
+        .webhook(id)
+        .await
+        .map(Json)
+        .ok_or_else(webhook_not_found)
+}
+
+async fn unregister_webhook(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Webhook>, ApiServerError> {
+    state
+        .webhooks()
+        .unregister(id)
+        .await
+        .map(Json)
+        .ok_or_else(webhook_not_found)
+}
+
+async fn get_deliveries(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<DeliveryAttempt>>, ApiServerError> {
+    let webhooks = state.webhooks();
+    webhooks.webhook(id).await.ok_or_else(webhook_not_found)?;
+
+    Ok(Json(webhooks.deliveries(id).await))
+}
+
+async fn get_dead_letters(_: Admin, State(state): State<ApiServerState>) -> Json<Vec<DeadLetter>> {
+    Json(state.webhooks().dead_letters().await)
+}
+
+async fn redeliver(
+    _: Admin,
+    State(state): State<ApiServerState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<Delivery>), ApiServerError> {
+    state
+        .webhooks()
+        .redeliver(&id)
+        .await
+        .map(|delivery| (StatusCode::ACCEPTED, Json(delivery)))
+        .ok_or_else(|| ApiServerError::NotFound("Dead letter not found".to_string()))
+}
+
+fn webhook_not_found() -> ApiServerError {
+    ApiServerError::NotFound("Webhook not found".to_string())
+}
@@ -1,37 +1,94 @@
-use crate::database::errors::DbError;
+use crate::{api_server::request_id::current_request_id, database::errors::DbError};
 
-use axum::{body::Body, http::Response, response::IntoResponse};
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ApiServerError {
-    #[error("Bad Request")]
-    BadRequest,
-    #[error("Internal Server Error")]
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("Missing or invalid admin token")]
+    Unauthorized,
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("Service unavailable")]
+    ServiceUnavailable,
+    #[error("Internal server error")]
     InternalServerError,
 }
 
+impl ApiServerError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiServerError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiServerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiServerError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiServerError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiServerError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ApiServerError::BadRequest(_) => "bad_request",
+            ApiServerError::Unauthorized => "unauthorized",
+            ApiServerError::Forbidden(_) => "forbidden",
+            ApiServerError::NotFound(_) => "not_found",
+            ApiServerError::ServiceUnavailable => "service_unavailable",
+            ApiServerError::InternalServerError => "internal_server_error",
+        }
+    }
+}
+
 impl IntoResponse for ApiServerError {
-    fn into_response(self) -> Response<Body> {
-        let status = match self {
-            ApiServerError::BadRequest => 400,
-            ApiServerError::InternalServerError => 500,
-        };
-
-        Response::builder()
-            .status(status)
-            .body(self.to_string().into())
-            .expect("Error building response")
+    fn into_response(self) -> Response {
+        let body = json!({
+            "code": self.code(),
+            "message": self.to_string(),
+            "request_id": current_request_id(),
+        });
+
+        (self.status(), Json(body)).into_response()
     }
 }
 
 impl From<DbError> for ApiServerError {
     fn from(error: DbError) -> Self {
         match error {
-            DbError::BlockNotFound | DbError::AccountNotFound | DbError::TransactionNotFound => {
-                ApiServerError::InternalServerError
-            }
-            _ => ApiServerError::BadRequest,
+            DbError::BlockNotFound
+            | DbError::SlotNotFound
+            | DbError::AccountNotFound
+            | DbError::TransactionNotFound
+            | DbError::VoteNotFound => ApiServerError::NotFound(error.to_string()),
+            DbError::Unavailable => ApiServerError::ServiceUnavailable,
+            DbError::_Other(_) => ApiServerError::InternalServerError,
         }
     }
 }
+
+impl From<PathRejection> for ApiServerError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiServerError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiServerError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiServerError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiServerError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiServerError::BadRequest(rejection.body_text())
+    }
+}
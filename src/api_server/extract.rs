@@ -0,0 +1,50 @@
+use crate::api_server::errors::ApiServerError;
+
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+//
+// Axum's extractors, but rejecting bad input with our own JSON errors rather
+// than plain text
+//
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiServerError))]
+pub struct Path<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiServerError))]
+pub struct Query<T>(pub T);
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiServerError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+// Addresses and blockhashes are 32 bytes, signatures 64
+pub fn validate_pubkey(id: &str) -> Result<(), ApiServerError> {
+    validate_base58(id, 32, "address")
+}
+
+pub fn validate_blockhash(id: &str) -> Result<(), ApiServerError> {
+    validate_base58(id, 32, "blockhash")
+}
+
+pub fn validate_signature(id: &str) -> Result<(), ApiServerError> {
+    validate_base58(id, 64, "signature")
+}
+
+fn validate_base58(id: &str, length: usize, kind: &str) -> Result<(), ApiServerError> {
+    match bs58::decode(id).into_vec() {
+        Ok(bytes) if bytes.len() == length => Ok(()),
+        _ => Err(ApiServerError::BadRequest(format!("Invalid {kind}: {id}"))),
+    }
+}
@@ -1,4 +1,7 @@
+pub mod admin;
 pub mod api;
 pub mod errors;
+pub mod extract;
+pub mod request_id;
 pub mod server;
 pub mod state;
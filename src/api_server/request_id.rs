@@ -0,0 +1,63 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+//
+// Every request gets an id, either the one the client sent in x-request-id or
+// a fresh one, which is echoed back in the response headers and in error
+// bodies so failures can be matched up with the logs.
+//
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Client supplied ids longer than this are replaced
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH)
+        .map(String::from)
+        .unwrap_or_else(new_request_id);
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+
+    if let Ok(id) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, id);
+    }
+
+    response
+}
+
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+// Unique within this process, and unlikely to repeat across restarts
+fn new_request_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    format!(
+        "{now:x}-{:x}",
+        NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
@@ -1,6 +1,14 @@
 use crate::{
-    api_server::{api::v1, errors::ApiServerError, state::ApiServerState},
+    api_server::{
+        api::{graphql, rpc, v1},
+        errors::ApiServerError,
+        request_id::{request_id, REQUEST_ID_HEADER},
+        state::ApiServerState,
+    },
     database::server::DbConnection,
+    events::bus::EventBus,
+    indexer::{cluster::ClusterState, filter::FilterState},
+    webhooks::registry::WebhookRegistry,
 };
 
 use serde_json::json;
@@ -8,7 +16,8 @@ use std::sync::Arc;
 use tower_http::cors::{AllowMethods, Any, CorsLayer};
 
 use axum::{
-    http::Method,
+    http::{header::CONTENT_TYPE, Method},
+    middleware,
     response::IntoResponse,
     routing::{get, Router},
     Json,
@@ -26,14 +35,32 @@ const CURRENT_API_VERSION: &str = "v1";
 pub struct ApiServer {
     task: Option<JoinHandle<()>>,
     db_connection: Arc<Mutex<DbConnection>>,
+    filter: Arc<FilterState>,
+    cluster: Arc<ClusterState>,
+    events: Arc<EventBus>,
+    webhooks: Arc<WebhookRegistry>,
+    admin_token: Option<String>,
     tx: Sender<()>,
 }
 
 impl ApiServer {
-    pub fn new(db_connection: Arc<Mutex<DbConnection>>, tx: Sender<()>) -> Self {
+    pub fn new(
+        db_connection: Arc<Mutex<DbConnection>>,
+        filter: Arc<FilterState>,
+        cluster: Arc<ClusterState>,
+        events: Arc<EventBus>,
+        webhooks: Arc<WebhookRegistry>,
+        admin_token: Option<String>,
+        tx: Sender<()>,
+    ) -> Self {
         ApiServer {
             task: None,
             db_connection,
+            filter,
+            cluster,
+            events,
+            webhooks,
+            admin_token,
             tx,
         }
     }
@@ -49,12 +76,25 @@ impl ApiServer {
         let routes = Router::new()
             .route("/", get(server_root))
             .nest("/api/v1", v1::routes())
-            .fallback(bad_request)
-            .with_state(ApiServerState::new(self.db_connection.clone()))
+            .nest("/rpc", rpc::routes())
+            .nest("/graphql", graphql::routes())
+            .fallback(not_found)
+            .with_state(ApiServerState::new(
+                self.db_connection.clone(),
+                self.filter.clone(),
+                self.cluster.clone(),
+                self.events.clone(),
+                self.webhooks.clone(),
+                self.admin_token.clone(),
+            ))
+            .layer(middleware::from_fn(request_id))
+            // Browsers on other origins can read and query, but can't send the
+            // admin token or the methods that only admin endpoints take
             .layer(
                 CorsLayer::new()
                     .allow_methods(AllowMethods::list([Method::GET, Method::POST]))
-                    .allow_headers(Any)
+                    .allow_headers([CONTENT_TYPE, REQUEST_ID_HEADER])
+                    .expose_headers([REQUEST_ID_HEADER])
                     .allow_origin(Any),
             );
 
@@ -108,6 +148,6 @@ async fn server_root() -> impl IntoResponse {
     }))
 }
 
-async fn bad_request() -> Result<(), ApiServerError> {
-    Err(ApiServerError::BadRequest)
+async fn not_found() -> Result<(), ApiServerError> {
+    Err(ApiServerError::NotFound("Unknown endpoint".to_string()))
 }
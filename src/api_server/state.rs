@@ -1,19 +1,74 @@
-use crate::database::server::DbConnection;
+use crate::{
+    database::{errors::DbError, server::DbConnection},
+    events::bus::EventBus,
+    indexer::{cluster::ClusterState, filter::FilterState},
+    webhooks::registry::WebhookRegistry,
+};
 
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{Mutex, OwnedMutexGuard},
+    time::timeout,
+};
+
+// How long a request waits for the store before giving up on it
+const DB_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Clone)]
 pub struct ApiServerState {
     db_connection: Arc<Mutex<DbConnection>>,
+    filter: Arc<FilterState>,
+    cluster: Arc<ClusterState>,
+    events: Arc<EventBus>,
+    webhooks: Arc<WebhookRegistry>,
+    admin_token: Option<Arc<str>>,
 }
 
 impl ApiServerState {
-    pub fn new(db_connection: Arc<Mutex<DbConnection>>) -> Self {
-        ApiServerState { db_connection }
+    pub fn new(
+        db_connection: Arc<Mutex<DbConnection>>,
+        filter: Arc<FilterState>,
+        cluster: Arc<ClusterState>,
+        events: Arc<EventBus>,
+        webhooks: Arc<WebhookRegistry>,
+        admin_token: Option<String>,
+    ) -> Self {
+        ApiServerState {
+            db_connection,
+            filter,
+            cluster,
+            events,
+            webhooks,
+            admin_token: admin_token.map(Arc::from),
+        }
+    }
+
+    // The indexer holds the store while it writes a block, which can take a
+    // while for a big one. Rather than queue up behind it indefinitely,
+    // requests give up and report the store as unavailable
+    pub async fn db_connection(&self) -> Result<OwnedMutexGuard<DbConnection>, DbError> {
+        timeout(DB_LOCK_TIMEOUT, self.db_connection.clone().lock_owned())
+            .await
+            .map_err(|_| DbError::Unavailable)
+    }
+
+    pub fn filter(&self) -> Arc<FilterState> {
+        self.filter.clone()
+    }
+
+    pub fn cluster(&self) -> Arc<ClusterState> {
+        self.cluster.clone()
+    }
+
+    pub fn events(&self) -> Arc<EventBus> {
+        self.events.clone()
+    }
+
+    pub fn webhooks(&self) -> Arc<WebhookRegistry> {
+        self.webhooks.clone()
     }
 
-    pub fn db_connection(&self) -> Arc<Mutex<DbConnection>> {
-        self.db_connection.clone()
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
     }
 }
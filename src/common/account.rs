@@ -1,13 +1,66 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
 use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccount;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Account {
     pub id: String,
     pub balance: u64,
+    // Slot the balance is from
+    pub slot: u64,
+    // Only known for accounts watched directly, rather than seen in passing in
+    // a transaction
+    pub state: Option<AccountState>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountState {
+    pub owner: String,
+    // Base64 encoded
+    pub data: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    // Slot of the last write we saw
+    pub slot: u64,
 }
 
 impl Account {
-    pub fn new(id: String, balance: u64) -> Self {
-        Account { id, balance }
+    pub fn new(id: String, balance: u64, slot: u64) -> Self {
+        Account {
+            id,
+            balance,
+            slot,
+            state: None,
+        }
+    }
+
+    pub fn from_ui_account(id: String, account: &UiAccount, slot: u64) -> Option<Self> {
+        Some(Account {
+            id,
+            balance: account.lamports,
+            slot,
+            state: Some(AccountState {
+                owner: account.owner.clone(),
+                data: BASE64_STANDARD.encode(account.data.decode()?),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                slot,
+            }),
+        })
+    }
+
+    pub fn from_sdk_account(id: String, account: &solana_sdk::account::Account, slot: u64) -> Self {
+        Account {
+            id,
+            balance: account.lamports,
+            slot,
+            state: Some(AccountState {
+                owner: account.owner.to_string(),
+                data: BASE64_STANDARD.encode(&account.data),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                slot,
+            }),
+        }
     }
 }
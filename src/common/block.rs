@@ -1,29 +1,155 @@
+use crate::common::{reward::Reward, transaction::is_vote};
+
 use serde::{Deserialize, Serialize};
-use solana_transaction_status::UiConfirmedBlock;
+use solana_transaction_status::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiConfirmedBlock, UiMessage,
+};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub id: String,
     pub previous_blockhash: String,
     pub slot: u64,
+    pub parent_slot: u64,
     pub block_time: Option<i64>,
     pub block_height: Option<u64>,
+    pub rewards: Vec<Reward>,
+    pub transaction_count: usize,
+    pub vote_transaction_count: usize,
 }
 
-impl From<UiConfirmedBlock> for Block {
-    fn from(block: UiConfirmedBlock) -> Self {
+impl Block {
+    // The slot isn't part of the block itself, so it comes from the source
+    pub fn new(slot: u64, block: UiConfirmedBlock) -> Self {
+        // Counted here rather than from what's stored, as votes may not be
+        let transactions = block.transactions.unwrap_or_default();
+
         Block {
             id: block.blockhash.to_string(),
             previous_blockhash: block.previous_blockhash.to_string(),
-            slot: block.parent_slot,
+            slot,
+            parent_slot: block.parent_slot,
             block_time: block.block_time,
             block_height: block.block_height,
+            rewards: block
+                .rewards
+                .unwrap_or_default()
+                .into_iter()
+                .map(Reward::from)
+                .collect(),
+            transaction_count: transactions.len(),
+            vote_transaction_count: transactions
+                .iter()
+                .filter(|transaction| is_vote_transaction(transaction))
+                .count(),
         }
     }
 }
 
-impl From<&UiConfirmedBlock> for Block {
-    fn from(block: &UiConfirmedBlock) -> Self {
-        block.into()
+fn is_vote_transaction(transaction: &EncodedTransactionWithStatusMeta) -> bool {
+    let EncodedTransaction::Json(transaction) = &transaction.transaction else {
+        return false;
+    };
+    let UiMessage::Raw(message) = &transaction.message else {
+        return false;
+    };
+
+    is_vote(message.instructions.iter().filter_map(|instruction| {
+        message
+            .account_keys
+            .get(instruction.program_id_index as usize)
+            .map(String::as_str)
+    }))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p25: u64,
+    pub p50: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    // Nearest-rank percentiles, so every value is one that actually occurred
+    pub fn new(mut values: Vec<u64>) -> Self {
+        if values.is_empty() {
+            return Percentiles::default();
+        }
+
+        values.sort_unstable();
+        let rank = |percentile: usize| values[(values.len() * percentile).div_ceil(100).max(1) - 1];
+
+        Percentiles {
+            p25: rank(25),
+            p50: rank(50),
+            p75: rank(75),
+            p90: rank(90),
+            p99: rank(99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+//
+// Aggregates over the transactions in a block. Votes are counted but left out
+// of the fee percentiles, as they never pay a priority fee and would otherwise
+// drown everything else out. Depending on the vote policy, not every
+// transaction in the block is indexed, so the totals only cover those that were.
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub id: String,
+    pub slot: u64,
+    pub transaction_count: usize,
+    pub vote_transaction_count: usize,
+    pub indexed_transaction_count: usize,
+    pub failed_transaction_count: usize,
+    pub total_compute_units: u64,
+    pub total_fees: u64,
+    pub total_priority_fees: u64,
+    pub priority_fee: Percentiles,
+    pub compute_unit_price: Percentiles,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percentiles(p25: u64, p50: u64, p75: u64, p90: u64, p99: u64, max: u64) -> Percentiles {
+        Percentiles {
+            p25,
+            p50,
+            p75,
+            p90,
+            p99,
+            max,
+        }
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        assert_eq!(Percentiles::new(Vec::new()), Percentiles::default());
+        assert_eq!(
+            Percentiles::new(vec![42]),
+            percentiles(42, 42, 42, 42, 42, 42)
+        );
+
+        // The rank is the percentage of the count, rounded up
+        assert_eq!(
+            Percentiles::new(vec![40, 10, 30, 20]),
+            percentiles(10, 20, 30, 40, 40, 40)
+        );
+        assert_eq!(
+            Percentiles::new(vec![50, 10, 40, 20, 30]),
+            percentiles(20, 30, 40, 50, 50, 50)
+        );
+        assert_eq!(
+            Percentiles::new((1..=100).rev().collect()),
+            percentiles(25, 50, 75, 90, 99, 100)
+        );
     }
 }
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+// What the runtime allows when a transaction doesn't set its own limit
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ComputeBudgetInstruction {
+    RequestHeapFrame { bytes: u32 },
+    SetComputeUnitLimit { units: u32 },
+    SetComputeUnitPrice { micro_lamports: u64 },
+    SetLoadedAccountsDataSizeLimit { bytes: u32 },
+}
+
+impl ComputeBudgetInstruction {
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let (tag, rest) = data.split_first()?;
+
+        let u32_arg = || Some(u32::from_le_bytes(rest.get(..4)?.try_into().ok()?));
+        let u64_arg = || Some(u64::from_le_bytes(rest.get(..8)?.try_into().ok()?));
+
+        // Tag 0 was the long-deprecated RequestUnits
+        match tag {
+            1 => Some(ComputeBudgetInstruction::RequestHeapFrame { bytes: u32_arg()? }),
+            2 => Some(ComputeBudgetInstruction::SetComputeUnitLimit { units: u32_arg()? }),
+            3 => Some(ComputeBudgetInstruction::SetComputeUnitPrice {
+                micro_lamports: u64_arg()?,
+            }),
+            4 => {
+                Some(ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes: u32_arg()? })
+            }
+            _ => None,
+        }
+    }
+}
+
+// The prioritization fee is the compute unit price times the limit requested,
+// not the units actually used, rounded up to whole lamports. Like the runtime,
+// an absurd price saturates rather than wrapping.
+pub fn prioritization_fee(compute_unit_price: u64, compute_unit_limit: u64) -> u64 {
+    let micro_lamports = compute_unit_price as u128 * compute_unit_limit as u128;
+    micro_lamports
+        .div_ceil(MICRO_LAMPORTS_PER_LAMPORT)
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fees_round_up_to_whole_lamports() {
+        assert_eq!(prioritization_fee(0, 200_000), 0);
+        assert_eq!(prioritization_fee(1_000_000, 200_000), 200_000);
+        assert_eq!(prioritization_fee(1_500, 300_000), 450);
+        // 0.2, 0.999999 and 1.000001 lamports
+        assert_eq!(prioritization_fee(1, 200_000), 1);
+        assert_eq!(prioritization_fee(3, 333_333), 1);
+        assert_eq!(prioritization_fee(1_000_001, 1), 2);
+        assert_eq!(
+            prioritization_fee(u64::MAX, MAX_COMPUTE_UNIT_LIMIT),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn unpacks_each_instruction() {
+        assert_eq!(
+            ComputeBudgetInstruction::unpack(&[&[1][..], &(256u32 * 1024).to_le_bytes()].concat()),
+            Some(ComputeBudgetInstruction::RequestHeapFrame { bytes: 256 * 1024 })
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::unpack(&[&[2][..], &300_000u32.to_le_bytes()].concat()),
+            Some(ComputeBudgetInstruction::SetComputeUnitLimit { units: 300_000 })
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::unpack(&[&[3][..], &25_000u64.to_le_bytes()].concat()),
+            Some(ComputeBudgetInstruction::SetComputeUnitPrice {
+                micro_lamports: 25_000
+            })
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::unpack(&[&[4][..], &65_536u32.to_le_bytes()].concat()),
+            Some(ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes: 65_536 })
+        );
+
+        // The deprecated RequestUnits, and arguments cut short
+        assert_eq!(ComputeBudgetInstruction::unpack(&[0; 9]), None);
+        assert_eq!(ComputeBudgetInstruction::unpack(&[3, 1, 2, 3]), None);
+        assert_eq!(ComputeBudgetInstruction::unpack(&[]), None);
+    }
+}
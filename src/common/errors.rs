@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DecodeError {
+    #[error("Missing transaction meta")]
+    MissingMeta,
+    #[error("Transaction isn't json encoded")]
+    UnsupportedEncoding,
+    #[error("Transaction message is parsed rather than raw")]
+    ParsedMessage,
+    #[error("Transaction has no signatures")]
+    MissingSignature,
+    #[error("Instruction references account {0}, which the transaction doesn't have")]
+    MissingAccount(u8),
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub program_id: String,
+    pub name: String,
+    pub data: Value,
+}
+
+//
+// A decoded instruction or event found by name, along with the transaction it
+// came from
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgramActivity {
+    pub signature: String,
+    pub slot: u64,
+    pub name: String,
+    pub data: Value,
+}
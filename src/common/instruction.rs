@@ -0,0 +1,182 @@
+use crate::common::{errors::DecodeError, native::NativeInstruction};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_transaction_status::{
+    UiCompiledInstruction, UiInnerInstructions, UiInstruction, UiParsedInstruction,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: String,
+    pub decoded: Option<DecodedInstruction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum DecodedInstruction {
+    Native(NativeInstruction),
+    // Decoded from an Anchor IDL, so only as typed as the IDL itself
+    Parsed {
+        program: String,
+        #[serde(rename = "type")]
+        instruction_type: String,
+        info: Value,
+    },
+    Raw {
+        data: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InnerInstruction {
+    // Index of the top-level instruction that made this CPI
+    pub index: u8,
+    pub stack_height: Option<u32>,
+    #[serde(flatten)]
+    pub instruction: Instruction,
+}
+
+impl DecodedInstruction {
+    // The instruction's name and its decoded accounts and arguments
+    pub fn name_and_info(&self) -> Option<(String, Value)> {
+        match self {
+            DecodedInstruction::Native(native) => {
+                let Value::Object(mut object) = serde_json::to_value(native).ok()? else {
+                    return None;
+                };
+                let Value::String(name) = object.remove("type")? else {
+                    return None;
+                };
+                Some((name, object.remove("info").unwrap_or_default()))
+            }
+            DecodedInstruction::Parsed {
+                instruction_type,
+                info,
+                ..
+            } => Some((instruction_type.clone(), info.clone())),
+            DecodedInstruction::Raw { .. } => None,
+        }
+    }
+}
+
+impl Instruction {
+    pub fn new(
+        instruction: &UiCompiledInstruction,
+        accounts: &[String],
+    ) -> Result<Self, DecodeError> {
+        Ok(Instruction {
+            program_id: resolve(accounts, instruction.program_id_index)?,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|index| resolve(accounts, *index))
+                .collect::<Result<_, _>>()?,
+            data: instruction.data.clone(),
+            // Filled in by the indexer's decoder registry
+            decoded: None,
+        })
+    }
+}
+
+impl InnerInstruction {
+    pub fn from_meta(
+        inner_instructions: &[UiInnerInstructions],
+        accounts: &[String],
+    ) -> Result<Vec<InnerInstruction>, DecodeError> {
+        inner_instructions
+            .iter()
+            .flat_map(|inner| {
+                inner
+                    .instructions
+                    .iter()
+                    .filter_map(move |instruction| match instruction {
+                        UiInstruction::Compiled(compiled) => {
+                            Some(Instruction::new(compiled, accounts).map(|instruction| {
+                                InnerInstruction {
+                                    index: inner.index,
+                                    stack_height: compiled.stack_height,
+                                    instruction,
+                                }
+                            }))
+                        }
+                        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded)) => {
+                            Some(Ok(InnerInstruction {
+                                index: inner.index,
+                                stack_height: decoded.stack_height,
+                                instruction: Instruction {
+                                    program_id: decoded.program_id.clone(),
+                                    accounts: decoded.accounts.clone(),
+                                    data: decoded.data.clone(),
+                                    decoded: None,
+                                },
+                            }))
+                        }
+                        // Only requested with the json encoding, so fully
+                        // parsed instructions should never turn up here. If
+                        // one does it can't be stored as raw data, so say so
+                        // rather than losing it quietly
+                        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+                            println!(
+                                "Dropping parsed inner instruction {} of {}: {}",
+                                inner.index, parsed.program_id, parsed.program
+                            );
+                            None
+                        }
+                    })
+            })
+            .collect()
+    }
+}
+
+// An index past the end means the transaction's account list doesn't match
+// its instructions, which is better rejected than stored with blank keys
+fn resolve(accounts: &[String], index: u8) -> Result<String, DecodeError> {
+    accounts
+        .get(index as usize)
+        .cloned()
+        .ok_or(DecodeError::MissingAccount(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(program_id_index: u8, accounts: Vec<u8>) -> UiCompiledInstruction {
+        UiCompiledInstruction {
+            program_id_index,
+            accounts,
+            data: "3Bxs4h24hBtQy9rw".to_string(),
+            stack_height: None,
+        }
+    }
+
+    #[test]
+    fn rejects_missing_accounts() {
+        let accounts = vec!["payer".to_string(), "program".to_string()];
+
+        let instruction = Instruction::new(&compiled(1, vec![0]), &accounts).unwrap();
+        assert_eq!(instruction.program_id, "program");
+        assert_eq!(instruction.accounts, vec!["payer".to_string()]);
+
+        assert_eq!(
+            Instruction::new(&compiled(2, vec![0]), &accounts),
+            Err(DecodeError::MissingAccount(2))
+        );
+        assert_eq!(
+            Instruction::new(&compiled(1, vec![0, 5]), &accounts),
+            Err(DecodeError::MissingAccount(5))
+        );
+
+        let inner = UiInnerInstructions {
+            index: 0,
+            instructions: vec![UiInstruction::Compiled(compiled(1, vec![7]))],
+        };
+        assert_eq!(
+            InnerInstruction::from_meta(&[inner], &accounts),
+            Err(DecodeError::MissingAccount(7))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//
+// Helpers for picking apart `meta.log_messages`. The runtime brackets each
+// program's output with "Program <id> invoke [depth]" and "Program <id>
+// success" / "Program <id> failed: ..." lines, so tracking those as a stack
+// tells us which program emitted each "Program log:" or "Program data:" line.
+//
+
+const INVOKE_SUFFIX: &str = " invoke [";
+const LOG_PREFIX: &str = "Program log: ";
+const DATA_PREFIX: &str = "Program data: ";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProgramOutput<'a> {
+    Log(&'a str),
+    Data(&'a str),
+}
+
+impl<'a> ProgramOutput<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            ProgramOutput::Log(line) | ProgramOutput::Data(line) => line,
+        }
+    }
+}
+
+pub fn program_output<'a>(logs: &'a [String], program_id: &str) -> Vec<ProgramOutput<'a>> {
+    let mut stack: Vec<&str> = Vec::new();
+    let mut output = Vec::new();
+
+    for line in logs {
+        if let Some(rest) = line.strip_prefix(LOG_PREFIX) {
+            if stack.last() == Some(&program_id) {
+                output.push(ProgramOutput::Log(rest));
+            }
+        } else if let Some(rest) = line.strip_prefix(DATA_PREFIX) {
+            if stack.last() == Some(&program_id) {
+                output.push(ProgramOutput::Data(rest));
+            }
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            if let Some((id, _)) = rest.split_once(INVOKE_SUFFIX) {
+                stack.push(id);
+            } else if rest.ends_with(" success") || rest.contains(" failed: ") {
+                stack.pop();
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTER: &str = "Outer111111111111111111111111111111111111111";
+    const INNER: &str = "Inner111111111111111111111111111111111111111";
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn attributes_output_to_the_innermost_program() {
+        let logs = logs(&[
+            &format!("Program {OUTER} invoke [1]"),
+            "Program log: before",
+            &format!("Program {INNER} invoke [2]"),
+            "Program log: inside",
+            "Program data: aW5zaWRl",
+            &format!("Program {INNER} consumed 1200 of 198000 compute units"),
+            &format!("Program {INNER} success"),
+            "Program log: after",
+            "Program data: YWZ0ZXI=",
+            &format!("Program {OUTER} consumed 5000 of 200000 compute units"),
+            &format!("Program {OUTER} success"),
+            "Program log: nobody's",
+        ]);
+
+        assert_eq!(
+            program_output(&logs, OUTER),
+            [
+                ProgramOutput::Log("before"),
+                ProgramOutput::Log("after"),
+                ProgramOutput::Data("YWZ0ZXI="),
+            ]
+        );
+        assert_eq!(
+            program_output(&logs, INNER),
+            [
+                ProgramOutput::Log("inside"),
+                ProgramOutput::Data("aW5zaWRl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn failures_unwind_the_stack() {
+        let logs = logs(&[
+            &format!("Program {OUTER} invoke [1]"),
+            &format!("Program {INNER} invoke [2]"),
+            "Program log: giving up",
+            &format!("Program {INNER} failed: custom program error: 0x1"),
+            "Program log: caught it",
+            &format!("Program {OUTER} failed: custom program error: 0x1"),
+            &format!("Program {INNER} invoke [1]"),
+            "Program log: next instruction",
+            &format!("Program {INNER} success"),
+        ]);
+
+        assert_eq!(
+            program_output(&logs, OUTER),
+            [ProgramOutput::Log("caught it")]
+        );
+        assert_eq!(
+            program_output(&logs, INNER)
+                .iter()
+                .map(ProgramOutput::as_str)
+                .collect::<Vec<_>>(),
+            ["giving up", "next instruction"]
+        );
+    }
+}
@@ -1,3 +1,12 @@
 pub mod account;
 pub mod block;
+pub mod compute_budget;
+pub mod errors;
+pub mod event;
+pub mod instruction;
+pub mod logs;
+pub mod native;
+pub mod reward;
+pub mod token;
 pub mod transaction;
+pub mod vote;
@@ -0,0 +1,798 @@
+use crate::common::compute_budget::ComputeBudgetInstruction;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_account_decoder::parse_token::UiTokenAmount;
+
+//
+// Typed forms of the native and SPL program instructions. They serialize the
+// same way as the SDK's jsonParsed output, {"program", "type", "info"}, with
+// accounts and pubkeys as base58 strings and token amounts as strings so they
+// survive JSON number precision. Optional fields are left out when unset, as
+// the SDK does.
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "program", rename_all = "kebab-case")]
+pub enum NativeInstruction {
+    System(SystemInstruction),
+    Stake(StakeInstruction),
+    Vote(VoteInstruction),
+    AddressLookupTable(AddressLookupTableInstruction),
+    SplMemo(MemoInstruction),
+    SplToken(TokenInstruction),
+    SplAssociatedTokenAccount(AssociatedTokenInstruction),
+    ComputeBudget(ComputeBudgetInstruction),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SystemInstruction {
+    CreateAccount {
+        source: String,
+        new_account: String,
+        lamports: u64,
+        space: u64,
+        owner: String,
+    },
+    Assign {
+        account: String,
+        owner: String,
+    },
+    Transfer {
+        source: String,
+        destination: String,
+        lamports: u64,
+    },
+    CreateAccountWithSeed {
+        source: String,
+        new_account: String,
+        base: String,
+        seed: String,
+        lamports: u64,
+        space: u64,
+        owner: String,
+    },
+    #[serde(rename = "advanceNonce")]
+    AdvanceNonceAccount {
+        nonce_account: String,
+        recent_blockhashes_sysvar: String,
+        nonce_authority: String,
+    },
+    #[serde(rename = "withdrawFromNonce")]
+    WithdrawNonceAccount {
+        nonce_account: String,
+        destination: String,
+        recent_blockhashes_sysvar: String,
+        rent_sysvar: String,
+        nonce_authority: String,
+        lamports: u64,
+    },
+    #[serde(rename = "initializeNonce")]
+    InitializeNonceAccount {
+        nonce_account: String,
+        recent_blockhashes_sysvar: String,
+        rent_sysvar: String,
+        nonce_authority: String,
+    },
+    #[serde(rename = "authorizeNonce")]
+    AuthorizeNonceAccount {
+        nonce_account: String,
+        nonce_authority: String,
+        new_authorized: String,
+    },
+    #[serde(rename = "upgradeNonce")]
+    UpgradeNonceAccount {
+        nonce_account: String,
+    },
+    Allocate {
+        account: String,
+        space: u64,
+    },
+    AllocateWithSeed {
+        account: String,
+        base: String,
+        seed: String,
+        space: u64,
+        owner: String,
+    },
+    AssignWithSeed {
+        account: String,
+        base: String,
+        seed: String,
+        owner: String,
+    },
+    TransferWithSeed {
+        source: String,
+        source_base: String,
+        destination: String,
+        lamports: u64,
+        source_seed: String,
+        source_owner: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StakeAuthority {
+    Staker,
+    Withdrawer,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StakeAuthorized {
+    pub staker: String,
+    pub withdrawer: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lockup {
+    pub unix_timestamp: i64,
+    pub epoch: u64,
+    pub custodian: String,
+}
+
+// Only the parts of the lockup being changed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockupChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unix_timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custodian: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum StakeInstruction {
+    Initialize {
+        stake_account: String,
+        rent_sysvar: String,
+        authorized: StakeAuthorized,
+        lockup: Lockup,
+    },
+    Authorize {
+        stake_account: String,
+        clock_sysvar: String,
+        authority: String,
+        new_authority: String,
+        authority_type: StakeAuthority,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custodian: Option<String>,
+    },
+    #[serde(rename = "delegate")]
+    DelegateStake {
+        stake_account: String,
+        vote_account: String,
+        clock_sysvar: String,
+        stake_history_sysvar: String,
+        stake_config_account: String,
+        stake_authority: String,
+    },
+    Split {
+        stake_account: String,
+        new_split_account: String,
+        stake_authority: String,
+        lamports: u64,
+    },
+    Withdraw {
+        stake_account: String,
+        destination: String,
+        clock_sysvar: String,
+        stake_history_sysvar: String,
+        withdraw_authority: String,
+        lamports: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custodian: Option<String>,
+    },
+    Deactivate {
+        stake_account: String,
+        clock_sysvar: String,
+        stake_authority: String,
+    },
+    SetLockup {
+        stake_account: String,
+        custodian: String,
+        lockup: LockupChange,
+    },
+    Merge {
+        destination: String,
+        source: String,
+        clock_sysvar: String,
+        stake_history_sysvar: String,
+        stake_authority: String,
+    },
+    AuthorizeWithSeed {
+        stake_account: String,
+        authority_base: String,
+        new_authorized: String,
+        authority_type: StakeAuthority,
+        authority_seed: String,
+        authority_owner: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        clock_sysvar: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custodian: Option<String>,
+    },
+    InitializeChecked {
+        stake_account: String,
+        rent_sysvar: String,
+        staker: String,
+        withdrawer: String,
+    },
+    AuthorizeChecked {
+        stake_account: String,
+        clock_sysvar: String,
+        authority: String,
+        new_authority: String,
+        authority_type: StakeAuthority,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custodian: Option<String>,
+    },
+    AuthorizeCheckedWithSeed {
+        stake_account: String,
+        authority_base: String,
+        clock_sysvar: String,
+        new_authorized: String,
+        authority_type: StakeAuthority,
+        authority_seed: String,
+        authority_owner: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custodian: Option<String>,
+    },
+    SetLockupChecked {
+        stake_account: String,
+        custodian: String,
+        lockup: LockupChange,
+    },
+    GetMinimumDelegation,
+    DeactivateDelinquent {
+        stake_account: String,
+        vote_account: String,
+        reference_vote_account: String,
+    },
+    Redelegate {
+        stake_account: String,
+        new_stake_account: String,
+        vote_account: String,
+        stake_config_account: String,
+        stake_authority: String,
+    },
+    MoveStake {
+        source: String,
+        destination: String,
+        stake_authority: String,
+        lamports: u64,
+    },
+    MoveLamports {
+        source: String,
+        destination: String,
+        stake_authority: String,
+        lamports: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VoteAuthority {
+    Voter,
+    Withdrawer,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vote {
+    pub slots: Vec<u64>,
+    pub hash: String,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lockout {
+    pub slot: u64,
+    pub confirmation_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoteStateUpdate {
+    pub lockouts: Vec<Lockout>,
+    pub root: Option<u64>,
+    pub hash: String,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TowerSync {
+    pub lockouts: Vec<Lockout>,
+    pub root: Option<u64>,
+    pub hash: String,
+    pub timestamp: Option<i64>,
+    pub block_id: String,
+}
+
+// The SDK names the vote state and tower sync instructions in lower case
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum VoteInstruction {
+    #[serde(rename = "initialize")]
+    InitializeAccount {
+        vote_account: String,
+        rent_sysvar: String,
+        clock_sysvar: String,
+        node: String,
+        authorized_voter: String,
+        authorized_withdrawer: String,
+        commission: u8,
+    },
+    Authorize {
+        vote_account: String,
+        clock_sysvar: String,
+        authority: String,
+        new_authority: String,
+        authority_type: VoteAuthority,
+    },
+    AuthorizeWithSeed {
+        vote_account: String,
+        clock_sysvar: String,
+        authority_base_key: String,
+        authority_owner: String,
+        authority_seed: String,
+        new_authority: String,
+        authority_type: VoteAuthority,
+    },
+    AuthorizeCheckedWithSeed {
+        vote_account: String,
+        clock_sysvar: String,
+        authority_base_key: String,
+        authority_owner: String,
+        authority_seed: String,
+        new_authority: String,
+        authority_type: VoteAuthority,
+    },
+    Vote {
+        vote_account: String,
+        slot_hashes_sysvar: String,
+        clock_sysvar: String,
+        vote_authority: String,
+        vote: Vote,
+    },
+    #[serde(rename = "updatevotestate")]
+    UpdateVoteState {
+        vote_account: String,
+        vote_authority: String,
+        vote_state_update: VoteStateUpdate,
+    },
+    #[serde(rename = "updatevotestateswitch")]
+    UpdateVoteStateSwitch {
+        vote_account: String,
+        vote_authority: String,
+        vote_state_update: VoteStateUpdate,
+        hash: String,
+    },
+    #[serde(rename = "compactupdatevotestate")]
+    CompactUpdateVoteState {
+        vote_account: String,
+        vote_authority: String,
+        vote_state_update: VoteStateUpdate,
+    },
+    #[serde(rename = "compactupdatevotestateswitch")]
+    CompactUpdateVoteStateSwitch {
+        vote_account: String,
+        vote_authority: String,
+        vote_state_update: VoteStateUpdate,
+        hash: String,
+    },
+    #[serde(rename = "towersync")]
+    TowerSync {
+        vote_account: String,
+        vote_authority: String,
+        tower_sync: TowerSync,
+    },
+    #[serde(rename = "towersyncswitch")]
+    TowerSyncSwitch {
+        vote_account: String,
+        vote_authority: String,
+        tower_sync: TowerSync,
+        hash: String,
+    },
+    Withdraw {
+        vote_account: String,
+        destination: String,
+        withdraw_authority: String,
+        lamports: u64,
+    },
+    UpdateValidatorIdentity {
+        vote_account: String,
+        new_validator_identity: String,
+        withdraw_authority: String,
+    },
+    UpdateCommission {
+        vote_account: String,
+        withdraw_authority: String,
+        commission: u8,
+    },
+    VoteSwitch {
+        vote_account: String,
+        slot_hashes_sysvar: String,
+        clock_sysvar: String,
+        vote_authority: String,
+        vote: Vote,
+        hash: String,
+    },
+    AuthorizeChecked {
+        vote_account: String,
+        clock_sysvar: String,
+        authority: String,
+        new_authority: String,
+        authority_type: VoteAuthority,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum AddressLookupTableInstruction {
+    CreateLookupTable {
+        lookup_table_account: String,
+        lookup_table_authority: String,
+        payer_account: String,
+        system_program: String,
+        recent_slot: u64,
+        bump_seed: u8,
+    },
+    FreezeLookupTable {
+        lookup_table_account: String,
+        lookup_table_authority: String,
+    },
+    ExtendLookupTable {
+        lookup_table_account: String,
+        lookup_table_authority: String,
+        new_addresses: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        payer_account: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        system_program: Option<String>,
+    },
+    DeactivateLookupTable {
+        lookup_table_account: String,
+        lookup_table_authority: String,
+    },
+    CloseLookupTable {
+        lookup_table_account: String,
+        lookup_table_authority: String,
+        recipient: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "info", rename_all = "camelCase")]
+pub enum MemoInstruction {
+    Memo(String),
+}
+
+//
+// Instructions signed by a multisig name the multisig account in place of the
+// single authority, followed by the signers, so each of those comes as a pair
+// of optional fields.
+//
+// Token-2022 extension instructions (transfer fees, confidential transfers,
+// metadata and so on) aren't modelled and keep the SDK's untyped info.
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenAuthority {
+    MintTokens,
+    FreezeAccount,
+    AccountOwner,
+    CloseAccount,
+    TransferFeeConfig,
+    WithheldWithdraw,
+    CloseMint,
+    InterestRate,
+    PermanentDelegate,
+    ConfidentialTransferMint,
+    TransferHookProgramId,
+    ConfidentialTransferFeeConfig,
+    MetadataPointer,
+    GroupPointer,
+    GroupMemberPointer,
+    ScaledUiAmount,
+    Pause,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TokenInstruction {
+    InitializeMint {
+        mint: String,
+        decimals: u8,
+        mint_authority: String,
+        rent_sysvar: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        freeze_authority: Option<String>,
+    },
+    InitializeMint2 {
+        mint: String,
+        decimals: u8,
+        mint_authority: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        freeze_authority: Option<String>,
+    },
+    InitializeAccount {
+        account: String,
+        mint: String,
+        owner: String,
+        rent_sysvar: String,
+    },
+    InitializeAccount2 {
+        account: String,
+        mint: String,
+        owner: String,
+        rent_sysvar: String,
+    },
+    InitializeAccount3 {
+        account: String,
+        mint: String,
+        owner: String,
+    },
+    InitializeMultisig {
+        multisig: String,
+        rent_sysvar: String,
+        signers: Vec<String>,
+        m: u8,
+    },
+    InitializeMultisig2 {
+        multisig: String,
+        signers: Vec<String>,
+        m: u8,
+    },
+    Transfer {
+        source: String,
+        destination: String,
+        amount: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    Approve {
+        source: String,
+        delegate: String,
+        amount: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    Revoke {
+        source: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    // Names the mint or the account depending on the authority being changed
+    SetAuthority {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mint: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        account: Option<String>,
+        authority_type: TokenAuthority,
+        new_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    MintTo {
+        mint: String,
+        account: String,
+        amount: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mint_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_mint_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    Burn {
+        account: String,
+        mint: String,
+        amount: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    CloseAccount {
+        account: String,
+        destination: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    FreezeAccount {
+        account: String,
+        mint: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        freeze_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_freeze_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    ThawAccount {
+        account: String,
+        mint: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        freeze_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_freeze_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    TransferChecked {
+        source: String,
+        mint: String,
+        destination: String,
+        token_amount: UiTokenAmount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    ApproveChecked {
+        source: String,
+        mint: String,
+        delegate: String,
+        token_amount: UiTokenAmount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_owner: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    MintToChecked {
+        mint: String,
+        account: String,
+        token_amount: UiTokenAmount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mint_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_mint_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    BurnChecked {
+        account: String,
+        mint: String,
+        token_amount: UiTokenAmount,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    SyncNative {
+        account: String,
+    },
+    GetAccountDataSize {
+        mint: String,
+        // Token-2022 extension names, such as "transferFeeConfig"
+        #[serde(skip_serializing_if = "Option::is_none")]
+        extension_types: Option<Vec<String>>,
+    },
+    InitializeImmutableOwner {
+        account: String,
+    },
+    AmountToUiAmount {
+        mint: String,
+        amount: String,
+    },
+    UiAmountToAmount {
+        mint: String,
+        ui_amount: String,
+    },
+    CreateNativeMint {
+        payer: String,
+        native_mint: String,
+        system_program: String,
+    },
+    InitializeNonTransferableMint {
+        mint: String,
+    },
+    WithdrawExcessLamports {
+        source: String,
+        destination: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        multisig_authority: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signers: Option<Vec<String>>,
+    },
+    #[serde(untagged)]
+    Extension {
+        #[serde(rename = "type")]
+        instruction_type: String,
+        info: Value,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "info",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum AssociatedTokenInstruction {
+    Create {
+        source: String,
+        account: String,
+        wallet: String,
+        mint: String,
+        system_program: String,
+        token_program: String,
+    },
+    CreateIdempotent {
+        source: String,
+        account: String,
+        wallet: String,
+        mint: String,
+        system_program: String,
+        token_program: String,
+    },
+    RecoverNested {
+        nested_source: String,
+        nested_mint: String,
+        destination: String,
+        nested_owner: String,
+        owner_mint: String,
+        wallet: String,
+        token_program: String,
+    },
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reward {
+    pub pubkey: String,
+    pub lamports: i64,
+    pub post_balance: u64,
+    // One of "fee", "rent", "staking" or "voting"
+    pub reward_type: Option<String>,
+    pub commission: Option<u8>,
+}
+
+impl From<solana_transaction_status::Reward> for Reward {
+    fn from(reward: solana_transaction_status::Reward) -> Self {
+        Reward {
+            pubkey: reward.pubkey,
+            lamports: reward.lamports,
+            post_balance: reward.post_balance,
+            reward_type: reward
+                .reward_type
+                .map(|reward_type| reward_type.to_string()),
+            commission: reward.commission,
+        }
+    }
+}
+
+//
+// An account's reward along with the block it was paid in, for reconciling
+// income over time
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountReward {
+    pub block: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    #[serde(flatten)]
+    pub reward: Reward,
+}
@@ -0,0 +1,371 @@
+use crate::common::instruction::Instruction;
+
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::UiTransactionTokenBalance;
+use std::collections::BTreeMap;
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+// SPL Token instruction tags. Token-2022 keeps the same layout for these.
+const TRANSFER: u8 = 3;
+const MINT_TO: u8 = 7;
+const BURN: u8 = 8;
+const TRANSFER_CHECKED: u8 = 12;
+const MINT_TO_CHECKED: u8 = 14;
+const BURN_CHECKED: u8 = 15;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub account: String,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub program_id: Option<String>,
+    pub decimals: u8,
+    pub pre_amount: u64,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenTransferKind {
+    Transfer,
+    Mint,
+    Burn,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenTransfer {
+    pub kind: TokenTransferKind,
+    pub program_id: String,
+    pub mint: Option<String>,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub authority: Option<String>,
+    pub amount: u64,
+}
+
+//
+// Latest known state of a token account, as seen in the token balances of the
+// most recent transaction that touched it
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenAccount {
+    pub id: String,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub program_id: Option<String>,
+    pub decimals: u8,
+    pub amount: u64,
+    pub slot: u64,
+}
+
+//
+// An owner's balance of a mint, summed over all their token accounts for it.
+// Accounts whose owner isn't known can't be grouped, so each of those is a
+// holding of its own.
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenHolding {
+    pub owner: Option<String>,
+    pub mint: String,
+    pub program_id: Option<String>,
+    pub decimals: u8,
+    pub amount: u64,
+    pub accounts: Vec<String>,
+    // Slot of the most recent balance among the accounts
+    pub slot: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenAccountTransfers {
+    pub signature: String,
+    pub slot: u64,
+    pub transfers: Vec<TokenTransfer>,
+}
+
+impl TokenBalance {
+    pub fn from_meta(
+        pre_token_balances: &[UiTransactionTokenBalance],
+        post_token_balances: &[UiTransactionTokenBalance],
+        accounts: &[String],
+    ) -> Vec<TokenBalance> {
+        let mut balances: BTreeMap<u8, TokenBalance> = BTreeMap::new();
+
+        // An account closed by the transaction only has a pre-balance, and one
+        // created by it only has a post-balance
+        for (balance, is_post) in pre_token_balances
+            .iter()
+            .map(|balance| (balance, false))
+            .chain(post_token_balances.iter().map(|balance| (balance, true)))
+        {
+            let amount = balance.ui_token_amount.amount.parse().unwrap_or_default();
+
+            let entry = balances
+                .entry(balance.account_index)
+                .or_insert_with(|| TokenBalance {
+                    account: accounts
+                        .get(balance.account_index as usize)
+                        .cloned()
+                        .unwrap_or_default(),
+                    mint: balance.mint.clone(),
+                    owner: balance.owner.clone().into(),
+                    program_id: balance.program_id.clone().into(),
+                    decimals: balance.ui_token_amount.decimals,
+                    pre_amount: 0,
+                    amount: 0,
+                });
+
+            if is_post {
+                entry.amount = amount;
+            } else {
+                entry.pre_amount = amount;
+            }
+        }
+
+        balances.into_values().collect()
+    }
+}
+
+impl TokenHolding {
+    pub fn aggregate(token_accounts: Vec<TokenAccount>) -> Vec<TokenHolding> {
+        let mut holdings: BTreeMap<(String, String), TokenHolding> = BTreeMap::new();
+
+        for token_account in token_accounts {
+            let holder = token_account
+                .owner
+                .clone()
+                .unwrap_or_else(|| token_account.id.clone());
+
+            let holding = holdings
+                .entry((holder, token_account.mint.clone()))
+                .or_insert_with(|| TokenHolding {
+                    owner: token_account.owner,
+                    mint: token_account.mint,
+                    program_id: token_account.program_id,
+                    decimals: token_account.decimals,
+                    amount: 0,
+                    accounts: Vec::new(),
+                    slot: 0,
+                });
+
+            // Can't overflow, a mint's whole supply fits in a u64
+            holding.amount += token_account.amount;
+            holding.accounts.push(token_account.id);
+            holding.slot = holding.slot.max(token_account.slot);
+        }
+
+        holdings.into_values().collect()
+    }
+}
+
+impl TokenTransfer {
+    pub fn decode(instruction: &Instruction, balances: &[TokenBalance]) -> Option<TokenTransfer> {
+        if instruction.program_id != TOKEN_PROGRAM_ID
+            && instruction.program_id != TOKEN_2022_PROGRAM_ID
+        {
+            return None;
+        }
+
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+        let (tag, rest) = data.split_first()?;
+        let amount = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+        let account = |index: usize| instruction.accounts.get(index).cloned();
+
+        // Plain transfers don't name the mint, so take it from the balances
+        let mint_of = |account: &Option<String>| {
+            balances
+                .iter()
+                .find(|balance| Some(&balance.account) == account.as_ref())
+                .map(|balance| balance.mint.clone())
+        };
+
+        let (kind, mint, source, destination, authority) = match *tag {
+            TRANSFER => (
+                TokenTransferKind::Transfer,
+                mint_of(&account(0)),
+                account(0),
+                account(1),
+                account(2),
+            ),
+            TRANSFER_CHECKED => (
+                TokenTransferKind::Transfer,
+                account(1),
+                account(0),
+                account(2),
+                account(3),
+            ),
+            MINT_TO | MINT_TO_CHECKED => (
+                TokenTransferKind::Mint,
+                account(0),
+                None,
+                account(1),
+                account(2),
+            ),
+            BURN | BURN_CHECKED => (
+                TokenTransferKind::Burn,
+                account(1),
+                account(0),
+                None,
+                account(2),
+            ),
+            _ => return None,
+        };
+
+        Some(TokenTransfer {
+            kind,
+            program_id: instruction.program_id.clone(),
+            mint,
+            source,
+            destination,
+            authority,
+            amount,
+        })
+    }
+
+    pub fn involves(&self, account: &str) -> bool {
+        self.source.as_deref() == Some(account) || self.destination.as_deref() == Some(account)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(
+        program_id: &str,
+        tag: u8,
+        decimals: Option<u8>,
+        accounts: &[&str],
+    ) -> Instruction {
+        let mut data = vec![tag];
+        data.extend(1_250u64.to_le_bytes());
+        data.extend(decimals);
+
+        Instruction {
+            program_id: program_id.to_string(),
+            accounts: accounts.iter().map(|account| account.to_string()).collect(),
+            data: bs58::encode(data).into_string(),
+            decoded: None,
+        }
+    }
+
+    fn transfer(
+        kind: TokenTransferKind,
+        mint: &str,
+        source: Option<&str>,
+        destination: Option<&str>,
+    ) -> TokenTransfer {
+        TokenTransfer {
+            kind,
+            program_id: TOKEN_PROGRAM_ID.to_string(),
+            mint: Some(mint.to_string()),
+            source: source.map(str::to_string),
+            destination: destination.map(str::to_string),
+            authority: Some("authority".to_string()),
+            amount: 1_250,
+        }
+    }
+
+    #[test]
+    fn decodes_transfers_mints_and_burns() {
+        let balances = vec![TokenBalance {
+            account: "source".to_string(),
+            mint: "mint".to_string(),
+            owner: None,
+            program_id: None,
+            decimals: 6,
+            pre_amount: 2_000,
+            amount: 750,
+        }];
+        let decode = |tag, decimals, accounts: &[&str]| {
+            TokenTransfer::decode(
+                &instruction(TOKEN_PROGRAM_ID, tag, decimals, accounts),
+                &balances,
+            )
+        };
+
+        // The unchecked forms carry only the amount, the checked ones add the
+        // decimals after it, and the mint moves to the accounts
+        let transferred = transfer(
+            TokenTransferKind::Transfer,
+            "mint",
+            Some("source"),
+            Some("destination"),
+        );
+        assert_eq!(
+            decode(TRANSFER, None, &["source", "destination", "authority"]),
+            Some(transferred.clone())
+        );
+        assert_eq!(
+            decode(
+                TRANSFER_CHECKED,
+                Some(6),
+                &["source", "mint", "destination", "authority"]
+            ),
+            Some(transferred)
+        );
+
+        let minted = transfer(TokenTransferKind::Mint, "mint", None, Some("destination"));
+        assert_eq!(
+            decode(MINT_TO, None, &["mint", "destination", "authority"]),
+            Some(minted.clone())
+        );
+        assert_eq!(
+            decode(
+                MINT_TO_CHECKED,
+                Some(6),
+                &["mint", "destination", "authority"]
+            ),
+            Some(minted)
+        );
+
+        let burned = transfer(TokenTransferKind::Burn, "mint", Some("source"), None);
+        assert_eq!(
+            decode(BURN, None, &["source", "mint", "authority"]),
+            Some(burned.clone())
+        );
+        assert_eq!(
+            decode(BURN_CHECKED, Some(6), &["source", "mint", "authority"]),
+            Some(burned)
+        );
+
+        // A plain transfer from an account without a balance has no mint
+        assert_eq!(
+            decode(TRANSFER, None, &["elsewhere", "destination", "authority"])
+                .unwrap()
+                .mint,
+            None
+        );
+
+        // Approvals, and anything not from a token program, aren't transfers
+        assert_eq!(decode(4, None, &["source", "delegate", "authority"]), None);
+        assert_eq!(
+            TokenTransfer::decode(
+                &instruction("11111111111111111111111111111111", TRANSFER, None, &[]),
+                &balances
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn token_2022_keeps_its_program_id() {
+        let transfer = TokenTransfer::decode(
+            &instruction(
+                TOKEN_2022_PROGRAM_ID,
+                TRANSFER_CHECKED,
+                Some(9),
+                &["source", "mint", "destination", "authority"],
+            ),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(transfer.program_id, TOKEN_2022_PROGRAM_ID);
+        assert_eq!(transfer.mint.as_deref(), Some("mint"));
+        assert_eq!(transfer.amount, 1_250);
+    }
+}
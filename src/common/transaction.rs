@@ -1,10 +1,35 @@
+use crate::common::{
+    compute_budget::{
+        prioritization_fee, ComputeBudgetInstruction, COMPUTE_BUDGET_PROGRAM_ID,
+        DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT, MAX_COMPUTE_UNIT_LIMIT,
+    },
+    errors::DecodeError,
+    event::Event,
+    instruction::{InnerInstruction, Instruction},
+    token::{TokenBalance, TokenTransfer},
+};
+
 use serde::{Deserialize, Serialize};
-use solana_sdk::transaction::TransactionError;
-use solana_transaction_status::{EncodedTransaction, EncodedTransactionWithStatusMeta, UiMessage};
+use solana_sdk::{
+    instruction::InstructionError,
+    transaction::{TransactionError, TransactionVersion},
+};
+use solana_transaction_status::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiAddressTableLookup,
+    UiInnerInstructions, UiLoadedAddresses, UiMessage, UiRawMessage, UiTransactionTokenBalance,
+};
+use std::{collections::BTreeSet, iter::once};
+
+//
+// Mirrors of the SDK's TransactionError and InstructionError that keep the
+// failing instruction index, custom program error codes and account indices.
+// Both are internally tagged, so every variant serializes as an object with a
+// "type" field whether or not it carries any detail.
+//
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum TransactionErrors {
-    None,
     AccountBorrowOutstanding,
     AccountInUse,
     AccountLoadedTwice,
@@ -14,16 +39,17 @@ pub enum TransactionErrors {
     BlockhashNotFound,
     CallChainTooDeep,
     ClusterMaintenance,
-    DuplicateInstruction,
-    InstructionError,
+    CommitCancelled,
+    DuplicateInstruction { index: u8 },
+    InstructionError { index: u8, error: InstructionErrors },
     InsufficientFundsForFee,
-    InsufficientFundsForRent,
+    InsufficientFundsForRent { account_index: u8 },
     InvalidAccountForFee,
     InvalidAccountIndex,
     InvalidAddressLookupTableData,
     InvalidAddressLookupTableIndex,
     InvalidAddressLookupTableOwner,
-    InvalidLoadedAccountDataSizeLimit,
+    InvalidLoadedAccountsDataSizeLimit,
     InvalidProgramForExecution,
     InvalidRentPayingAccount,
     InvalidWritableAccount,
@@ -31,20 +57,79 @@ pub enum TransactionErrors {
     MissingSignatureForFee,
     ProgramAccountNotFound,
     ProgramCacheHitMaxLimit,
-    ProgramExecutionTemporarilyRetsricted,
+    ProgramExecutionTemporarilyRestricted { account_index: u8 },
     ResanitizationNeeded,
     SanitizeFailure,
     SignatureFailure,
     TooManyAccountLocks,
     UnbalancedTransaction,
     UnsupportedVersion,
-    WoulbExceedMaxAccountCostLimit,
     WouldExceedAccountDataBlockLimit,
     WouldExceedAccountDataTotalLimit,
+    WouldExceedMaxAccountCostLimit,
     WouldExceedMaxBlockCostLimit,
     WouldExceedMaxVoteCostLimit,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum InstructionErrors {
+    AccountAlreadyInitialized,
+    AccountBorrowFailed,
+    AccountBorrowOutstanding,
+    AccountDataSizeChanged,
+    AccountDataTooSmall,
+    AccountNotExecutable,
+    AccountNotRentExempt,
+    ArithmeticOverflow,
+    BorshIoError { message: String },
+    BuiltinProgramsMustConsumeComputeUnits,
+    CallDepth,
+    ComputationalBudgetExceeded,
+    Custom { code: u32 },
+    DuplicateAccountIndex,
+    DuplicateAccountOutOfSync,
+    ExecutableAccountNotRentExempt,
+    ExecutableDataModified,
+    ExecutableLamportChange,
+    ExecutableModified,
+    ExternalAccountDataModified,
+    ExternalAccountLamportSpend,
+    GenericError,
+    IllegalOwner,
+    Immutable,
+    IncorrectAuthority,
+    IncorrectProgramId,
+    InsufficientFunds,
+    InvalidAccountData,
+    InvalidAccountOwner,
+    InvalidArgument,
+    InvalidError,
+    InvalidInstructionData,
+    InvalidRealloc,
+    InvalidSeeds,
+    MaxAccountsDataAllocationsExceeded,
+    MaxAccountsExceeded,
+    MaxInstructionTraceLengthExceeded,
+    MaxSeedLengthExceeded,
+    MissingAccount,
+    MissingRequiredSignature,
+    ModifiedProgramId,
+    NotEnoughAccountKeys,
+    PrivilegeEscalation,
+    ProgramEnvironmentSetupFailure,
+    ProgramFailedToCompile,
+    ProgramFailedToComplete,
+    ReadonlyDataModified,
+    ReadonlyLamportChange,
+    ReentrancyNotAllowed,
+    RentEpochModified,
+    UnbalancedInstruction,
+    UninitializedAccount,
+    UnsupportedProgramId,
+    UnsupportedSysvar,
+}
+
 impl From<TransactionError> for TransactionErrors {
     fn from(error: TransactionError) -> Self {
         match error {
@@ -61,11 +146,19 @@ impl From<TransactionError> for TransactionErrors {
             TransactionError::BlockhashNotFound => TransactionErrors::BlockhashNotFound,
             TransactionError::CallChainTooDeep => TransactionErrors::CallChainTooDeep,
             TransactionError::ClusterMaintenance => TransactionErrors::ClusterMaintenance,
-            TransactionError::DuplicateInstruction(_) => TransactionErrors::DuplicateInstruction,
-            TransactionError::InstructionError(_, _) => TransactionErrors::InstructionError,
+            TransactionError::CommitCancelled => TransactionErrors::CommitCancelled,
+            TransactionError::DuplicateInstruction(index) => {
+                TransactionErrors::DuplicateInstruction { index }
+            }
+            TransactionError::InstructionError(index, error) => {
+                TransactionErrors::InstructionError {
+                    index,
+                    error: error.into(),
+                }
+            }
             TransactionError::InsufficientFundsForFee => TransactionErrors::InsufficientFundsForFee,
-            TransactionError::InsufficientFundsForRent { .. } => {
-                TransactionErrors::InsufficientFundsForRent
+            TransactionError::InsufficientFundsForRent { account_index } => {
+                TransactionErrors::InsufficientFundsForRent { account_index }
             }
             TransactionError::InvalidAccountForFee => TransactionErrors::InvalidAccountForFee,
             TransactionError::InvalidAccountIndex => TransactionErrors::InvalidAccountIndex,
@@ -79,7 +172,7 @@ impl From<TransactionError> for TransactionErrors {
                 TransactionErrors::InvalidAddressLookupTableOwner
             }
             TransactionError::InvalidLoadedAccountsDataSizeLimit => {
-                TransactionErrors::InvalidLoadedAccountDataSizeLimit
+                TransactionErrors::InvalidLoadedAccountsDataSizeLimit
             }
             TransactionError::InvalidProgramForExecution => {
                 TransactionErrors::InvalidProgramForExecution
@@ -94,8 +187,8 @@ impl From<TransactionError> for TransactionErrors {
             TransactionError::MissingSignatureForFee => TransactionErrors::MissingSignatureForFee,
             TransactionError::ProgramAccountNotFound => TransactionErrors::ProgramAccountNotFound,
             TransactionError::ProgramCacheHitMaxLimit => TransactionErrors::ProgramCacheHitMaxLimit,
-            TransactionError::ProgramExecutionTemporarilyRestricted { .. } => {
-                TransactionErrors::ProgramExecutionTemporarilyRetsricted
+            TransactionError::ProgramExecutionTemporarilyRestricted { account_index } => {
+                TransactionErrors::ProgramExecutionTemporarilyRestricted { account_index }
             }
             TransactionError::ResanitizationNeeded => TransactionErrors::ResanitizationNeeded,
             TransactionError::SanitizeFailure => TransactionErrors::SanitizeFailure,
@@ -103,21 +196,307 @@ impl From<TransactionError> for TransactionErrors {
             TransactionError::TooManyAccountLocks => TransactionErrors::TooManyAccountLocks,
             TransactionError::UnbalancedTransaction => TransactionErrors::UnbalancedTransaction,
             TransactionError::UnsupportedVersion => TransactionErrors::UnsupportedVersion,
-            TransactionError::WouldExceedMaxAccountCostLimit => {
-                TransactionErrors::WoulbExceedMaxAccountCostLimit
-            }
             TransactionError::WouldExceedAccountDataBlockLimit => {
                 TransactionErrors::WouldExceedAccountDataBlockLimit
             }
+            TransactionError::WouldExceedAccountDataTotalLimit => {
+                TransactionErrors::WouldExceedAccountDataTotalLimit
+            }
+            TransactionError::WouldExceedMaxAccountCostLimit => {
+                TransactionErrors::WouldExceedMaxAccountCostLimit
+            }
             TransactionError::WouldExceedMaxBlockCostLimit => {
                 TransactionErrors::WouldExceedMaxBlockCostLimit
             }
             TransactionError::WouldExceedMaxVoteCostLimit => {
                 TransactionErrors::WouldExceedMaxVoteCostLimit
             }
-            TransactionError::WouldExceedAccountDataTotalLimit => {
-                TransactionErrors::WouldExceedAccountDataTotalLimit
+        }
+    }
+}
+
+impl From<InstructionError> for InstructionErrors {
+    fn from(error: InstructionError) -> Self {
+        match error {
+            InstructionError::AccountAlreadyInitialized => {
+                InstructionErrors::AccountAlreadyInitialized
+            }
+            InstructionError::AccountBorrowFailed => InstructionErrors::AccountBorrowFailed,
+            InstructionError::AccountBorrowOutstanding => {
+                InstructionErrors::AccountBorrowOutstanding
+            }
+            InstructionError::AccountDataSizeChanged => InstructionErrors::AccountDataSizeChanged,
+            InstructionError::AccountDataTooSmall => InstructionErrors::AccountDataTooSmall,
+            InstructionError::AccountNotExecutable => InstructionErrors::AccountNotExecutable,
+            InstructionError::AccountNotRentExempt => InstructionErrors::AccountNotRentExempt,
+            InstructionError::ArithmeticOverflow => InstructionErrors::ArithmeticOverflow,
+            InstructionError::BorshIoError(message) => InstructionErrors::BorshIoError { message },
+            InstructionError::BuiltinProgramsMustConsumeComputeUnits => {
+                InstructionErrors::BuiltinProgramsMustConsumeComputeUnits
+            }
+            InstructionError::CallDepth => InstructionErrors::CallDepth,
+            InstructionError::ComputationalBudgetExceeded => {
+                InstructionErrors::ComputationalBudgetExceeded
+            }
+            InstructionError::Custom(code) => InstructionErrors::Custom { code },
+            InstructionError::DuplicateAccountIndex => InstructionErrors::DuplicateAccountIndex,
+            InstructionError::DuplicateAccountOutOfSync => {
+                InstructionErrors::DuplicateAccountOutOfSync
+            }
+            InstructionError::ExecutableAccountNotRentExempt => {
+                InstructionErrors::ExecutableAccountNotRentExempt
+            }
+            InstructionError::ExecutableDataModified => InstructionErrors::ExecutableDataModified,
+            InstructionError::ExecutableLamportChange => InstructionErrors::ExecutableLamportChange,
+            InstructionError::ExecutableModified => InstructionErrors::ExecutableModified,
+            InstructionError::ExternalAccountDataModified => {
+                InstructionErrors::ExternalAccountDataModified
+            }
+            InstructionError::ExternalAccountLamportSpend => {
+                InstructionErrors::ExternalAccountLamportSpend
+            }
+            InstructionError::GenericError => InstructionErrors::GenericError,
+            InstructionError::IllegalOwner => InstructionErrors::IllegalOwner,
+            InstructionError::Immutable => InstructionErrors::Immutable,
+            InstructionError::IncorrectAuthority => InstructionErrors::IncorrectAuthority,
+            InstructionError::IncorrectProgramId => InstructionErrors::IncorrectProgramId,
+            InstructionError::InsufficientFunds => InstructionErrors::InsufficientFunds,
+            InstructionError::InvalidAccountData => InstructionErrors::InvalidAccountData,
+            InstructionError::InvalidAccountOwner => InstructionErrors::InvalidAccountOwner,
+            InstructionError::InvalidArgument => InstructionErrors::InvalidArgument,
+            InstructionError::InvalidError => InstructionErrors::InvalidError,
+            InstructionError::InvalidInstructionData => InstructionErrors::InvalidInstructionData,
+            InstructionError::InvalidRealloc => InstructionErrors::InvalidRealloc,
+            InstructionError::InvalidSeeds => InstructionErrors::InvalidSeeds,
+            InstructionError::MaxAccountsDataAllocationsExceeded => {
+                InstructionErrors::MaxAccountsDataAllocationsExceeded
+            }
+            InstructionError::MaxAccountsExceeded => InstructionErrors::MaxAccountsExceeded,
+            InstructionError::MaxInstructionTraceLengthExceeded => {
+                InstructionErrors::MaxInstructionTraceLengthExceeded
+            }
+            InstructionError::MaxSeedLengthExceeded => InstructionErrors::MaxSeedLengthExceeded,
+            InstructionError::MissingAccount => InstructionErrors::MissingAccount,
+            InstructionError::MissingRequiredSignature => {
+                InstructionErrors::MissingRequiredSignature
+            }
+            InstructionError::ModifiedProgramId => InstructionErrors::ModifiedProgramId,
+            InstructionError::NotEnoughAccountKeys => InstructionErrors::NotEnoughAccountKeys,
+            InstructionError::PrivilegeEscalation => InstructionErrors::PrivilegeEscalation,
+            InstructionError::ProgramEnvironmentSetupFailure => {
+                InstructionErrors::ProgramEnvironmentSetupFailure
+            }
+            InstructionError::ProgramFailedToCompile => InstructionErrors::ProgramFailedToCompile,
+            InstructionError::ProgramFailedToComplete => InstructionErrors::ProgramFailedToComplete,
+            InstructionError::ReadonlyDataModified => InstructionErrors::ReadonlyDataModified,
+            InstructionError::ReadonlyLamportChange => InstructionErrors::ReadonlyLamportChange,
+            InstructionError::ReentrancyNotAllowed => InstructionErrors::ReentrancyNotAllowed,
+            InstructionError::RentEpochModified => InstructionErrors::RentEpochModified,
+            InstructionError::UnbalancedInstruction => InstructionErrors::UnbalancedInstruction,
+            InstructionError::UninitializedAccount => InstructionErrors::UninitializedAccount,
+            InstructionError::UnsupportedProgramId => InstructionErrors::UnsupportedProgramId,
+            InstructionError::UnsupportedSysvar => InstructionErrors::UnsupportedSysvar,
+        }
+    }
+}
+
+impl From<TransactionErrors> for TransactionError {
+    fn from(error: TransactionErrors) -> Self {
+        match error {
+            TransactionErrors::AccountBorrowOutstanding => {
+                TransactionError::AccountBorrowOutstanding
+            }
+            TransactionErrors::AccountInUse => TransactionError::AccountInUse,
+            TransactionErrors::AccountLoadedTwice => TransactionError::AccountLoadedTwice,
+            TransactionErrors::AccountNotFound => TransactionError::AccountNotFound,
+            TransactionErrors::AddressLookupTableNotFound => {
+                TransactionError::AddressLookupTableNotFound
+            }
+            TransactionErrors::AlreadyProcessed => TransactionError::AlreadyProcessed,
+            TransactionErrors::BlockhashNotFound => TransactionError::BlockhashNotFound,
+            TransactionErrors::CallChainTooDeep => TransactionError::CallChainTooDeep,
+            TransactionErrors::ClusterMaintenance => TransactionError::ClusterMaintenance,
+            TransactionErrors::CommitCancelled => TransactionError::CommitCancelled,
+            TransactionErrors::DuplicateInstruction { index } => {
+                TransactionError::DuplicateInstruction(index)
+            }
+            TransactionErrors::InstructionError { index, error } => {
+                TransactionError::InstructionError(index, error.into())
             }
+            TransactionErrors::InsufficientFundsForFee => TransactionError::InsufficientFundsForFee,
+            TransactionErrors::InsufficientFundsForRent { account_index } => {
+                TransactionError::InsufficientFundsForRent { account_index }
+            }
+            TransactionErrors::InvalidAccountForFee => TransactionError::InvalidAccountForFee,
+            TransactionErrors::InvalidAccountIndex => TransactionError::InvalidAccountIndex,
+            TransactionErrors::InvalidAddressLookupTableData => {
+                TransactionError::InvalidAddressLookupTableData
+            }
+            TransactionErrors::InvalidAddressLookupTableIndex => {
+                TransactionError::InvalidAddressLookupTableIndex
+            }
+            TransactionErrors::InvalidAddressLookupTableOwner => {
+                TransactionError::InvalidAddressLookupTableOwner
+            }
+            TransactionErrors::InvalidLoadedAccountsDataSizeLimit => {
+                TransactionError::InvalidLoadedAccountsDataSizeLimit
+            }
+            TransactionErrors::InvalidProgramForExecution => {
+                TransactionError::InvalidProgramForExecution
+            }
+            TransactionErrors::InvalidRentPayingAccount => {
+                TransactionError::InvalidRentPayingAccount
+            }
+            TransactionErrors::InvalidWritableAccount => TransactionError::InvalidWritableAccount,
+            TransactionErrors::MaxLoadedAccountsDataSizeExceeded => {
+                TransactionError::MaxLoadedAccountsDataSizeExceeded
+            }
+            TransactionErrors::MissingSignatureForFee => TransactionError::MissingSignatureForFee,
+            TransactionErrors::ProgramAccountNotFound => TransactionError::ProgramAccountNotFound,
+            TransactionErrors::ProgramCacheHitMaxLimit => TransactionError::ProgramCacheHitMaxLimit,
+            TransactionErrors::ProgramExecutionTemporarilyRestricted { account_index } => {
+                TransactionError::ProgramExecutionTemporarilyRestricted { account_index }
+            }
+            TransactionErrors::ResanitizationNeeded => TransactionError::ResanitizationNeeded,
+            TransactionErrors::SanitizeFailure => TransactionError::SanitizeFailure,
+            TransactionErrors::SignatureFailure => TransactionError::SignatureFailure,
+            TransactionErrors::TooManyAccountLocks => TransactionError::TooManyAccountLocks,
+            TransactionErrors::UnbalancedTransaction => TransactionError::UnbalancedTransaction,
+            TransactionErrors::UnsupportedVersion => TransactionError::UnsupportedVersion,
+            TransactionErrors::WouldExceedAccountDataBlockLimit => {
+                TransactionError::WouldExceedAccountDataBlockLimit
+            }
+            TransactionErrors::WouldExceedAccountDataTotalLimit => {
+                TransactionError::WouldExceedAccountDataTotalLimit
+            }
+            TransactionErrors::WouldExceedMaxAccountCostLimit => {
+                TransactionError::WouldExceedMaxAccountCostLimit
+            }
+            TransactionErrors::WouldExceedMaxBlockCostLimit => {
+                TransactionError::WouldExceedMaxBlockCostLimit
+            }
+            TransactionErrors::WouldExceedMaxVoteCostLimit => {
+                TransactionError::WouldExceedMaxVoteCostLimit
+            }
+        }
+    }
+}
+
+impl From<InstructionErrors> for InstructionError {
+    fn from(error: InstructionErrors) -> Self {
+        match error {
+            InstructionErrors::AccountAlreadyInitialized => {
+                InstructionError::AccountAlreadyInitialized
+            }
+            InstructionErrors::AccountBorrowFailed => InstructionError::AccountBorrowFailed,
+            InstructionErrors::AccountBorrowOutstanding => {
+                InstructionError::AccountBorrowOutstanding
+            }
+            InstructionErrors::AccountDataSizeChanged => InstructionError::AccountDataSizeChanged,
+            InstructionErrors::AccountDataTooSmall => InstructionError::AccountDataTooSmall,
+            InstructionErrors::AccountNotExecutable => InstructionError::AccountNotExecutable,
+            InstructionErrors::AccountNotRentExempt => InstructionError::AccountNotRentExempt,
+            InstructionErrors::ArithmeticOverflow => InstructionError::ArithmeticOverflow,
+            InstructionErrors::BorshIoError { message } => InstructionError::BorshIoError(message),
+            InstructionErrors::BuiltinProgramsMustConsumeComputeUnits => {
+                InstructionError::BuiltinProgramsMustConsumeComputeUnits
+            }
+            InstructionErrors::CallDepth => InstructionError::CallDepth,
+            InstructionErrors::ComputationalBudgetExceeded => {
+                InstructionError::ComputationalBudgetExceeded
+            }
+            InstructionErrors::Custom { code } => InstructionError::Custom(code),
+            InstructionErrors::DuplicateAccountIndex => InstructionError::DuplicateAccountIndex,
+            InstructionErrors::DuplicateAccountOutOfSync => {
+                InstructionError::DuplicateAccountOutOfSync
+            }
+            InstructionErrors::ExecutableAccountNotRentExempt => {
+                InstructionError::ExecutableAccountNotRentExempt
+            }
+            InstructionErrors::ExecutableDataModified => InstructionError::ExecutableDataModified,
+            InstructionErrors::ExecutableLamportChange => InstructionError::ExecutableLamportChange,
+            InstructionErrors::ExecutableModified => InstructionError::ExecutableModified,
+            InstructionErrors::ExternalAccountDataModified => {
+                InstructionError::ExternalAccountDataModified
+            }
+            InstructionErrors::ExternalAccountLamportSpend => {
+                InstructionError::ExternalAccountLamportSpend
+            }
+            InstructionErrors::GenericError => InstructionError::GenericError,
+            InstructionErrors::IllegalOwner => InstructionError::IllegalOwner,
+            InstructionErrors::Immutable => InstructionError::Immutable,
+            InstructionErrors::IncorrectAuthority => InstructionError::IncorrectAuthority,
+            InstructionErrors::IncorrectProgramId => InstructionError::IncorrectProgramId,
+            InstructionErrors::InsufficientFunds => InstructionError::InsufficientFunds,
+            InstructionErrors::InvalidAccountData => InstructionError::InvalidAccountData,
+            InstructionErrors::InvalidAccountOwner => InstructionError::InvalidAccountOwner,
+            InstructionErrors::InvalidArgument => InstructionError::InvalidArgument,
+            InstructionErrors::InvalidError => InstructionError::InvalidError,
+            InstructionErrors::InvalidInstructionData => InstructionError::InvalidInstructionData,
+            InstructionErrors::InvalidRealloc => InstructionError::InvalidRealloc,
+            InstructionErrors::InvalidSeeds => InstructionError::InvalidSeeds,
+            InstructionErrors::MaxAccountsDataAllocationsExceeded => {
+                InstructionError::MaxAccountsDataAllocationsExceeded
+            }
+            InstructionErrors::MaxAccountsExceeded => InstructionError::MaxAccountsExceeded,
+            InstructionErrors::MaxInstructionTraceLengthExceeded => {
+                InstructionError::MaxInstructionTraceLengthExceeded
+            }
+            InstructionErrors::MaxSeedLengthExceeded => InstructionError::MaxSeedLengthExceeded,
+            InstructionErrors::MissingAccount => InstructionError::MissingAccount,
+            InstructionErrors::MissingRequiredSignature => {
+                InstructionError::MissingRequiredSignature
+            }
+            InstructionErrors::ModifiedProgramId => InstructionError::ModifiedProgramId,
+            InstructionErrors::NotEnoughAccountKeys => InstructionError::NotEnoughAccountKeys,
+            InstructionErrors::PrivilegeEscalation => InstructionError::PrivilegeEscalation,
+            InstructionErrors::ProgramEnvironmentSetupFailure => {
+                InstructionError::ProgramEnvironmentSetupFailure
+            }
+            InstructionErrors::ProgramFailedToCompile => InstructionError::ProgramFailedToCompile,
+            InstructionErrors::ProgramFailedToComplete => InstructionError::ProgramFailedToComplete,
+            InstructionErrors::ReadonlyDataModified => InstructionError::ReadonlyDataModified,
+            InstructionErrors::ReadonlyLamportChange => InstructionError::ReadonlyLamportChange,
+            InstructionErrors::ReentrancyNotAllowed => InstructionError::ReentrancyNotAllowed,
+            InstructionErrors::RentEpochModified => InstructionError::RentEpochModified,
+            InstructionErrors::UnbalancedInstruction => InstructionError::UnbalancedInstruction,
+            InstructionErrors::UninitializedAccount => InstructionError::UninitializedAccount,
+            InstructionErrors::UnsupportedProgramId => InstructionError::UnsupportedProgramId,
+            InstructionErrors::UnsupportedSysvar => InstructionError::UnsupportedSysvar,
+        }
+    }
+}
+
+pub const VOTE_PROGRAM_ID: &str = "Vote111111111111111111111111111111111111111";
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+// Whether a transaction invoking these programs is a vote, for both indexed
+// and still encoded transactions
+pub fn is_vote<'a>(mut program_ids: impl Iterator<Item = &'a str>) -> bool {
+    program_ids.any(|program_id| program_id == VOTE_PROGRAM_ID)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountFlags {
+    pub signer: bool,
+    pub writable: bool,
+    pub fee_payer: bool,
+    pub program: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AddressTableLookup {
+    pub account_key: String,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+impl From<UiAddressTableLookup> for AddressTableLookup {
+    fn from(lookup: UiAddressTableLookup) -> Self {
+        AddressTableLookup {
+            account_key: lookup.account_key,
+            writable_indexes: lookup.writable_indexes,
+            readonly_indexes: lookup.readonly_indexes,
         }
     }
 }
@@ -125,30 +504,117 @@ impl From<TransactionError> for TransactionErrors {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
+    pub slot: u64,
+    // None for legacy transactions
+    pub version: Option<u8>,
     pub err: Option<TransactionErrors>,
     pub recent_blockhash: String,
     pub signatures: Vec<String>,
     pub accounts: Vec<String>,
+    pub address_table_lookups: Vec<AddressTableLookup>,
+    pub account_flags: Vec<AccountFlags>,
+    pub pre_balances: Vec<u64>,
     pub balances: Vec<u64>,
+    pub balance_changes: Vec<i64>,
     pub fees: u64,
+    pub priority_fee: u64,
+    pub signer_count: u8,
+    pub compute_units_consumed: Option<u64>,
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price: Option<u64>,
+    pub instructions: Vec<Instruction>,
+    pub inner_instructions: Vec<InnerInstruction>,
+    pub log_messages: Vec<String>,
+    pub token_balances: Vec<TokenBalance>,
+    pub token_transfers: Vec<TokenTransfer>,
+    pub events: Vec<Event>,
 }
 
-impl From<EncodedTransactionWithStatusMeta> for Transaction {
-    fn from(transaction: EncodedTransactionWithStatusMeta) -> Self {
-        let meta = transaction.meta.expect("Error decoding transaction meta");
+//
+// Only transactions fetched with the json encoding can be indexed, as the
+// binary ones would need decoding and the parsed ones have lost the account
+// indexes instructions refer to
+//
+
+impl TryFrom<EncodedTransactionWithStatusMeta> for Transaction {
+    type Error = DecodeError;
+
+    fn try_from(transaction: EncodedTransactionWithStatusMeta) -> Result<Self, Self::Error> {
+        let meta = transaction.meta.ok_or(DecodeError::MissingMeta)?;
+        let version = match transaction.version {
+            Some(TransactionVersion::Number(version)) => Some(version),
+            _ => None,
+        };
 
-        let transaction = match transaction.transaction {
-            EncodedTransaction::Json(transaction) => transaction,
-            _ => panic!("Error decoding transaction"),
+        let EncodedTransaction::Json(transaction) = transaction.transaction else {
+            return Err(DecodeError::UnsupportedEncoding);
         };
 
-        let message = match transaction.message {
-            UiMessage::Raw(message) => message,
-            _ => panic!("Error decoding transaction message"),
+        let UiMessage::Raw(message) = transaction.message else {
+            return Err(DecodeError::ParsedMessage);
         };
 
-        Transaction {
-            id: transaction.signatures[0].to_string(),
+        let id = transaction
+            .signatures
+            .first()
+            .cloned()
+            .ok_or(DecodeError::MissingSignature)?;
+
+        let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.into();
+        let account_flags = account_flags(&message, loaded_addresses.as_ref());
+
+        // Loaded addresses from lookup tables follow the static keys, writable
+        // first, matching the order of the balances in the meta
+        let mut accounts = message.account_keys;
+        if let Some(loaded_addresses) = loaded_addresses {
+            accounts.extend(loaded_addresses.writable);
+            accounts.extend(loaded_addresses.readonly);
+        }
+
+        let balance_changes = meta
+            .pre_balances
+            .iter()
+            .zip(meta.post_balances.iter())
+            // Saturating, as a u64 balance can move by more than an i64 holds
+            .map(|(pre, post)| {
+                (*post as i128 - *pre as i128).clamp(i64::MIN.into(), i64::MAX.into()) as i64
+            })
+            .collect();
+
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|instruction| Instruction::new(instruction, &accounts))
+            .collect::<Result<_, _>>()?;
+
+        let inner_instructions: Option<Vec<UiInnerInstructions>> = meta.inner_instructions.into();
+        let inner_instructions = match inner_instructions {
+            Some(inner_instructions) => {
+                InnerInstruction::from_meta(&inner_instructions, &accounts)?
+            }
+            None => Vec::new(),
+        };
+
+        let log_messages: Option<Vec<String>> = meta.log_messages.into();
+        let compute_units_consumed: Option<u64> = meta.compute_units_consumed.into();
+        let signer_count = message.header.num_required_signatures;
+
+        let pre_token_balances: Option<Vec<UiTransactionTokenBalance>> =
+            meta.pre_token_balances.into();
+        let post_token_balances: Option<Vec<UiTransactionTokenBalance>> =
+            meta.post_token_balances.into();
+        let token_balances = TokenBalance::from_meta(
+            &pre_token_balances.unwrap_or_default(),
+            &post_token_balances.unwrap_or_default(),
+            &accounts,
+        );
+
+        let mut transaction = Transaction {
+            id,
+            // The encoded transaction doesn't know which block it's from, so
+            // the indexer fills this in
+            slot: 0,
+            version,
             err: meta.err.map(|e| e.into()),
             recent_blockhash: message.recent_blockhash,
             signatures: transaction
@@ -156,15 +622,332 @@ impl From<EncodedTransactionWithStatusMeta> for Transaction {
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
-            accounts: message.account_keys,
+            accounts,
+            address_table_lookups: message
+                .address_table_lookups
+                .unwrap_or_default()
+                .into_iter()
+                .map(AddressTableLookup::from)
+                .collect(),
+            account_flags,
+            pre_balances: meta.pre_balances,
             balances: meta.post_balances,
+            balance_changes,
             fees: meta.fee,
+            // Filled in from the ComputeBudget instructions below
+            priority_fee: 0,
+            signer_count,
+            compute_units_consumed,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            instructions,
+            inner_instructions,
+            log_messages: log_messages.unwrap_or_default(),
+            token_balances,
+            token_transfers: Vec::new(),
+            // Decoded by the indexer, which knows the programs' IDLs
+            events: Vec::new(),
+        };
+
+        transaction.token_transfers = transaction
+            .all_instructions()
+            .filter_map(|instruction| {
+                TokenTransfer::decode(instruction, &transaction.token_balances)
+            })
+            .collect();
+
+        transaction.apply_compute_budget();
+
+        Ok(transaction)
+    }
+}
+
+impl Transaction {
+    // Top-level instructions, each followed by the CPIs it made, in execution
+    // order
+    pub fn all_instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .flat_map(|(index, instruction)| {
+                once(instruction).chain(
+                    self.inner_instructions
+                        .iter()
+                        .filter(move |inner| inner.index as usize == index)
+                        .map(|inner| &inner.instruction),
+                )
+            })
+    }
+
+    pub fn is_vote(&self) -> bool {
+        is_vote(
+            self.instructions
+                .iter()
+                .map(|instruction| instruction.program_id.as_str()),
+        )
+    }
+
+    fn apply_compute_budget(&mut self) {
+        for instruction in &self.instructions {
+            if instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID {
+                continue;
+            }
+
+            let Ok(data) = bs58::decode(&instruction.data).into_vec() else {
+                continue;
+            };
+
+            match ComputeBudgetInstruction::unpack(&data) {
+                Some(ComputeBudgetInstruction::SetComputeUnitLimit { units }) => {
+                    self.compute_unit_limit = Some(units)
+                }
+                Some(ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports }) => {
+                    self.compute_unit_price = Some(micro_lamports)
+                }
+                _ => {}
+            }
         }
+
+        // Without an explicit limit, every instruction other than the
+        // ComputeBudget ones gets the default allowance
+        let compute_unit_limit = match self.compute_unit_limit {
+            Some(units) => units as u64,
+            None => {
+                let instructions = self
+                    .instructions
+                    .iter()
+                    .filter(|instruction| instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID)
+                    .count() as u64;
+                instructions * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+            }
+        }
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+
+        self.priority_fee =
+            prioritization_fee(self.compute_unit_price.unwrap_or(0), compute_unit_limit);
+    }
+
+    pub fn program_ids(&self) -> BTreeSet<&str> {
+        self.all_instructions()
+            .map(|instruction| instruction.program_id.as_str())
+            .collect()
     }
 }
 
-impl From<&EncodedTransactionWithStatusMeta> for Transaction {
-    fn from(transaction: &EncodedTransactionWithStatusMeta) -> Self {
-        (transaction.clone()).into()
+fn account_flags(
+    message: &UiRawMessage,
+    loaded_addresses: Option<&UiLoadedAddresses>,
+) -> Vec<AccountFlags> {
+    let header = &message.header;
+    let num_keys = message.account_keys.len();
+    let num_signers = header.num_required_signatures as usize;
+    let num_writable_signers =
+        num_signers.saturating_sub(header.num_readonly_signed_accounts as usize);
+    let num_writable_keys = num_keys.saturating_sub(header.num_readonly_unsigned_accounts as usize);
+
+    let mut flags: Vec<AccountFlags> = (0..num_keys)
+        .map(|index| AccountFlags {
+            signer: index < num_signers,
+            writable: if index < num_signers {
+                index < num_writable_signers
+            } else {
+                index < num_writable_keys
+            },
+            fee_payer: index == 0,
+            program: false,
+        })
+        .collect();
+
+    if let Some(loaded_addresses) = loaded_addresses {
+        flags.extend(loaded_addresses.writable.iter().map(|_| AccountFlags {
+            writable: true,
+            ..Default::default()
+        }));
+        flags.extend(
+            loaded_addresses
+                .readonly
+                .iter()
+                .map(|_| AccountFlags::default()),
+        );
+    }
+
+    for instruction in &message.instructions {
+        if let Some(flags) = flags.get_mut(instruction.program_id_index as usize) {
+            flags.program = true;
+        }
+    }
+
+    flags
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSummary {
+    pub signature: String,
+    pub slot: u64,
+    pub err: Option<TransactionErrors>,
+}
+
+impl From<&Transaction> for TransactionSummary {
+    fn from(transaction: &Transaction) -> Self {
+        TransactionSummary {
+            signature: transaction.id.clone(),
+            slot: transaction.slot,
+            err: transaction.err.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Success,
+    Failed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+    const GET_TRANSACTION: &str = include_str!("../../tests/fixtures/rpc/get_transaction_v0.json");
+
+    fn fixture() -> Transaction {
+        decode(serde_json::from_str(GET_TRANSACTION).unwrap())
+    }
+
+    fn decode(mut response: Value) -> Transaction {
+        let encoded: EncodedConfirmedTransactionWithStatusMeta =
+            serde_json::from_value(response["result"].take()).unwrap();
+        Transaction::try_from(encoded.transaction).unwrap()
+    }
+
+    fn compute_budget(data: &[u8]) -> Instruction {
+        Instruction {
+            program_id: COMPUTE_BUDGET_PROGRAM_ID.to_string(),
+            accounts: Vec::new(),
+            data: bs58::encode(data).into_string(),
+            decoded: None,
+        }
+    }
+
+    fn unit_limit(units: u32) -> Instruction {
+        compute_budget(&[&[2][..], &units.to_le_bytes()].concat())
+    }
+
+    fn unit_price(micro_lamports: u64) -> Instruction {
+        compute_budget(&[&[3][..], &micro_lamports.to_le_bytes()].concat())
+    }
+
+    fn priority_fee(instructions: Vec<Instruction>) -> u64 {
+        let mut transaction = Transaction {
+            instructions,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            ..fixture()
+        };
+        transaction.apply_compute_budget();
+        transaction.priority_fee
+    }
+
+    #[test]
+    fn compute_budget_and_priority_fee() {
+        // 120k units at 25,000 micro-lamports, 3,000 of the 8,000 lamport fee
+        let transaction = fixture();
+        assert_eq!(transaction.compute_unit_limit, Some(120_000));
+        assert_eq!(transaction.compute_unit_price, Some(25_000));
+        assert_eq!(transaction.compute_units_consumed, Some(31_412));
+        assert_eq!(transaction.priority_fee, 3_000);
+        assert_eq!(transaction.fees - transaction.priority_fee, 5_000);
+        assert_eq!(transaction.signer_count, 1);
+
+        let program = transaction.instructions[2].clone();
+
+        // Without a limit, each instruction other than the ComputeBudget ones
+        // is allowed 200k units, up to 1.4M for the transaction
+        assert_eq!(
+            priority_fee(vec![unit_price(25_000), program.clone()]),
+            5_000
+        );
+        assert_eq!(
+            priority_fee(vec![
+                unit_price(25_000),
+                program.clone(),
+                program.clone(),
+                program.clone()
+            ]),
+            15_000
+        );
+        assert_eq!(
+            priority_fee(
+                once(unit_price(25_000))
+                    .chain(vec![program.clone(); 8])
+                    .collect()
+            ),
+            35_000
+        );
+
+        // An explicit limit is capped the same way, and without a price
+        // there's no priority fee whatever the limit
+        assert_eq!(
+            priority_fee(vec![
+                unit_limit(2_000_000),
+                unit_price(25_000),
+                program.clone()
+            ]),
+            35_000
+        );
+        assert_eq!(priority_fee(vec![unit_limit(300_000), program]), 0);
+    }
+
+    #[test]
+    fn balances_and_flags_cover_loaded_addresses() {
+        let transaction = fixture();
+
+        // The static keys, then the lookup table's writable and readonly ones
+        assert_eq!(
+            transaction.accounts,
+            [
+                "F5khjU46QTLjNJng9V4YRbsJffGxjFDTr7x5mMb4EG3i",
+                "HVBj4D13nAY6ZFkKzty7c5Wm7gaYYAomjPYWN4QjJgwR",
+                "ComputeBudget111111111111111111111111111111",
+                "AeTwcoZRZdmeKQ5t7c4emZhhBZ4BaabqcxWVGH1u9J1n",
+                "Cj6kCMAEHzkVXTietN7fYM6XV2YqzzDQEQLcT975UkM2",
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            ]
+        );
+        assert_eq!(transaction.pre_balances.len(), 7);
+        assert_eq!(transaction.balances.len(), 7);
+        assert_eq!(transaction.balance_changes, [-8_000, 0, 0, 0, 0, 0, 0]);
+
+        let flags = |signer, writable, fee_payer, program| AccountFlags {
+            signer,
+            writable,
+            fee_payer,
+            program,
+        };
+        assert_eq!(
+            transaction.account_flags,
+            [
+                flags(true, true, true, false),
+                flags(false, true, false, false),
+                flags(false, false, false, true),
+                flags(false, false, false, true),
+                flags(false, true, false, false),
+                flags(false, false, false, false),
+                flags(false, false, false, false),
+            ]
+        );
+
+        // Changes too big for an i64 saturate rather than wrap
+        let mut response: Value = serde_json::from_str(GET_TRANSACTION).unwrap();
+        let meta = &mut response["result"]["meta"];
+        meta["preBalances"][0] = 0.into();
+        meta["postBalances"][0] = u64::MAX.into();
+        meta["preBalances"][1] = u64::MAX.into();
+        meta["postBalances"][1] = 0.into();
+        let transaction = decode(response);
+        assert_eq!(transaction.balance_changes[..2], [i64::MAX, i64::MIN]);
     }
 }
@@ -0,0 +1,68 @@
+use crate::common::{
+    instruction::Instruction,
+    transaction::{Transaction, TransactionErrors, VOTE_PROGRAM_ID},
+};
+
+use serde::{Deserialize, Serialize};
+
+//
+// What's worth keeping from a vote transaction when storing them compactly:
+// who voted, when, and whether it landed
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoteTransaction {
+    pub id: String,
+    pub slot: u64,
+    pub vote_account: String,
+    pub authority: String,
+    pub err: Option<TransactionErrors>,
+}
+
+impl From<&Transaction> for VoteTransaction {
+    fn from(transaction: &Transaction) -> Self {
+        let instruction = transaction
+            .instructions
+            .iter()
+            .find(|instruction| instruction.program_id == VOTE_PROGRAM_ID);
+
+        let vote_account = instruction
+            .and_then(|instruction| instruction.accounts.first())
+            .cloned()
+            .unwrap_or_default();
+        // Other vote program instructions aren't votes, so whoever paid for
+        // them is the closest thing to an authority
+        let authority = instruction
+            .and_then(vote_authority)
+            .or_else(|| transaction.accounts.first())
+            .cloned()
+            .unwrap_or_default();
+
+        VoteTransaction {
+            id: transaction.id.clone(),
+            slot: transaction.slot,
+            vote_account,
+            authority,
+            err: transaction.err.clone(),
+        }
+    }
+}
+
+// The signing authority's place among a vote's accounts depends on the kind of
+// vote. Vote and VoteSwitch also pass the slot hashes and clock sysvars, while
+// the vote state updates and tower syncs only take the vote account.
+fn vote_authority(instruction: &Instruction) -> Option<&String> {
+    let data = bs58::decode(&instruction.data).into_vec().ok()?;
+    let (tag, _) = data.split_first_chunk::<4>()?;
+
+    let index = match u32::from_le_bytes(*tag) {
+        // Vote, VoteSwitch
+        2 | 6 => 3,
+        // UpdateVoteState(Switch), CompactUpdateVoteState(Switch),
+        // TowerSync(Switch)
+        8 | 9 | 12..=15 => 1,
+        _ => return None,
+    };
+
+    instruction.accounts.get(index)
+}
@@ -0,0 +1,134 @@
+use crate::{
+    harness::server::HarnessMode, indexer::filter::IndexFilter, sinks::common::SinkKind,
+    source::common::SourceKind,
+};
+
+use std::{env, path::PathBuf, str::FromStr};
+
+const DEFAULT_RPC_URL: &str = "http://api.testnet.solana.com";
+const DEFAULT_WEBSOCKET_URL: &str = "ws://api.testnet.solana.com";
+
+//
+// Settings come from SOLFORGE_* environment variables so the indexer and API
+// server can be reconfigured per deployment without a rebuild. Anything unset
+// falls back to the previous hardcoded behaviour.
+//
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub rpc_url: String,
+    pub websocket_url: String,
+    // Record the cluster traffic to disk, or replay it from there
+    pub harness: Option<HarnessMode>,
+    pub source: SourceKind,
+    // Directory of Anchor IDL JSON files to decode programs with
+    pub idl_dir: Option<PathBuf>,
+    pub vote_policy: VotePolicy,
+    // Programs and addresses whose full account state is kept up to date
+    pub watch_programs: Vec<String>,
+    pub watch_accounts: Vec<String>,
+    // Initial filter for which transactions get stored. Can be changed at
+    // runtime through the API.
+    pub filter: IndexFilter,
+    // Where to export indexed events to
+    pub sinks: Vec<SinkKind>,
+    // Lets webhooks be registered for loopback and private network addresses
+    pub webhook_allow_private: bool,
+    // Bearer token the admin endpoints require. They're disabled without one.
+    pub admin_token: Option<String>,
+}
+
+//
+// Votes make up most transactions on Solana, so they can be kept out of the
+// main transaction table: skipped entirely, sampled (every nth vote in a block
+// is stored in full), or stored in a separate compact vote table.
+//
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VotePolicy {
+    #[default]
+    Store,
+    Skip,
+    Sample(usize),
+    Compact,
+}
+
+impl FromStr for VotePolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "store" => Ok(VotePolicy::Store),
+            "skip" => Ok(VotePolicy::Skip),
+            "compact" => Ok(VotePolicy::Compact),
+            _ => match policy.strip_prefix("sample:").map(str::parse) {
+                Some(Ok(every)) if every > 0 => Ok(VotePolicy::Sample(every)),
+                _ => Err(format!("Unknown vote policy: {policy}")),
+            },
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Settings {
+            rpc_url: env::var("SOLFORGE_RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string()),
+            websocket_url: env::var("SOLFORGE_WEBSOCKET_URL")
+                .unwrap_or_else(|_| DEFAULT_WEBSOCKET_URL.to_string()),
+            harness: parse_var("SOLFORGE_HARNESS"),
+            source: parse_var("SOLFORGE_SOURCE").unwrap_or_default(),
+            idl_dir: env::var_os("SOLFORGE_IDL_DIR").map(PathBuf::from),
+            vote_policy: parse_var("SOLFORGE_VOTE_POLICY").unwrap_or_default(),
+            watch_programs: list_var("SOLFORGE_WATCH_PROGRAMS"),
+            watch_accounts: list_var("SOLFORGE_WATCH_ACCOUNTS"),
+            filter: IndexFilter {
+                include_accounts: list_var("SOLFORGE_INCLUDE_ACCOUNTS"),
+                exclude_accounts: list_var("SOLFORGE_EXCLUDE_ACCOUNTS"),
+                include_programs: list_var("SOLFORGE_INCLUDE_PROGRAMS"),
+                exclude_programs: list_var("SOLFORGE_EXCLUDE_PROGRAMS"),
+                success_only: parse_var("SOLFORGE_SUCCESS_ONLY").unwrap_or_default(),
+                min_fee: parse_var("SOLFORGE_MIN_FEE"),
+            },
+            sinks: parse_list_var("SOLFORGE_SINKS"),
+            webhook_allow_private: parse_var("SOLFORGE_WEBHOOK_ALLOW_PRIVATE").unwrap_or_default(),
+            admin_token: env::var("SOLFORGE_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+fn parse_var<T: FromStr>(name: &str) -> Option<T>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = env::var(name).ok()?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(error) => panic!("Error parsing {name}: {error}"),
+    }
+}
+
+// Comma separated, with blanks ignored
+fn list_var<T: FromIterator<String>>(name: &str) -> T {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_list_var<T: FromStr>(name: &str) -> Vec<T>
+where
+    T::Err: std::fmt::Display,
+{
+    list_var::<Vec<String>>(name)
+        .iter()
+        .map(|value| match value.parse() {
+            Ok(value) => value,
+            Err(error) => panic!("Error parsing {name}: {error}"),
+        })
+        .collect()
+}
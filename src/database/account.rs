@@ -1,4 +1,9 @@
-use crate::{common::account::Account, database::common::DbResponse};
+use crate::{
+    common::{account::Account, reward::AccountReward},
+    database::common::DbResponse,
+};
+
+use std::collections::BTreeMap;
 
 impl From<DbResponse> for Account {
     fn from(response: DbResponse) -> Self {
@@ -8,3 +13,21 @@ impl From<DbResponse> for Account {
         }
     }
 }
+
+impl From<DbResponse> for BTreeMap<String, Option<Account>> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Accounts(accounts) => accounts,
+            _ => panic!("Error retrieving accounts"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<AccountReward> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::AccountRewards(rewards) => rewards,
+            _ => panic!("Error retrieving account rewards"),
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{common::block::Block, database::common::DbResponse};
+use crate::{
+    common::block::{Block, BlockStats},
+    database::common::DbResponse,
+};
+
+use std::collections::BTreeMap;
 
 impl From<DbResponse> for Block {
     fn from(response: DbResponse) -> Self {
@@ -8,3 +13,48 @@ impl From<DbResponse> for Block {
         }
     }
 }
+
+impl From<DbResponse> for BTreeMap<String, Option<Block>> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Blocks(blocks) => blocks,
+            _ => panic!("Error retrieving blocks"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<Block> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::BlockRange(blocks) => blocks,
+            _ => panic!("Error retrieving blocks"),
+        }
+    }
+}
+
+impl From<DbResponse> for BlockStats {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::BlockStats(stats) => stats,
+            _ => panic!("Error retrieving block stats"),
+        }
+    }
+}
+
+impl From<DbResponse> for u64 {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Slot(slot) => slot,
+            _ => panic!("Error retrieving slot"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<u64> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Slots(slots) => slots,
+            _ => panic!("Error retrieving slots"),
+        }
+    }
+}
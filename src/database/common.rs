@@ -1,22 +1,128 @@
-use crate::common::{account::Account, block::Block, transaction::Transaction};
+use crate::common::{
+    account::Account,
+    block::{Block, BlockStats},
+    event::ProgramActivity,
+    reward::AccountReward,
+    token::{TokenAccountTransfers, TokenHolding},
+    transaction::{Transaction, TransactionStatus, TransactionSummary},
+    vote::VoteTransaction,
+};
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone)]
 pub enum DbQuery {
     InsertBlock(Block),
+    // Along with everything stored from it, for blocks that were forked off
+    RemoveBlock(String),
     GetBlock(String),
+    GetBlocks(Vec<String>),
     GetBlockBySlot(u64),
+    GetBlockStats(String),
+    GetLatestSlot,
+    GetLowestSlot,
+    // Slots with a block, between the two inclusive
+    GetSlots(u64, u64),
+    GetBlockRange(SlotRange),
 
-    InsertTransaction(Transaction),
+    InsertTransaction(Box<Transaction>),
     GetTransaction(String),
+    GetTransactions(Vec<String>),
+    GetSlotTransactions(u64),
+    GetAccountTransactions(String, TransactionFilter),
+    GetProgramTransactions(String, TransactionFilter),
+    SearchLogs(LogSearch),
+    GetProgramInstructions(String, String, TransactionFilter),
+    GetProgramEvents(String, String, TransactionFilter),
+
+    InsertVote(VoteTransaction),
+    GetVote(String),
 
     InsertAccount(Account),
     GetAccount(String),
+    GetAccounts(Vec<String>),
+    GetAccountRewards(String, SlotRange),
+
+    GetTokenHoldings(String),
+    GetTokenHolders(String, Limit),
+    GetTokenTransfers(String, TransactionFilter),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DbResponse {
     Ok,
     Block(Block),
-    Transaction(Transaction),
+    // Looked up by id, with None for any that weren't found
+    Blocks(BTreeMap<String, Option<Block>>),
+    BlockRange(Vec<Block>),
+    BlockStats(BlockStats),
+    Slot(u64),
+    Slots(Vec<u64>),
+    Transaction(Box<Transaction>),
+    Transactions(Vec<Transaction>),
+    TransactionsById(BTreeMap<String, Option<Transaction>>),
+    TransactionSummaries(Vec<TransactionSummary>),
+    ProgramActivity(Vec<ProgramActivity>),
+    Vote(VoteTransaction),
     Account(Account),
+    Accounts(BTreeMap<String, Option<Account>>),
+    AccountRewards(Vec<AccountReward>),
+    TokenHoldings(Vec<TokenHolding>),
+    TokenTransfers(Vec<TokenAccountTransfers>),
+}
+
+//
+// Paging and filtering for transaction listings. Results come newest first, and
+// `before` takes the last signature of the previous page.
+//
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TransactionFilter {
+    pub status: Option<TransactionStatus>,
+    pub min_slot: Option<u64>,
+    pub max_slot: Option<u64>,
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+//
+// Log search matches `contains` against every log line, or with `program` set,
+// only against the "Program log:" and "Program data:" lines that program
+// emitted. Giving just `program` finds every transaction where it logged.
+//
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogSearch {
+    pub contains: Option<String>,
+    pub program: Option<String>,
+    pub status: Option<TransactionStatus>,
+    pub min_slot: Option<u64>,
+    pub max_slot: Option<u64>,
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl LogSearch {
+    pub fn filter(&self) -> TransactionFilter {
+        TransactionFilter {
+            status: self.status,
+            min_slot: self.min_slot,
+            max_slot: self.max_slot,
+            before: self.before.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Limit {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlotRange {
+    pub min_slot: Option<u64>,
+    pub max_slot: Option<u64>,
+    pub limit: Option<usize>,
 }
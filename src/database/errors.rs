@@ -8,8 +8,12 @@ pub enum DbError {
     SlotNotFound,
     #[error("Transaction not found")]
     TransactionNotFound,
+    #[error("Vote not found")]
+    VoteNotFound,
     #[error("Account not found")]
     AccountNotFound,
+    #[error("Database unavailable")]
+    Unavailable,
     #[error("Other error: {0}")]
     _Other(String),
 }
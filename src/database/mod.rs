@@ -3,4 +3,6 @@ pub mod block;
 pub mod common;
 pub mod errors;
 pub mod server;
+pub mod token;
 pub mod transaction;
+pub mod vote;
@@ -1,17 +1,45 @@
 use crate::{
-    common::{account::Account, block::Block, transaction::Transaction},
-    database::{common::DbQuery, common::DbResponse, errors::DbError},
+    common::{
+        account::Account,
+        block::{Block, BlockStats, Percentiles},
+        event::ProgramActivity,
+        logs::program_output,
+        reward::AccountReward,
+        token::{TokenAccount, TokenAccountTransfers, TokenHolding},
+        transaction::{Transaction, TransactionStatus, TransactionSummary},
+        vote::VoteTransaction,
+    },
+    database::{
+        common::{DbQuery, DbResponse, Limit, LogSearch, SlotRange, TransactionFilter},
+        errors::DbError,
+    },
 };
 
-use std::collections::HashMap;
-use tokio::time::{sleep, Duration};
+use std::{
+    borrow::Borrow,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct DbConnection {
     blocks: HashMap<String, Block>,
-    slots_to_blocks: HashMap<u64, String>,
+    slots_to_blocks: BTreeMap<u64, String>,
+    accounts_to_rewards: HashMap<String, BTreeSet<(u64, String)>>,
     transactions: HashMap<String, Transaction>,
+    slots_to_transactions: BTreeSet<(u64, String)>,
+    programs_to_transactions: HashMap<String, BTreeSet<(u64, String)>>,
+    accounts_to_transactions: HashMap<String, BTreeSet<(u64, String)>>,
+    votes: HashMap<String, VoteTransaction>,
     accounts: HashMap<String, Account>,
+    token_accounts: HashMap<String, TokenAccount>,
+    // Owner, then mint, to the token accounts that make up the holding
+    owners_to_holdings: HashMap<String, HashMap<String, BTreeSet<String>>>,
+    mints_to_token_accounts: HashMap<String, BTreeSet<String>>,
+    token_accounts_to_transactions: HashMap<String, BTreeSet<(u64, String)>>,
 }
 
 //
@@ -25,9 +53,18 @@ impl DbConnection {
     pub fn new() -> Self {
         Self {
             blocks: HashMap::new(),
-            slots_to_blocks: HashMap::new(),
+            slots_to_blocks: BTreeMap::new(),
+            accounts_to_rewards: HashMap::new(),
             transactions: HashMap::new(),
+            slots_to_transactions: BTreeSet::new(),
+            programs_to_transactions: HashMap::new(),
+            accounts_to_transactions: HashMap::new(),
+            votes: HashMap::new(),
             accounts: HashMap::new(),
+            token_accounts: HashMap::new(),
+            owners_to_holdings: HashMap::new(),
+            mints_to_token_accounts: HashMap::new(),
+            token_accounts_to_transactions: HashMap::new(),
         }
     }
 
@@ -39,27 +76,48 @@ impl DbConnection {
     // }
 
     pub async fn query(&mut self, query: &DbQuery) -> Result<DbResponse, DbError> {
-        loop {
-            if let Ok(response) = self._query_no_retry(query).await {
-                return Ok(response);
-            }
-
-            // TODO: Add config for user-specified timeouts
-            sleep(Duration::from_millis(100)).await;
-        }
-    }
-
-    pub async fn _query_no_retry(&mut self, query: &DbQuery) -> Result<DbResponse, DbError> {
         match query {
             DbQuery::InsertBlock(block) => self._insert_block(block.clone()).await,
+            DbQuery::RemoveBlock(id) => self._remove_block(id).await,
             DbQuery::GetBlock(id) => self._get_block(id).await,
+            DbQuery::GetBlocks(ids) => self._get_blocks(ids).await,
             DbQuery::GetBlockBySlot(slot) => self._get_block_by_slot(*slot).await,
+            DbQuery::GetBlockStats(id) => self._get_block_stats(id).await,
+            DbQuery::GetLatestSlot => self._get_latest_slot().await,
+            DbQuery::GetLowestSlot => self._get_lowest_slot().await,
+            DbQuery::GetSlots(start, end) => self._get_slots(*start, *end).await,
+            DbQuery::GetBlockRange(range) => self._get_block_range(range).await,
 
             DbQuery::InsertTransaction(transaction) => self._insert_transaction(transaction).await,
             DbQuery::GetTransaction(id) => self._get_transaction(id).await,
+            DbQuery::GetTransactions(ids) => self._get_transactions(ids).await,
+            DbQuery::GetSlotTransactions(slot) => self._get_slot_transactions(*slot).await,
+            DbQuery::GetProgramTransactions(program_id, filter) => {
+                self._get_program_transactions(program_id, filter).await
+            }
+            DbQuery::GetAccountTransactions(account, filter) => {
+                self._get_account_transactions(account, filter).await
+            }
+            DbQuery::SearchLogs(search) => self._search_logs(search).await,
+            DbQuery::GetProgramInstructions(program_id, name, filter) => {
+                self._get_program_instructions(program_id, name, filter)
+                    .await
+            }
+            DbQuery::GetProgramEvents(program_id, name, filter) => {
+                self._get_program_events(program_id, name, filter).await
+            }
+
+            DbQuery::InsertVote(vote) => self._insert_vote(vote.clone()).await,
+            DbQuery::GetVote(id) => self._get_vote(id).await,
 
             DbQuery::InsertAccount(account) => self._insert_account(account.clone()).await,
             DbQuery::GetAccount(id) => self._get_account(id).await,
+            DbQuery::GetAccounts(ids) => self._get_accounts(ids).await,
+            DbQuery::GetAccountRewards(id, range) => self._get_account_rewards(id, range).await,
+
+            DbQuery::GetTokenHoldings(owner) => self._get_token_holdings(owner).await,
+            DbQuery::GetTokenHolders(mint, limit) => self._get_token_holders(mint, limit).await,
+            DbQuery::GetTokenTransfers(id, filter) => self._get_token_transfers(id, filter).await,
         }
     }
 
@@ -68,6 +126,13 @@ impl DbConnection {
     //
 
     async fn _insert_block(&mut self, block: Block) -> Result<DbResponse, DbError> {
+        for reward in &block.rewards {
+            self.accounts_to_rewards
+                .entry(reward.pubkey.clone())
+                .or_default()
+                .insert((block.slot, block.id.clone()));
+        }
+
         self.blocks.insert(block.id.clone(), block.clone());
         self.slots_to_blocks.insert(block.slot, block.id.clone());
         Ok(DbResponse::Ok)
@@ -80,6 +145,14 @@ impl DbConnection {
         }
     }
 
+    async fn _get_blocks(&self, ids: &[String]) -> Result<DbResponse, DbError> {
+        Ok(DbResponse::Blocks(
+            ids.iter()
+                .map(|id| (id.clone(), self.blocks.get(id).cloned()))
+                .collect(),
+        ))
+    }
+
     async fn _get_block_by_slot(&self, slot: u64) -> Result<DbResponse, DbError> {
         match self.slots_to_blocks.get(&slot) {
             Some(block_id) => self._get_block(block_id).await,
@@ -87,6 +160,160 @@ impl DbConnection {
         }
     }
 
+    async fn _get_latest_slot(&self) -> Result<DbResponse, DbError> {
+        match self.slots_to_blocks.keys().next_back() {
+            Some(slot) => Ok(DbResponse::Slot(*slot)),
+            None => Err(DbError::SlotNotFound),
+        }
+    }
+
+    async fn _get_lowest_slot(&self) -> Result<DbResponse, DbError> {
+        match self.slots_to_blocks.keys().next() {
+            Some(slot) => Ok(DbResponse::Slot(*slot)),
+            None => Err(DbError::SlotNotFound),
+        }
+    }
+
+    // Newest first, like the other listings
+    async fn _get_block_range(&self, range: &SlotRange) -> Result<DbResponse, DbError> {
+        let min_slot = range.min_slot.unwrap_or(0);
+        let max_slot = range.max_slot.unwrap_or(u64::MAX);
+        let limit = range.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        if min_slot > max_slot {
+            return Ok(DbResponse::BlockRange(Vec::new()));
+        }
+
+        Ok(DbResponse::BlockRange(
+            self.slots_to_blocks
+                .range(min_slot..=max_slot)
+                .rev()
+                .filter_map(|(_, id)| self.blocks.get(id).cloned())
+                .take(limit)
+                .collect(),
+        ))
+    }
+
+    async fn _get_slots(&self, start: u64, end: u64) -> Result<DbResponse, DbError> {
+        Ok(DbResponse::Slots(
+            self.slots_to_blocks
+                .range(start..=end)
+                .map(|(slot, _)| *slot)
+                .collect(),
+        ))
+    }
+
+    async fn _get_block_stats(&self, id: &str) -> Result<DbResponse, DbError> {
+        let block = self.blocks.get(id).ok_or(DbError::BlockNotFound)?;
+
+        let transactions: Vec<&Transaction> = self
+            .slots_to_transactions
+            .range((block.slot, String::new())..)
+            .take_while(|(slot, _)| *slot == block.slot)
+            .filter_map(|(_, id)| self.transactions.get(id))
+            .collect();
+
+        let non_votes = || {
+            transactions
+                .iter()
+                .filter(|transaction| !transaction.is_vote())
+        };
+
+        Ok(DbResponse::BlockStats(BlockStats {
+            id: block.id.clone(),
+            slot: block.slot,
+            transaction_count: block.transaction_count,
+            vote_transaction_count: block.vote_transaction_count,
+            indexed_transaction_count: transactions.len(),
+            failed_transaction_count: transactions
+                .iter()
+                .filter(|transaction| transaction.err.is_some())
+                .count(),
+            total_compute_units: transactions
+                .iter()
+                .filter_map(|transaction| transaction.compute_units_consumed)
+                .sum(),
+            total_fees: transactions
+                .iter()
+                .map(|transaction| transaction.fees)
+                .sum(),
+            total_priority_fees: transactions
+                .iter()
+                .map(|transaction| transaction.priority_fee)
+                .sum(),
+            priority_fee: Percentiles::new(
+                non_votes()
+                    .map(|transaction| transaction.priority_fee)
+                    .collect(),
+            ),
+            compute_unit_price: Percentiles::new(
+                non_votes()
+                    .map(|transaction| transaction.compute_unit_price.unwrap_or(0))
+                    .collect(),
+            ),
+        }))
+    }
+
+    //
+    // A forked off block takes everything stored from it along. Balances it
+    // left behind go back to what the latest remaining transaction says, as
+    // there's nothing else to go on.
+    //
+
+    async fn _remove_block(&mut self, id: &str) -> Result<DbResponse, DbError> {
+        let block = self.blocks.remove(id).ok_or(DbError::BlockNotFound)?;
+
+        for reward in &block.rewards {
+            remove_from_index(
+                &mut self.accounts_to_rewards,
+                &reward.pubkey,
+                &(block.slot, block.id.clone()),
+            );
+        }
+
+        // Anything else in the slot belongs to whichever block replaced it
+        if self.slots_to_blocks.get(&block.slot) != Some(&block.id) {
+            return Ok(DbResponse::Ok);
+        }
+        self.slots_to_blocks.remove(&block.slot);
+
+        let orphaned: Vec<(u64, String)> = self
+            .slots_to_transactions
+            .range((block.slot, String::new())..)
+            .take_while(|(slot, _)| *slot == block.slot)
+            .cloned()
+            .collect();
+
+        for entry in orphaned {
+            self.slots_to_transactions.remove(&entry);
+            let Some(transaction) = self.transactions.remove(&entry.1) else {
+                continue;
+            };
+
+            for program_id in transaction.program_ids() {
+                remove_from_index(&mut self.programs_to_transactions, program_id, &entry);
+            }
+
+            for account in &transaction.accounts {
+                remove_from_index(&mut self.accounts_to_transactions, account, &entry);
+                self._restore_balance(account);
+            }
+
+            for balance in &transaction.token_balances {
+                remove_from_index(
+                    &mut self.token_accounts_to_transactions,
+                    &balance.account,
+                    &entry,
+                );
+                self._restore_token_account(&balance.account);
+            }
+        }
+
+        self.votes.retain(|_, vote| vote.slot != block.slot);
+
+        Ok(DbResponse::Ok)
+    }
+
     //
     // Transaction methods
     //
@@ -95,6 +322,23 @@ impl DbConnection {
         &mut self,
         transaction: &Transaction,
     ) -> Result<DbResponse, DbError> {
+        for program_id in transaction.program_ids() {
+            self.programs_to_transactions
+                .entry(program_id.to_string())
+                .or_default()
+                .insert((transaction.slot, transaction.id.clone()));
+        }
+
+        for account in &transaction.accounts {
+            self.accounts_to_transactions
+                .entry(account.clone())
+                .or_default()
+                .insert((transaction.slot, transaction.id.clone()));
+        }
+
+        self.slots_to_transactions
+            .insert((transaction.slot, transaction.id.clone()));
+        self._index_token_balances(transaction);
         self.transactions
             .insert(transaction.id.clone(), transaction.clone());
         Ok(DbResponse::Ok)
@@ -102,17 +346,386 @@ impl DbConnection {
 
     async fn _get_transaction(&self, id: &str) -> Result<DbResponse, DbError> {
         match self.transactions.get(id) {
-            Some(transaction) => Ok(DbResponse::Transaction(transaction.clone())),
+            Some(transaction) => Ok(DbResponse::Transaction(Box::new(transaction.clone()))),
             None => Err(DbError::TransactionNotFound),
         }
     }
 
+    async fn _get_transactions(&self, ids: &[String]) -> Result<DbResponse, DbError> {
+        Ok(DbResponse::TransactionsById(
+            ids.iter()
+                .map(|id| (id.clone(), self.transactions.get(id).cloned()))
+                .collect(),
+        ))
+    }
+
+    // Only what was indexed, so votes may be missing depending on the vote
+    // policy, and transactions come in signature rather than block order
+    async fn _get_slot_transactions(&self, slot: u64) -> Result<DbResponse, DbError> {
+        Ok(DbResponse::Transactions(
+            self.slots_to_transactions
+                .range((slot, String::new())..)
+                .take_while(|(transaction_slot, _)| *transaction_slot == slot)
+                .filter_map(|(_, id)| self.transactions.get(id).cloned())
+                .collect(),
+        ))
+    }
+
+    async fn _get_account_transactions(
+        &self,
+        account: &str,
+        filter: &TransactionFilter,
+    ) -> Result<DbResponse, DbError> {
+        let Some(index) = self.accounts_to_transactions.get(account) else {
+            return Ok(DbResponse::TransactionSummaries(Vec::new()));
+        };
+
+        Ok(DbResponse::TransactionSummaries(
+            self._filter_transactions(index, filter, |_| true)
+                .into_iter()
+                .map(TransactionSummary::from)
+                .collect(),
+        ))
+    }
+
+    async fn _get_program_transactions(
+        &self,
+        program_id: &str,
+        filter: &TransactionFilter,
+    ) -> Result<DbResponse, DbError> {
+        let Some(index) = self.programs_to_transactions.get(program_id) else {
+            return Ok(DbResponse::TransactionSummaries(Vec::new()));
+        };
+
+        Ok(DbResponse::TransactionSummaries(
+            self._filter_transactions(index, filter, |_| true)
+                .into_iter()
+                .map(TransactionSummary::from)
+                .collect(),
+        ))
+    }
+
+    async fn _search_logs(&self, search: &LogSearch) -> Result<DbResponse, DbError> {
+        let contains = search.contains.as_deref().unwrap_or_default();
+
+        let matches = |transaction: &Transaction| match &search.program {
+            Some(program_id) => program_output(&transaction.log_messages, program_id)
+                .iter()
+                .any(|output| output.as_str().contains(contains)),
+            None => transaction
+                .log_messages
+                .iter()
+                .any(|line| line.contains(contains)),
+        };
+
+        Ok(DbResponse::TransactionSummaries(
+            self._filter_transactions(&self.slots_to_transactions, &search.filter(), matches)
+                .into_iter()
+                .map(TransactionSummary::from)
+                .collect(),
+        ))
+    }
+
+    async fn _get_program_instructions(
+        &self,
+        program_id: &str,
+        name: &str,
+        filter: &TransactionFilter,
+    ) -> Result<DbResponse, DbError> {
+        let instructions = |transaction: &Transaction| -> Vec<ProgramActivity> {
+            transaction
+                .all_instructions()
+                .filter(|instruction| instruction.program_id == program_id)
+                .filter_map(|instruction| {
+                    let (instruction_name, info) = instruction.decoded.as_ref()?.name_and_info()?;
+                    (instruction_name == name).then(|| ProgramActivity {
+                        signature: transaction.id.clone(),
+                        slot: transaction.slot,
+                        name: instruction_name,
+                        data: info,
+                    })
+                })
+                .collect()
+        };
+
+        self._get_program_activity(program_id, filter, instructions)
+    }
+
+    async fn _get_program_events(
+        &self,
+        program_id: &str,
+        name: &str,
+        filter: &TransactionFilter,
+    ) -> Result<DbResponse, DbError> {
+        let events = |transaction: &Transaction| -> Vec<ProgramActivity> {
+            transaction
+                .events
+                .iter()
+                .filter(|event| event.program_id == program_id && event.name == name)
+                .map(|event| ProgramActivity {
+                    signature: transaction.id.clone(),
+                    slot: transaction.slot,
+                    name: event.name.clone(),
+                    data: event.data.clone(),
+                })
+                .collect()
+        };
+
+        self._get_program_activity(program_id, filter, events)
+    }
+
+    fn _get_program_activity(
+        &self,
+        program_id: &str,
+        filter: &TransactionFilter,
+        activity: impl Fn(&Transaction) -> Vec<ProgramActivity>,
+    ) -> Result<DbResponse, DbError> {
+        let Some(index) = self.programs_to_transactions.get(program_id) else {
+            return Ok(DbResponse::ProgramActivity(Vec::new()));
+        };
+
+        Ok(DbResponse::ProgramActivity(
+            self._filter_transactions(index, filter, |transaction| {
+                !activity(transaction).is_empty()
+            })
+            .into_iter()
+            .flat_map(&activity)
+            .collect(),
+        ))
+    }
+
+    fn _filter_transactions(
+        &self,
+        index: &BTreeSet<(u64, String)>,
+        filter: &TransactionFilter,
+        matches: impl Fn(&Transaction) -> bool,
+    ) -> Vec<&Transaction> {
+        let entries = match &filter.before {
+            Some(before) => match self.transactions.get(before) {
+                Some(transaction) => index.range(..(transaction.slot, transaction.id.clone())),
+                None => return Vec::new(),
+            },
+            None => index.range(..),
+        };
+
+        let min_slot = filter.min_slot.unwrap_or(0);
+        let max_slot = filter.max_slot.unwrap_or(u64::MAX);
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        entries
+            .rev()
+            .skip_while(|(slot, _)| *slot > max_slot)
+            .take_while(|(slot, _)| *slot >= min_slot)
+            .filter_map(|(_, id)| self.transactions.get(id))
+            .filter(|transaction| match filter.status {
+                Some(TransactionStatus::Success) => transaction.err.is_none(),
+                Some(TransactionStatus::Failed) => transaction.err.is_some(),
+                None => true,
+            })
+            .filter(|transaction| matches(transaction))
+            .take(limit)
+            .collect()
+    }
+
+    //
+    // Vote methods
+    //
+
+    async fn _insert_vote(&mut self, vote: VoteTransaction) -> Result<DbResponse, DbError> {
+        self.votes.insert(vote.id.clone(), vote);
+        Ok(DbResponse::Ok)
+    }
+
+    async fn _get_vote(&self, id: &str) -> Result<DbResponse, DbError> {
+        match self.votes.get(id) {
+            Some(vote) => Ok(DbResponse::Vote(vote.clone())),
+            None => Err(DbError::VoteNotFound),
+        }
+    }
+
+    //
+    // Token methods
+    //
+
+    fn _index_token_balances(&mut self, transaction: &Transaction) {
+        for balance in &transaction.token_balances {
+            self.token_accounts_to_transactions
+                .entry(balance.account.clone())
+                .or_default()
+                .insert((transaction.slot, transaction.id.clone()));
+
+            // Blocks can arrive out of order, so don't let an older
+            // transaction overwrite a newer balance
+            if let Some(existing) = self.token_accounts.get(&balance.account) {
+                if existing.slot > transaction.slot {
+                    continue;
+                }
+            }
+
+            // Token accounts can change hands, so take the old balance out of
+            // its holding first
+            self._unindex_token_account(&balance.account);
+
+            if let Some(owner) = &balance.owner {
+                self.owners_to_holdings
+                    .entry(owner.clone())
+                    .or_default()
+                    .entry(balance.mint.clone())
+                    .or_default()
+                    .insert(balance.account.clone());
+            }
+            self.mints_to_token_accounts
+                .entry(balance.mint.clone())
+                .or_default()
+                .insert(balance.account.clone());
+
+            self.token_accounts.insert(
+                balance.account.clone(),
+                TokenAccount {
+                    id: balance.account.clone(),
+                    mint: balance.mint.clone(),
+                    owner: balance.owner.clone(),
+                    program_id: balance.program_id.clone(),
+                    decimals: balance.decimals,
+                    amount: balance.amount,
+                    slot: transaction.slot,
+                },
+            );
+        }
+    }
+
+    fn _token_accounts(&self, ids: Option<&BTreeSet<String>>) -> Vec<TokenAccount> {
+        ids.into_iter()
+            .flatten()
+            .filter_map(|id| self.token_accounts.get(id))
+            .cloned()
+            .collect()
+    }
+
+    async fn _get_token_holdings(&self, owner: &str) -> Result<DbResponse, DbError> {
+        let token_accounts = self
+            .owners_to_holdings
+            .get(owner)
+            .into_iter()
+            .flat_map(|holdings| holdings.values())
+            .flat_map(|ids| self._token_accounts(Some(ids)))
+            .collect();
+
+        Ok(DbResponse::TokenHoldings(TokenHolding::aggregate(
+            token_accounts,
+        )))
+    }
+
+    async fn _get_token_holders(&self, mint: &str, limit: &Limit) -> Result<DbResponse, DbError> {
+        let mut holders: Vec<TokenHolding> =
+            TokenHolding::aggregate(self._token_accounts(self.mints_to_token_accounts.get(mint)))
+                .into_iter()
+                .filter(|holding| holding.amount > 0)
+                .collect();
+
+        holders.sort_by_key(|holding| Reverse(holding.amount));
+        holders.truncate(limit.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE));
+
+        Ok(DbResponse::TokenHoldings(holders))
+    }
+
+    async fn _get_token_transfers(
+        &self,
+        id: &str,
+        filter: &TransactionFilter,
+    ) -> Result<DbResponse, DbError> {
+        let Some(index) = self.token_accounts_to_transactions.get(id) else {
+            return Ok(DbResponse::TokenTransfers(Vec::new()));
+        };
+
+        let transfers = self
+            ._filter_transactions(index, filter, |transaction| {
+                transaction
+                    .token_transfers
+                    .iter()
+                    .any(|transfer| transfer.involves(id))
+            })
+            .into_iter()
+            .map(|transaction| TokenAccountTransfers {
+                signature: transaction.id.clone(),
+                slot: transaction.slot,
+                transfers: transaction
+                    .token_transfers
+                    .iter()
+                    .filter(|transfer| transfer.involves(id))
+                    .cloned()
+                    .collect(),
+            })
+            .collect();
+
+        Ok(DbResponse::TokenTransfers(transfers))
+    }
+
+    fn _unindex_token_account(&mut self, account: &str) {
+        let Some(existing) = self.token_accounts.remove(account) else {
+            return;
+        };
+
+        if let Some(owner) = &existing.owner {
+            if let Some(holdings) = self.owners_to_holdings.get_mut(owner) {
+                remove_from_index(holdings, &existing.mint, account);
+                if holdings.is_empty() {
+                    self.owners_to_holdings.remove(owner);
+                }
+            }
+        }
+        remove_from_index(&mut self.mints_to_token_accounts, &existing.mint, account);
+    }
+
+    fn _restore_token_account(&mut self, account: &str) {
+        self._unindex_token_account(account);
+
+        let latest = self
+            .token_accounts_to_transactions
+            .get(account)
+            .and_then(|index| index.last())
+            .and_then(|(_, id)| self.transactions.get(id))
+            .cloned();
+
+        if let Some(transaction) = latest {
+            self._index_token_balances(&Transaction {
+                token_balances: transaction
+                    .token_balances
+                    .into_iter()
+                    .filter(|balance| balance.account == account)
+                    .collect(),
+                ..transaction
+            });
+        }
+    }
+
     //
     // Account methods
     //
 
-    async fn _insert_account(&mut self, account: Account) -> Result<DbResponse, DbError> {
-        self.accounts.insert(account.id.clone(), account.clone());
+    async fn _insert_account(&mut self, mut account: Account) -> Result<DbResponse, DbError> {
+        if let Some(existing) = self.accounts.get(&account.id) {
+            match (&existing.state, &account.state) {
+                // Balances seen in transactions don't carry the rest of the
+                // account, so keep what the account subscription told us. The
+                // balance too, unless the transaction is newer: blocks can be
+                // fetched well behind the subscription
+                (Some(state), None) => {
+                    if state.slot >= account.slot {
+                        account.balance = existing.balance;
+                        account.slot = existing.slot;
+                    }
+                    account.state = existing.state.clone();
+                }
+                // Subscription updates can race the initial seeding
+                (Some(existing), Some(incoming)) if existing.slot > incoming.slot => {
+                    return Ok(DbResponse::Ok)
+                }
+                _ => {}
+            }
+        }
+
+        self.accounts.insert(account.id.clone(), account);
         Ok(DbResponse::Ok)
     }
 
@@ -122,4 +735,492 @@ impl DbConnection {
             None => Err(DbError::AccountNotFound),
         }
     }
+
+    async fn _get_accounts(&self, pubkeys: &[String]) -> Result<DbResponse, DbError> {
+        Ok(DbResponse::Accounts(
+            pubkeys
+                .iter()
+                .map(|pubkey| (pubkey.clone(), self.accounts.get(pubkey).cloned()))
+                .collect(),
+        ))
+    }
+
+    async fn _get_account_rewards(
+        &self,
+        pubkey: &str,
+        range: &SlotRange,
+    ) -> Result<DbResponse, DbError> {
+        let Some(index) = self.accounts_to_rewards.get(pubkey) else {
+            return Ok(DbResponse::AccountRewards(Vec::new()));
+        };
+
+        let min_slot = range.min_slot.unwrap_or(0);
+        let max_slot = range.max_slot.unwrap_or(u64::MAX);
+        let limit = range.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        let rewards = index
+            .iter()
+            .rev()
+            .skip_while(|(slot, _)| *slot > max_slot)
+            .take_while(|(slot, _)| *slot >= min_slot)
+            .filter_map(|(_, id)| self.blocks.get(id))
+            .flat_map(|block| {
+                block
+                    .rewards
+                    .iter()
+                    .filter(|reward| reward.pubkey == pubkey)
+                    .map(|reward| AccountReward {
+                        block: block.id.clone(),
+                        slot: block.slot,
+                        block_time: block.block_time,
+                        reward: reward.clone(),
+                    })
+            })
+            .take(limit)
+            .collect();
+
+        Ok(DbResponse::AccountRewards(rewards))
+    }
+
+    // Only balances seen in transactions; watched accounts keep what their
+    // subscription said
+    fn _restore_balance(&mut self, account: &str) {
+        if self
+            .accounts
+            .get(account)
+            .is_none_or(|existing| existing.state.is_some())
+        {
+            return;
+        }
+
+        let balance = self
+            .accounts_to_transactions
+            .get(account)
+            .and_then(|index| index.last())
+            .and_then(|(_, id)| self.transactions.get(id))
+            .and_then(|transaction| {
+                let index = transaction.accounts.iter().position(|key| key == account)?;
+                Some((transaction.balances.get(index).copied()?, transaction.slot))
+            });
+
+        match balance {
+            Some((balance, slot)) => self.accounts.insert(
+                account.to_string(),
+                Account::new(account.to_string(), balance, slot),
+            ),
+            None => self.accounts.remove(account),
+        };
+    }
+}
+
+impl Default for DbConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn remove_from_index<T: Ord + Borrow<E>, E: Ord + ?Sized>(
+    index: &mut HashMap<String, BTreeSet<T>>,
+    key: &str,
+    entry: &E,
+) {
+    if let Some(entries) = index.get_mut(key) {
+        entries.remove(entry);
+        if entries.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        account::AccountState,
+        instruction::{InnerInstruction, Instruction},
+        reward::Reward,
+        token::{TokenBalance, TOKEN_PROGRAM_ID},
+        transaction::TransactionErrors,
+    };
+
+    async fn account(db: &mut DbConnection, id: &str) -> Account {
+        db.query(&DbQuery::GetAccount(id.to_string()))
+            .await
+            .unwrap()
+            .into()
+    }
+
+    fn transaction(id: &str, slot: u64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            slot,
+            version: None,
+            err: None,
+            recent_blockhash: String::new(),
+            signatures: vec![id.to_string()],
+            accounts: Vec::new(),
+            address_table_lookups: Vec::new(),
+            account_flags: Vec::new(),
+            pre_balances: Vec::new(),
+            balances: Vec::new(),
+            balance_changes: Vec::new(),
+            fees: 5000,
+            priority_fee: 0,
+            signer_count: 1,
+            compute_units_consumed: None,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            instructions: Vec::new(),
+            inner_instructions: Vec::new(),
+            log_messages: Vec::new(),
+            token_balances: Vec::new(),
+            token_transfers: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn instruction(program_id: &str) -> Instruction {
+        Instruction {
+            program_id: program_id.to_string(),
+            accounts: Vec::new(),
+            data: String::new(),
+            decoded: None,
+        }
+    }
+
+    fn block(slot: u64, rewards: &[(&str, i64)]) -> Block {
+        Block {
+            id: format!("block-{slot}"),
+            previous_blockhash: format!("block-{}", slot - 1),
+            slot,
+            parent_slot: slot - 1,
+            block_time: None,
+            block_height: None,
+            rewards: rewards
+                .iter()
+                .map(|(pubkey, lamports)| Reward {
+                    pubkey: pubkey.to_string(),
+                    lamports: *lamports,
+                    post_balance: 0,
+                    reward_type: Some("voting".to_string()),
+                    commission: None,
+                })
+                .collect(),
+            transaction_count: 0,
+            vote_transaction_count: 0,
+        }
+    }
+
+    async fn slots_and_lamports(db: &mut DbConnection, query: DbQuery) -> Vec<(u64, i64)> {
+        Vec::<AccountReward>::from(db.query(&query).await.unwrap())
+            .into_iter()
+            .map(|reward| (reward.slot, reward.reward.lamports))
+            .collect()
+    }
+
+    async fn signatures(db: &mut DbConnection, query: DbQuery) -> Vec<String> {
+        Vec::<TransactionSummary>::from(db.query(&query).await.unwrap())
+            .into_iter()
+            .map(|summary| summary.signature)
+            .collect()
+    }
+
+    fn token_balance(account: &str, owner: Option<&str>, mint: &str, amount: u64) -> TokenBalance {
+        TokenBalance {
+            account: account.to_string(),
+            mint: mint.to_string(),
+            owner: owner.map(str::to_string),
+            program_id: Some(TOKEN_PROGRAM_ID.to_string()),
+            decimals: 6,
+            pre_amount: 0,
+            amount,
+        }
+    }
+
+    // Owner, mint, amount and token accounts
+    type Holding = (Option<String>, String, u64, Vec<String>);
+
+    async fn holdings(db: &mut DbConnection, query: DbQuery) -> Vec<Holding> {
+        Vec::<TokenHolding>::from(db.query(&query).await.unwrap())
+            .into_iter()
+            .map(|holding| {
+                (
+                    holding.owner,
+                    holding.mint,
+                    holding.amount,
+                    holding.accounts,
+                )
+            })
+            .collect()
+    }
+
+    fn holding(owner: Option<&str>, mint: &str, amount: u64, accounts: &[&str]) -> Holding {
+        (
+            owner.map(str::to_string),
+            mint.to_string(),
+            amount,
+            accounts.iter().map(|account| account.to_string()).collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn holdings_are_summed_per_owner_and_mint() {
+        let mut db = DbConnection::new();
+        let insert = |transaction: Transaction| DbQuery::InsertTransaction(Box::new(transaction));
+
+        db.query(&insert(Transaction {
+            token_balances: vec![
+                token_balance("alice-1", Some("alice"), "usdc", 100),
+                token_balance("alice-2", Some("alice"), "usdc", 50),
+                token_balance("alice-3", Some("alice"), "bonk", 7),
+                token_balance("bob-1", Some("bob"), "usdc", 30),
+                // From a block too old to say who owns it
+                token_balance("unowned", None, "usdc", 20),
+            ],
+            ..transaction("a", 10)
+        }))
+        .await
+        .unwrap();
+
+        let alice = || DbQuery::GetTokenHoldings("alice".to_string());
+        let usdc_holders = |limit| DbQuery::GetTokenHolders("usdc".to_string(), Limit { limit });
+
+        assert_eq!(
+            holdings(&mut db, alice()).await,
+            vec![
+                holding(Some("alice"), "bonk", 7, &["alice-3"]),
+                holding(Some("alice"), "usdc", 150, &["alice-1", "alice-2"]),
+            ]
+        );
+        assert_eq!(
+            holdings(&mut db, usdc_holders(None)).await,
+            vec![
+                holding(Some("alice"), "usdc", 150, &["alice-1", "alice-2"]),
+                holding(Some("bob"), "usdc", 30, &["bob-1"]),
+                holding(None, "usdc", 20, &["unowned"]),
+            ]
+        );
+
+        // One of alice's accounts is handed to bob, and a block from before
+        // the handover arriving late doesn't hand it back
+        db.query(&insert(Transaction {
+            token_balances: vec![token_balance("alice-2", Some("bob"), "usdc", 50)],
+            ..transaction("b", 12)
+        }))
+        .await
+        .unwrap();
+        db.query(&insert(Transaction {
+            token_balances: vec![token_balance("alice-2", Some("alice"), "usdc", 45)],
+            ..transaction("c", 11)
+        }))
+        .await
+        .unwrap();
+
+        assert_eq!(
+            holdings(&mut db, alice()).await,
+            vec![
+                holding(Some("alice"), "bonk", 7, &["alice-3"]),
+                holding(Some("alice"), "usdc", 100, &["alice-1"]),
+            ]
+        );
+        assert_eq!(
+            holdings(&mut db, usdc_holders(Some(1))).await,
+            vec![holding(Some("alice"), "usdc", 100, &["alice-1"])]
+        );
+        assert_eq!(
+            holdings(&mut db, DbQuery::GetTokenHoldings("bob".to_string())).await,
+            vec![holding(Some("bob"), "usdc", 80, &["alice-2", "bob-1"])]
+        );
+    }
+
+    #[tokio::test]
+    async fn older_transaction_balances_keep_the_subscribed_one() {
+        let mut db = DbConnection::new();
+        let subscribed = Account {
+            id: "watched".to_string(),
+            balance: 500,
+            slot: 200,
+            state: Some(AccountState {
+                owner: "owner".to_string(),
+                data: String::new(),
+                executable: false,
+                rent_epoch: 0,
+                slot: 200,
+            }),
+        };
+        db.query(&DbQuery::InsertAccount(subscribed.clone()))
+            .await
+            .unwrap();
+
+        // Fetched from a block behind the subscription
+        db.query(&DbQuery::InsertAccount(Account::new(
+            "watched".to_string(),
+            100,
+            150,
+        )))
+        .await
+        .unwrap();
+        assert_eq!(account(&mut db, "watched").await, subscribed);
+
+        // A newer one still updates the balance
+        db.query(&DbQuery::InsertAccount(Account::new(
+            "watched".to_string(),
+            700,
+            250,
+        )))
+        .await
+        .unwrap();
+        let updated = account(&mut db, "watched").await;
+        assert_eq!((updated.balance, updated.slot), (700, 250));
+        assert_eq!(updated.state, subscribed.state);
+    }
+
+    #[tokio::test]
+    async fn program_transactions_are_paged_newest_first() {
+        let mut db = DbConnection::new();
+
+        // "c" only reaches the program through a CPI, and "d" and "f" fail
+        for (id, slot) in [
+            ("a", 10),
+            ("b", 11),
+            ("c", 11),
+            ("d", 12),
+            ("e", 13),
+            ("f", 14),
+        ] {
+            let mut transaction = Transaction {
+                instructions: vec![instruction("program")],
+                ..transaction(id, slot)
+            };
+            if id == "c" {
+                transaction.instructions = vec![instruction("router")];
+                transaction.inner_instructions = vec![InnerInstruction {
+                    index: 0,
+                    stack_height: Some(2),
+                    instruction: instruction("program"),
+                }];
+            }
+            if id == "d" || id == "f" {
+                transaction.err = Some(TransactionErrors::AccountInUse);
+            }
+            db.query(&DbQuery::InsertTransaction(Box::new(transaction)))
+                .await
+                .unwrap();
+        }
+        db.query(&DbQuery::InsertTransaction(Box::new(Transaction {
+            instructions: vec![instruction("other")],
+            ..transaction("g", 12)
+        })))
+        .await
+        .unwrap();
+
+        let query = |filter: TransactionFilter| {
+            DbQuery::GetProgramTransactions("program".to_string(), filter)
+        };
+
+        assert_eq!(
+            signatures(&mut db, query(TransactionFilter::default())).await,
+            ["f", "e", "d", "c", "b", "a"]
+        );
+
+        // Following the cursor a page at a time
+        let page = |before: Option<&str>| {
+            query(TransactionFilter {
+                before: before.map(str::to_string),
+                limit: Some(2),
+                ..Default::default()
+            })
+        };
+        assert_eq!(signatures(&mut db, page(None)).await, ["f", "e"]);
+        assert_eq!(signatures(&mut db, page(Some("e"))).await, ["d", "c"]);
+        assert_eq!(signatures(&mut db, page(Some("c"))).await, ["b", "a"]);
+        assert!(signatures(&mut db, page(Some("a"))).await.is_empty());
+        // An unknown cursor doesn't start over from the top
+        assert!(signatures(&mut db, page(Some("unknown"))).await.is_empty());
+
+        assert_eq!(
+            signatures(
+                &mut db,
+                query(TransactionFilter {
+                    status: Some(TransactionStatus::Success),
+                    min_slot: Some(11),
+                    max_slot: Some(13),
+                    ..Default::default()
+                })
+            )
+            .await,
+            ["e", "c", "b"]
+        );
+        assert_eq!(
+            signatures(
+                &mut db,
+                query(TransactionFilter {
+                    status: Some(TransactionStatus::Failed),
+                    limit: Some(1),
+                    ..Default::default()
+                })
+            )
+            .await,
+            ["f"]
+        );
+        assert!(signatures(
+            &mut db,
+            DbQuery::GetProgramTransactions("unknown".to_string(), Default::default())
+        )
+        .await
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn rewards_are_listed_within_the_slot_range() {
+        let mut db = DbConnection::new();
+        for block in [
+            block(100, &[("validator", 10), ("staker", 1)]),
+            block(101, &[("staker", 2)]),
+            block(102, &[("validator", 12)]),
+            block(103, &[("validator", 13)]),
+            block(104, &[("validator", 14)]),
+        ] {
+            db.query(&DbQuery::InsertBlock(block)).await.unwrap();
+        }
+
+        let rewards = |pubkey: &str, min_slot, max_slot, limit| {
+            DbQuery::GetAccountRewards(
+                pubkey.to_string(),
+                SlotRange {
+                    min_slot,
+                    max_slot,
+                    limit,
+                },
+            )
+        };
+
+        assert_eq!(
+            slots_and_lamports(&mut db, rewards("validator", None, None, None)).await,
+            [(104, 14), (103, 13), (102, 12), (100, 10)]
+        );
+        // Both ends are inclusive
+        assert_eq!(
+            slots_and_lamports(&mut db, rewards("validator", Some(100), Some(103), None)).await,
+            [(103, 13), (102, 12), (100, 10)]
+        );
+        assert_eq!(
+            slots_and_lamports(&mut db, rewards("validator", Some(101), None, Some(2))).await,
+            [(104, 14), (103, 13)]
+        );
+        assert_eq!(
+            slots_and_lamports(&mut db, rewards("staker", None, Some(100), None)).await,
+            [(100, 1)]
+        );
+        assert!(
+            slots_and_lamports(&mut db, rewards("validator", Some(103), Some(102), None))
+                .await
+                .is_empty()
+        );
+        assert!(
+            slots_and_lamports(&mut db, rewards("nobody", None, None, None))
+                .await
+                .is_empty()
+        );
+    }
 }
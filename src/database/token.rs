@@ -0,0 +1,22 @@
+use crate::{
+    common::token::{TokenAccountTransfers, TokenHolding},
+    database::common::DbResponse,
+};
+
+impl From<DbResponse> for Vec<TokenHolding> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::TokenHoldings(holdings) => holdings,
+            _ => panic!("Error retrieving token holdings"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<TokenAccountTransfers> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::TokenTransfers(transfers) => transfers,
+            _ => panic!("Error retrieving token transfers"),
+        }
+    }
+}
@@ -1,10 +1,54 @@
-use crate::{common::transaction::Transaction, database::common::DbResponse};
+use crate::{
+    common::{
+        event::ProgramActivity,
+        transaction::{Transaction, TransactionSummary},
+    },
+    database::common::DbResponse,
+};
+
+use std::collections::BTreeMap;
 
 impl From<DbResponse> for Transaction {
     fn from(response: DbResponse) -> Self {
         match response {
-            DbResponse::Transaction(transaction) => transaction,
+            DbResponse::Transaction(transaction) => *transaction,
             _ => panic!("Error retrieving transaction"),
         }
     }
 }
+
+impl From<DbResponse> for Vec<Transaction> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Transactions(transactions) => transactions,
+            _ => panic!("Error retrieving transactions"),
+        }
+    }
+}
+
+impl From<DbResponse> for BTreeMap<String, Option<Transaction>> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::TransactionsById(transactions) => transactions,
+            _ => panic!("Error retrieving transactions"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<TransactionSummary> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::TransactionSummaries(summaries) => summaries,
+            _ => panic!("Error retrieving transaction summaries"),
+        }
+    }
+}
+
+impl From<DbResponse> for Vec<ProgramActivity> {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::ProgramActivity(activity) => activity,
+            _ => panic!("Error retrieving program activity"),
+        }
+    }
+}
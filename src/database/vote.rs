@@ -0,0 +1,10 @@
+use crate::{common::vote::VoteTransaction, database::common::DbResponse};
+
+impl From<DbResponse> for VoteTransaction {
+    fn from(response: DbResponse) -> Self {
+        match response {
+            DbResponse::Vote(vote) => vote,
+            _ => panic!("Error retrieving vote"),
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use crate::{
+    common::{
+        event::Event,
+        instruction::{DecodedInstruction, Instruction},
+    },
+    decoder::{errors::DecoderError, registry::InstructionDecoder},
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// Prefix of the self-CPI that `emit_cpi!` uses to smuggle events past log
+// truncation
+const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+//
+// Just enough of the Anchor IDL to decode instructions and events. Both the
+// current (0.30+) layout and the legacy one are accepted: legacy IDLs have no
+// discriminators, keep the address under metadata, use "publicKey" for
+// "pubkey", and refer to types with a bare string rather than {"name": ...}.
+//
+
+#[derive(Deserialize)]
+struct Idl {
+    address: Option<String>,
+    name: Option<String>,
+    #[serde(default)]
+    metadata: IdlMetadata,
+    #[serde(default)]
+    instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    events: Vec<IdlEvent>,
+    #[serde(default)]
+    types: Vec<IdlTypeDef>,
+}
+
+#[derive(Default, Deserialize)]
+struct IdlMetadata {
+    name: Option<String>,
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdlInstruction {
+    name: String,
+    discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    accounts: Vec<IdlAccountItem>,
+    #[serde(default)]
+    args: Vec<IdlField>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IdlAccountItem {
+    Composite {
+        name: String,
+        accounts: Vec<IdlAccountItem>,
+    },
+    Single {
+        name: String,
+    },
+}
+
+#[derive(Clone, Deserialize)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlType,
+}
+
+#[derive(Deserialize)]
+struct IdlEvent {
+    name: String,
+    discriminator: Option<Vec<u8>>,
+    fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Deserialize)]
+struct IdlTypeDef {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlTypeDefTy,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: IdlFields,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+impl Default for IdlFields {
+    fn default() -> Self {
+        IdlFields::Named(Vec::new())
+    }
+}
+
+#[derive(Clone, Deserialize)]
+struct IdlEnumVariant {
+    name: String,
+    fields: Option<IdlFields>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum IdlType {
+    Primitive(String),
+    Vec { vec: Box<IdlType> },
+    Option { option: Box<IdlType> },
+    COption { coption: Box<IdlType> },
+    Array { array: (Box<IdlType>, usize) },
+    Defined { defined: IdlDefined },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum IdlDefined {
+    Name(String),
+    Object { name: String },
+}
+
+struct AnchorInstruction {
+    discriminator: Vec<u8>,
+    name: String,
+    accounts: Vec<String>,
+    args: Vec<IdlField>,
+}
+
+struct AnchorEvent {
+    discriminator: Vec<u8>,
+    name: String,
+    fields: IdlFields,
+}
+
+pub struct AnchorDecoder {
+    program_name: String,
+    instructions: Vec<AnchorInstruction>,
+    events: Vec<AnchorEvent>,
+    types: HashMap<String, IdlTypeDefTy>,
+}
+
+impl AnchorDecoder {
+    // Returns the program id the IDL is for, along with its decoder
+    pub fn from_idl(idl: &str) -> Result<(String, AnchorDecoder), DecoderError> {
+        let idl: Idl = serde_json::from_str(idl)?;
+
+        let program_id = idl
+            .address
+            .or(idl.metadata.address)
+            .ok_or(DecoderError::MissingProgramId)?;
+
+        let types: HashMap<String, IdlTypeDefTy> = idl
+            .types
+            .into_iter()
+            .map(|type_def| (type_def.name, type_def.ty))
+            .collect();
+
+        let instructions = idl
+            .instructions
+            .into_iter()
+            .map(|instruction| AnchorInstruction {
+                discriminator: instruction.discriminator.unwrap_or_else(|| {
+                    sighash(&format!("global:{}", to_snake_case(&instruction.name)))
+                }),
+                accounts: flatten_accounts(&instruction.accounts, None),
+                name: instruction.name,
+                args: instruction.args,
+            })
+            .collect();
+
+        let events = idl
+            .events
+            .into_iter()
+            .map(|event| AnchorEvent {
+                discriminator: event
+                    .discriminator
+                    .unwrap_or_else(|| sighash(&format!("event:{}", event.name))),
+                // Current IDLs describe the event's fields in the types section
+                fields: match event.fields {
+                    Some(fields) => IdlFields::Named(fields),
+                    None => match types.get(&event.name) {
+                        Some(IdlTypeDefTy::Struct { fields }) => fields.clone(),
+                        _ => IdlFields::default(),
+                    },
+                },
+                name: event.name,
+            })
+            .collect();
+
+        let decoder = AnchorDecoder {
+            program_name: idl
+                .metadata
+                .name
+                .or(idl.name)
+                .unwrap_or_else(|| program_id.clone()),
+            instructions,
+            events,
+            types,
+        };
+
+        Ok((program_id, decoder))
+    }
+
+    fn decode_type(&self, ty: &IdlType, data: &mut &[u8]) -> Option<Value> {
+        let value = match ty {
+            IdlType::Primitive(name) => match name.as_str() {
+                "bool" => json!(take::<1>(data)?[0] != 0),
+                "u8" => json!(take::<1>(data)?[0]),
+                "i8" => json!(take::<1>(data)?[0] as i8),
+                "u16" => json!(u16::from_le_bytes(take(data)?)),
+                "i16" => json!(i16::from_le_bytes(take(data)?)),
+                "u32" => json!(u32::from_le_bytes(take(data)?)),
+                "i32" => json!(i32::from_le_bytes(take(data)?)),
+                "f32" => json!(f32::from_le_bytes(take(data)?)),
+                "u64" => json!(u64::from_le_bytes(take(data)?)),
+                "i64" => json!(i64::from_le_bytes(take(data)?)),
+                "f64" => json!(f64::from_le_bytes(take(data)?)),
+                // Too big for a JSON number to hold exactly
+                "u128" => json!(u128::from_le_bytes(take(data)?).to_string()),
+                "i128" => json!(i128::from_le_bytes(take(data)?).to_string()),
+                "pubkey" | "publicKey" => json!(bs58::encode(take::<32>(data)?).into_string()),
+                "string" => json!(String::from_utf8(take_vec(data)?).ok()?),
+                "bytes" => json!(BASE64_STANDARD.encode(take_vec(data)?)),
+                _ => return None,
+            },
+            IdlType::Vec { vec } => {
+                // Every element takes at least a byte, so a length past what's
+                // left is garbage and would otherwise spin for up to 2^32 items
+                let len = u32::from_le_bytes(take(data)?) as usize;
+                if len > data.len() {
+                    return None;
+                }
+                Value::Array(
+                    (0..len)
+                        .map(|_| self.decode_type(vec, data))
+                        .collect::<Option<_>>()?,
+                )
+            }
+            IdlType::Option { option } => match take::<1>(data)?[0] {
+                0 => Value::Null,
+                _ => self.decode_type(option, data)?,
+            },
+            IdlType::COption { coption } => match u32::from_le_bytes(take(data)?) {
+                0 => Value::Null,
+                _ => self.decode_type(coption, data)?,
+            },
+            IdlType::Array { array: (ty, len) } => Value::Array(
+                (0..*len)
+                    .map(|_| self.decode_type(ty, data))
+                    .collect::<Option<_>>()?,
+            ),
+            IdlType::Defined { defined } => {
+                let name = match defined {
+                    IdlDefined::Name(name) | IdlDefined::Object { name } => name,
+                };
+                self.decode_type_def(self.types.get(name)?, data)?
+            }
+        };
+
+        Some(value)
+    }
+
+    fn decode_type_def(&self, ty: &IdlTypeDefTy, data: &mut &[u8]) -> Option<Value> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.decode_fields(fields, data),
+            IdlTypeDefTy::Enum { variants } => {
+                let variant = variants.get(take::<1>(data)?[0] as usize)?;
+                match &variant.fields {
+                    Some(fields) => {
+                        Some(json!({ &variant.name: self.decode_fields(fields, data)? }))
+                    }
+                    None => Some(json!(variant.name)),
+                }
+            }
+            IdlTypeDefTy::Type { alias } => self.decode_type(alias, data),
+        }
+    }
+
+    fn decode_fields(&self, fields: &IdlFields, data: &mut &[u8]) -> Option<Value> {
+        match fields {
+            IdlFields::Named(fields) => Some(Value::Object(
+                fields
+                    .iter()
+                    .map(|field| Some((field.name.clone(), self.decode_type(&field.ty, data)?)))
+                    .collect::<Option<Map<_, _>>>()?,
+            )),
+            IdlFields::Tuple(types) => Some(Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode_type(ty, data))
+                    .collect::<Option<_>>()?,
+            )),
+        }
+    }
+}
+
+impl InstructionDecoder for AnchorDecoder {
+    fn decode(&self, instruction: &Instruction) -> Option<DecodedInstruction> {
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+        let definition = self
+            .instructions
+            .iter()
+            .find(|definition| data.starts_with(&definition.discriminator))?;
+
+        let mut rest = &data[definition.discriminator.len()..];
+        let args = self.decode_fields(&IdlFields::Named(definition.args.clone()), &mut rest)?;
+
+        let accounts: Map<String, Value> = definition
+            .accounts
+            .iter()
+            .zip(instruction.accounts.iter())
+            .map(|(name, account)| (name.clone(), json!(account)))
+            .collect();
+
+        Some(DecodedInstruction::Parsed {
+            program: self.program_name.clone(),
+            instruction_type: definition.name.clone(),
+            info: json!({ "accounts": accounts, "args": args }),
+        })
+    }
+
+    fn decode_event(&self, program_id: &str, data: &[u8]) -> Option<Event> {
+        let data = data.strip_prefix(&EVENT_IX_TAG).unwrap_or(data);
+        let definition = self
+            .events
+            .iter()
+            .find(|definition| data.starts_with(&definition.discriminator))?;
+
+        let mut rest = &data[definition.discriminator.len()..];
+
+        Some(Event {
+            program_id: program_id.to_string(),
+            name: definition.name.clone(),
+            data: self.decode_fields(&definition.fields, &mut rest)?,
+        })
+    }
+}
+
+// Nested account structs are flattened in order, named "parent.child"
+fn flatten_accounts(items: &[IdlAccountItem], prefix: Option<&str>) -> Vec<String> {
+    items
+        .iter()
+        .flat_map(|item| {
+            let (name, children) = match item {
+                IdlAccountItem::Composite { name, accounts } => (name, Some(accounts)),
+                IdlAccountItem::Single { name } => (name, None),
+            };
+            let name = match prefix {
+                Some(prefix) => format!("{prefix}.{name}"),
+                None => name.clone(),
+            };
+
+            match children {
+                Some(children) => flatten_accounts(children, Some(&name)),
+                None => vec![name],
+            }
+        })
+        .collect()
+}
+
+fn sighash(preimage: &str) -> Vec<u8> {
+    Sha256::digest(preimage.as_bytes())[..8].to_vec()
+}
+
+// Splits words the way heck does, which is what Anchor derives the sighash
+// names of legacy IDLs with: at a lowercase letter followed by an uppercase
+// one, before the last of a run of capitals followed by a lowercase letter
+// ("setURI" is set_uri, "HTTPServer" is http_server), and at anything that
+// isn't a letter or digit. Digits stick to whatever came before them.
+fn to_snake_case(name: &str) -> String {
+    let mut words = Vec::new();
+
+    for word in name.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<(usize, char)> = word.char_indices().collect();
+        let mut start = 0;
+        // Whether the previous letter was a capital, carried over digits
+        let mut upper = None;
+
+        for (position, &(index, c)) in chars.iter().enumerate() {
+            let Some(&(next_index, next)) = chars.get(position + 1) else {
+                words.push(&word[start..]);
+                break;
+            };
+
+            let current = match c {
+                c if c.is_lowercase() => Some(false),
+                c if c.is_uppercase() => Some(true),
+                _ => upper,
+            };
+
+            if current == Some(false) && next.is_uppercase() {
+                words.push(&word[start..next_index]);
+                start = next_index;
+                upper = None;
+            } else if upper == Some(true) && c.is_uppercase() && next.is_lowercase() {
+                words.push(&word[start..index]);
+                start = index;
+                upper = None;
+            } else {
+                upper = current;
+            }
+        }
+    }
+
+    words
+        .iter()
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = data.split_first_chunk::<N>()?;
+    *data = rest;
+    Some(*head)
+}
+
+fn take_vec(data: &mut &[u8]) -> Option<Vec<u8>> {
+    let len = u32::from_le_bytes(take(data)?) as usize;
+    let head = data.get(..len)?.to_vec();
+    *data = &data[len..];
+    Some(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(data: &[u8]) -> Instruction {
+        Instruction {
+            program_id: "Prog1111111111111111111111111111111111111111".to_string(),
+            accounts: Vec::new(),
+            data: bs58::encode(data).into_string(),
+            decoded: None,
+        }
+    }
+
+    #[test]
+    fn vec_lengths_are_capped_by_the_data() {
+        let idl = json!({
+            "address": "Prog1111111111111111111111111111111111111111",
+            "metadata": { "name": "test" },
+            "instructions": [{
+                "name": "store",
+                "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                "args": [{ "name": "values", "type": { "vec": "u8" } }]
+            }]
+        });
+        let (_, decoder) = AnchorDecoder::from_idl(&idl.to_string()).unwrap();
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(2u32.to_le_bytes());
+        data.extend([9, 10]);
+        let Some(DecodedInstruction::Parsed { info, .. }) = decoder.decode(&instruction(&data))
+        else {
+            panic!("instruction decodes");
+        };
+        assert_eq!(info["args"]["values"], json!([9, 10]));
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(u32::MAX.to_le_bytes());
+        data.extend([9, 10]);
+        assert_eq!(decoder.decode(&instruction(&data)), None);
+    }
+
+    #[test]
+    fn snake_cases_like_heck() {
+        for (name, snake) in [
+            ("initialize", "initialize"),
+            ("liquidUnstake", "liquid_unstake"),
+            ("setURI", "set_uri"),
+            ("HTTPServer", "http_server"),
+            ("XMLHttpRequest", "xml_http_request"),
+            ("mintV2", "mint_v2"),
+            ("serum3PlaceOrder", "serum3_place_order"),
+            ("already_snake", "already_snake"),
+        ] {
+            assert_eq!(to_snake_case(name), snake, "{name}");
+        }
+    }
+
+    #[test]
+    fn legacy_discriminators() {
+        // Marinade's legacy IDL, trimmed down to a few instructions
+        let idl = include_str!("../../tests/fixtures/idls/marinade_legacy.json");
+        let (program_id, decoder) = AnchorDecoder::from_idl(idl).unwrap();
+        assert_eq!(program_id, "MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD");
+        assert_eq!(decoder.program_name, "marinade_finance");
+
+        // The first 8 bytes of sha256("global:<snake_case name>")
+        let discriminators: Vec<(&str, &[u8])> = decoder
+            .instructions
+            .iter()
+            .map(|instruction| (instruction.name.as_str(), &instruction.discriminator[..]))
+            .collect();
+        assert_eq!(
+            discriminators,
+            [
+                ("initialize", &[175, 175, 109, 31, 13, 152, 155, 237][..]),
+                ("changeAuthority", &[50, 106, 66, 104, 99, 118, 145, 88]),
+                ("deposit", &[242, 35, 198, 137, 82, 225, 242, 182]),
+                ("liquidUnstake", &[30, 30, 119, 240, 191, 227, 12, 16]),
+                ("configLp", &[10, 24, 168, 119, 86, 48, 225, 17]),
+                ("orderUnstake", &[97, 167, 144, 107, 117, 190, 128, 36]),
+            ]
+        );
+
+        let mut data = vec![10, 24, 168, 119, 86, 48, 225, 17];
+        // min_fee: Some(30 bps), max_fee: None, liquidity_target: Some(5), treasury_cut: None
+        data.extend([1, 30, 0, 0, 0, 0, 1, 5, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let Some(DecodedInstruction::Parsed {
+            instruction_type,
+            info,
+            ..
+        }) = decoder.decode(&instruction(&data))
+        else {
+            panic!("configLp decodes");
+        };
+        assert_eq!(instruction_type, "configLp");
+        assert_eq!(
+            info["args"]["params"],
+            json!({
+                "minFee": { "basisPoints": 30 },
+                "maxFee": null,
+                "liquidityTarget": 5,
+                "treasuryCut": null,
+            })
+        );
+    }
+}
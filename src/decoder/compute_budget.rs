@@ -0,0 +1,21 @@
+use crate::{
+    common::{
+        compute_budget::ComputeBudgetInstruction,
+        instruction::{DecodedInstruction, Instruction},
+        native::NativeInstruction,
+    },
+    decoder::registry::InstructionDecoder,
+};
+
+pub struct ComputeBudgetDecoder;
+
+impl InstructionDecoder for ComputeBudgetDecoder {
+    fn decode(&self, instruction: &Instruction) -> Option<DecodedInstruction> {
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+        let decoded = ComputeBudgetInstruction::unpack(&data)?;
+
+        Some(DecodedInstruction::Native(
+            NativeInstruction::ComputeBudget(decoded),
+        ))
+    }
+}
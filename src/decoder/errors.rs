@@ -0,0 +1,11 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DecoderError {
+    #[error("Error reading IDL: {0}")]
+    IdlReadError(#[from] std::io::Error),
+    #[error("Error parsing IDL: {0}")]
+    IdlParseError(#[from] serde_json::Error),
+    #[error("IDL has no program address")]
+    MissingProgramId,
+}
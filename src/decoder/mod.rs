@@ -0,0 +1,5 @@
+pub mod anchor;
+pub mod compute_budget;
+pub mod errors;
+pub mod native;
+pub mod registry;
@@ -0,0 +1,75 @@
+use crate::{
+    common::{
+        instruction::{DecodedInstruction, Instruction},
+        native::NativeInstruction,
+    },
+    decoder::registry::InstructionDecoder,
+};
+
+use serde_json::{json, Value};
+use solana_sdk::{instruction::CompiledInstruction, message::AccountKeys, pubkey::Pubkey};
+use solana_transaction_status::parse_instruction::parse;
+use std::str::FromStr;
+
+//
+// The SDK already ships jsonParsed decoders for the native and SPL programs, so
+// this just adapts an indexed instruction back into the compiled form they
+// expect, with the instruction's own accounts as the account keys, and reads
+// what they produce into the typed instructions.
+//
+
+pub struct NativeDecoder;
+
+impl NativeDecoder {
+    pub const PROGRAM_IDS: [&'static str; 9] = [
+        "11111111111111111111111111111111",
+        "Stake11111111111111111111111111111111111111",
+        "Vote111111111111111111111111111111111111111",
+        "AddressLookupTab1e1111111111111111111111111",
+        "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+    ];
+}
+
+impl InstructionDecoder for NativeDecoder {
+    fn decode(&self, instruction: &Instruction) -> Option<DecodedInstruction> {
+        let program_id = Pubkey::from_str(&instruction.program_id).ok()?;
+        let keys = instruction
+            .accounts
+            .iter()
+            .map(|account| Pubkey::from_str(account).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        let compiled = CompiledInstruction {
+            program_id_index: 0,
+            accounts: (0..keys.len() as u8).collect(),
+            data: bs58::decode(&instruction.data).into_vec().ok()?,
+        };
+
+        let parsed = parse(&program_id, &compiled, &AccountKeys::new(&keys, None), None).ok()?;
+
+        // Memos parse to a bare string, everything else to {type, info}
+        let parsed = match parsed.parsed {
+            Value::Object(mut object) => {
+                object.insert("program".to_string(), json!(parsed.program));
+                Value::Object(object)
+            }
+            memo => json!({ "program": parsed.program, "type": "memo", "info": memo }),
+        };
+
+        // Only fails if the SDK's output has drifted from the typed forms
+        match serde_json::from_value::<NativeInstruction>(parsed) {
+            Ok(native) => Some(DecodedInstruction::Native(native)),
+            Err(error) => {
+                println!(
+                    "Couldn't read {} instruction: {}",
+                    instruction.program_id, error
+                );
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use crate::{
+    common::{
+        compute_budget::COMPUTE_BUDGET_PROGRAM_ID,
+        event::Event,
+        instruction::{DecodedInstruction, Instruction},
+        logs::{program_output, ProgramOutput},
+        transaction::Transaction,
+    },
+    decoder::{
+        anchor::AnchorDecoder, compute_budget::ComputeBudgetDecoder, errors::DecoderError,
+        native::NativeDecoder,
+    },
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+pub trait InstructionDecoder: Send + Sync {
+    fn decode(&self, instruction: &Instruction) -> Option<DecodedInstruction>;
+
+    // Events arrive as "Program data:" log lines or self-CPI instruction data
+    fn decode_event(&self, _program_id: &str, _data: &[u8]) -> Option<Event> {
+        None
+    }
+}
+
+//
+// Maps program ids to the decoder for their instructions. Anything without a
+// decoder, or that its decoder can't make sense of, is kept as raw base58.
+//
+
+#[derive(Clone, Default)]
+pub struct DecoderRegistry {
+    decoders: HashMap<String, Arc<dyn InstructionDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        let mut registry = DecoderRegistry::default();
+
+        let native = Arc::new(NativeDecoder);
+        for program_id in NativeDecoder::PROGRAM_IDS {
+            registry.register(program_id, native.clone());
+        }
+        registry.register(COMPUTE_BUDGET_PROGRAM_ID, Arc::new(ComputeBudgetDecoder));
+
+        registry
+    }
+
+    // Registers a decoder for every Anchor IDL (*.json) in the directory
+    pub fn load_idls(&mut self, dir: &Path) -> Result<(), DecoderError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+
+            let idl = match fs::read_to_string(&path) {
+                Ok(idl) => idl,
+                Err(error) => {
+                    println!("Skipping IDL {}: {}", path.display(), error);
+                    continue;
+                }
+            };
+
+            match AnchorDecoder::from_idl(&idl) {
+                Ok((program_id, decoder)) => {
+                    println!("Loaded IDL for program: {:?}", program_id);
+                    self.register(&program_id, Arc::new(decoder));
+                }
+                Err(error) => println!("Skipping IDL {}: {}", path.display(), error),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn register(&mut self, program_id: &str, decoder: Arc<dyn InstructionDecoder>) {
+        self.decoders.insert(program_id.to_string(), decoder);
+    }
+
+    pub fn decode(&self, instruction: &Instruction) -> DecodedInstruction {
+        self.decoders
+            .get(&instruction.program_id)
+            .and_then(|decoder| decoder.decode(instruction))
+            .unwrap_or_else(|| DecodedInstruction::Raw {
+                data: instruction.data.clone(),
+            })
+    }
+
+    pub fn decode_transaction(&self, transaction: &mut Transaction) {
+        for instruction in transaction.instructions.iter_mut() {
+            instruction.decoded = Some(self.decode(instruction));
+        }
+
+        for inner in transaction.inner_instructions.iter_mut() {
+            inner.instruction.decoded = Some(self.decode(&inner.instruction));
+        }
+
+        transaction.events = self.decode_events(transaction);
+    }
+
+    fn decode_events(&self, transaction: &Transaction) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for program_id in transaction.program_ids() {
+            let Some(decoder) = self.decoders.get(program_id) else {
+                continue;
+            };
+
+            for output in program_output(&transaction.log_messages, program_id) {
+                if let ProgramOutput::Data(data) = output {
+                    // A line can carry several space separated chunks, but the
+                    // event is always in the first
+                    let first = data.split(' ').next().unwrap_or_default();
+                    if let Ok(data) = BASE64_STANDARD.decode(first) {
+                        events.extend(decoder.decode_event(program_id, &data));
+                    }
+                }
+            }
+
+            for inner in &transaction.inner_instructions {
+                if inner.instruction.program_id == program_id {
+                    if let Ok(data) = bs58::decode(&inner.instruction.data).into_vec() {
+                        events.extend(decoder.decode_event(program_id, &data));
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        compute_budget::ComputeBudgetInstruction,
+        native::{
+            AddressLookupTableInstruction, AssociatedTokenInstruction, MemoInstruction,
+            NativeInstruction, StakeAuthority, StakeInstruction, SystemInstruction,
+            TokenInstruction, Vote, VoteInstruction,
+        },
+        token::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+        transaction::{SYSTEM_PROGRAM_ID, VOTE_PROGRAM_ID},
+    };
+
+    use serde_json::json;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_sdk::pubkey::Pubkey;
+
+    const STAKE_PROGRAM_ID: &str = "Stake11111111111111111111111111111111111111";
+    const LOOKUP_TABLE_PROGRAM_ID: &str = "AddressLookupTab1e1111111111111111111111111";
+    const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+    // Stands in for an account, or a hash, by number
+    fn key(n: u8) -> String {
+        Pubkey::new_from_array([n; 32]).to_string()
+    }
+
+    fn instruction(program_id: &str, accounts: &[u8], data: &[u8]) -> Instruction {
+        Instruction {
+            program_id: program_id.to_string(),
+            accounts: accounts.iter().map(|n| key(*n)).collect(),
+            data: bs58::encode(data).into_string(),
+            decoded: None,
+        }
+    }
+
+    fn decode(program_id: &str, accounts: &[u8], data: &[u8]) -> NativeInstruction {
+        match DecoderRegistry::new().decode(&instruction(program_id, accounts, data)) {
+            DecodedInstruction::Native(native) => native,
+            other => panic!("Expected a native instruction, got {other:?}"),
+        }
+    }
+
+    // Bincode encodes the native programs' instruction tags as a u32
+    fn tagged(tag: u32, args: &[&[u8]]) -> Vec<u8> {
+        [&tag.to_le_bytes()[..], &args.concat()].concat()
+    }
+
+    #[test]
+    fn system() {
+        assert_eq!(
+            decode(
+                SYSTEM_PROGRAM_ID,
+                &[1, 2],
+                &tagged(2, &[&5_000u64.to_le_bytes()])
+            ),
+            NativeInstruction::System(SystemInstruction::Transfer {
+                source: key(1),
+                destination: key(2),
+                lamports: 5_000,
+            })
+        );
+
+        let create = tagged(
+            0,
+            &[&1_000u64.to_le_bytes(), &165u64.to_le_bytes(), &[9; 32]],
+        );
+        assert_eq!(
+            decode(SYSTEM_PROGRAM_ID, &[1, 2], &create),
+            NativeInstruction::System(SystemInstruction::CreateAccount {
+                source: key(1),
+                new_account: key(2),
+                lamports: 1_000,
+                space: 165,
+                owner: key(9),
+            })
+        );
+    }
+
+    #[test]
+    fn stake() {
+        assert_eq!(
+            decode(
+                STAKE_PROGRAM_ID,
+                &[1, 2, 3],
+                &tagged(3, &[&7u64.to_le_bytes()])
+            ),
+            NativeInstruction::Stake(StakeInstruction::Split {
+                stake_account: key(1),
+                new_split_account: key(2),
+                stake_authority: key(3),
+                lamports: 7,
+            })
+        );
+
+        // The custodian is only named when a lockup is in force
+        let authorize = tagged(1, &[&[9; 32], &1u32.to_le_bytes()]);
+        let authorized = |custodian| {
+            NativeInstruction::Stake(StakeInstruction::Authorize {
+                stake_account: key(1),
+                clock_sysvar: key(2),
+                authority: key(3),
+                new_authority: key(9),
+                authority_type: StakeAuthority::Withdrawer,
+                custodian,
+            })
+        };
+        assert_eq!(
+            decode(STAKE_PROGRAM_ID, &[1, 2, 3], &authorize),
+            authorized(None)
+        );
+        assert_eq!(
+            decode(STAKE_PROGRAM_ID, &[1, 2, 3, 4], &authorize),
+            authorized(Some(key(4)))
+        );
+
+        assert_eq!(
+            decode(STAKE_PROGRAM_ID, &[1], &tagged(13, &[])),
+            NativeInstruction::Stake(StakeInstruction::GetMinimumDelegation)
+        );
+    }
+
+    #[test]
+    fn vote() {
+        let vote = tagged(
+            2,
+            &[
+                &2u64.to_le_bytes(),
+                &100u64.to_le_bytes(),
+                &101u64.to_le_bytes(),
+                &[7; 32],
+                &[1],
+                &1_700_000_000i64.to_le_bytes(),
+            ],
+        );
+        assert_eq!(
+            decode(VOTE_PROGRAM_ID, &[1, 2, 3, 4], &vote),
+            NativeInstruction::Vote(VoteInstruction::Vote {
+                vote_account: key(1),
+                slot_hashes_sysvar: key(2),
+                clock_sysvar: key(3),
+                vote_authority: key(4),
+                vote: Vote {
+                    slots: vec![100, 101],
+                    hash: key(7),
+                    timestamp: Some(1_700_000_000),
+                },
+            })
+        );
+
+        assert_eq!(
+            decode(
+                VOTE_PROGRAM_ID,
+                &[1, 2, 3],
+                &tagged(3, &[&9u64.to_le_bytes()])
+            ),
+            NativeInstruction::Vote(VoteInstruction::Withdraw {
+                vote_account: key(1),
+                destination: key(2),
+                withdraw_authority: key(3),
+                lamports: 9,
+            })
+        );
+    }
+
+    #[test]
+    fn compute_budget() {
+        assert_eq!(
+            decode(
+                COMPUTE_BUDGET_PROGRAM_ID,
+                &[],
+                &[&[2][..], &300_000u32.to_le_bytes()].concat()
+            ),
+            NativeInstruction::ComputeBudget(ComputeBudgetInstruction::SetComputeUnitLimit {
+                units: 300_000
+            })
+        );
+        assert_eq!(
+            decode(
+                COMPUTE_BUDGET_PROGRAM_ID,
+                &[],
+                &[&[3][..], &5_000u64.to_le_bytes()].concat()
+            ),
+            NativeInstruction::ComputeBudget(ComputeBudgetInstruction::SetComputeUnitPrice {
+                micro_lamports: 5_000
+            })
+        );
+    }
+
+    #[test]
+    fn address_lookup_table() {
+        let create = tagged(0, &[&42u64.to_le_bytes(), &[254]]);
+        assert_eq!(
+            decode(LOOKUP_TABLE_PROGRAM_ID, &[1, 2, 3, 4], &create),
+            NativeInstruction::AddressLookupTable(
+                AddressLookupTableInstruction::CreateLookupTable {
+                    lookup_table_account: key(1),
+                    lookup_table_authority: key(2),
+                    payer_account: key(3),
+                    system_program: key(4),
+                    recent_slot: 42,
+                    bump_seed: 254,
+                }
+            )
+        );
+
+        // Only needs a payer when the table has to grow
+        let extend = tagged(2, &[&2u64.to_le_bytes(), &[8; 32], &[9; 32]]);
+        let extended = |payer_account, system_program| {
+            NativeInstruction::AddressLookupTable(
+                AddressLookupTableInstruction::ExtendLookupTable {
+                    lookup_table_account: key(1),
+                    lookup_table_authority: key(2),
+                    new_addresses: vec![key(8), key(9)],
+                    payer_account,
+                    system_program,
+                },
+            )
+        };
+        assert_eq!(
+            decode(LOOKUP_TABLE_PROGRAM_ID, &[1, 2], &extend),
+            extended(None, None)
+        );
+        assert_eq!(
+            decode(LOOKUP_TABLE_PROGRAM_ID, &[1, 2, 3, 4], &extend),
+            extended(Some(key(3)), Some(key(4)))
+        );
+    }
+
+    #[test]
+    fn memo() {
+        for program_id in [
+            "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+            "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+        ] {
+            assert_eq!(
+                decode(program_id, &[1], "gm ☀️".as_bytes()),
+                NativeInstruction::SplMemo(MemoInstruction::Memo("gm ☀️".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn token() {
+        let transfer = [&[3][..], &250u64.to_le_bytes()].concat();
+        assert_eq!(
+            decode(TOKEN_PROGRAM_ID, &[1, 2, 3], &transfer),
+            NativeInstruction::SplToken(TokenInstruction::Transfer {
+                source: key(1),
+                destination: key(2),
+                amount: "250".to_string(),
+                authority: Some(key(3)),
+                multisig_authority: None,
+                signers: None,
+            })
+        );
+
+        // Signed by a multisig, its signers follow it
+        assert_eq!(
+            decode(TOKEN_PROGRAM_ID, &[1, 2, 3, 4, 5], &transfer),
+            NativeInstruction::SplToken(TokenInstruction::Transfer {
+                source: key(1),
+                destination: key(2),
+                amount: "250".to_string(),
+                authority: None,
+                multisig_authority: Some(key(3)),
+                signers: Some(vec![key(4), key(5)]),
+            })
+        );
+
+        let transfer_checked = [&[12][..], &1_500_000u64.to_le_bytes(), &[6]].concat();
+        assert_eq!(
+            decode(TOKEN_2022_PROGRAM_ID, &[1, 2, 3, 4], &transfer_checked),
+            NativeInstruction::SplToken(TokenInstruction::TransferChecked {
+                source: key(1),
+                mint: key(2),
+                destination: key(3),
+                token_amount: UiTokenAmount {
+                    ui_amount: Some(1.5),
+                    decimals: 6,
+                    amount: "1500000".to_string(),
+                    ui_amount_string: "1.5".to_string(),
+                },
+                authority: Some(key(4)),
+                multisig_authority: None,
+                signers: None,
+            })
+        );
+
+        // Token-2022 extensions keep the SDK's own info
+        let close_authority = [&[25, 1][..], &[9; 32]].concat();
+        assert_eq!(
+            decode(TOKEN_2022_PROGRAM_ID, &[1], &close_authority),
+            NativeInstruction::SplToken(TokenInstruction::Extension {
+                instruction_type: "initializeMintCloseAuthority".to_string(),
+                info: json!({ "mint": key(1), "newAuthority": key(9) }),
+            })
+        );
+    }
+
+    #[test]
+    fn associated_token_account() {
+        let created = |data: &[u8]| decode(ASSOCIATED_TOKEN_PROGRAM_ID, &[1, 2, 3, 4, 5, 6], data);
+
+        // The original create instruction has no data at all
+        assert_eq!(
+            created(&[]),
+            NativeInstruction::SplAssociatedTokenAccount(AssociatedTokenInstruction::Create {
+                source: key(1),
+                account: key(2),
+                wallet: key(3),
+                mint: key(4),
+                system_program: key(5),
+                token_program: key(6),
+            })
+        );
+        assert_eq!(
+            created(&[1]),
+            NativeInstruction::SplAssociatedTokenAccount(
+                AssociatedTokenInstruction::CreateIdempotent {
+                    source: key(1),
+                    account: key(2),
+                    wallet: key(3),
+                    mint: key(4),
+                    system_program: key(5),
+                    token_program: key(6),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn serializes_like_the_sdk() {
+        let registry = DecoderRegistry::new();
+        let decoded = registry.decode(&instruction(
+            SYSTEM_PROGRAM_ID,
+            &[1, 2],
+            &tagged(2, &[&5_000u64.to_le_bytes()]),
+        ));
+
+        let value = serde_json::to_value(&decoded).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "format": "native",
+                "program": "system",
+                "type": "transfer",
+                "info": { "source": key(1), "destination": key(2), "lamports": 5_000 },
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<DecodedInstruction>(value).unwrap(),
+            decoded
+        );
+        assert_eq!(
+            decoded.name_and_info(),
+            Some((
+                "transfer".to_string(),
+                json!({ "source": key(1), "destination": key(2), "lamports": 5_000 })
+            ))
+        );
+
+        // Unknown programs, and data a decoder can't read, stay as raw base58
+        for unknown in [
+            instruction(&key(7), &[1], &[1, 2, 3]),
+            instruction(SYSTEM_PROGRAM_ID, &[1], &[0xff]),
+        ] {
+            assert_eq!(
+                registry.decode(&unknown),
+                DecodedInstruction::Raw {
+                    data: unknown.data.clone()
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::events::event::IndexEvent;
+
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+
+// How far behind the latest slot a subscriber can resume from
+const HISTORY_SLOTS: u64 = 150;
+// Batches a live subscriber can fall behind by before it's cut off. A block's
+// events go out as one batch however many transactions it holds.
+const CHANNEL_CAPACITY: usize = 1024;
+
+//
+// Fans events out from the indexer to whoever is listening, in the batches
+// they were published in (a block's worth, or the rollbacks of a fork). Recent
+// batches are also kept around, so a subscriber that drops can resume from the
+// last slot it saw rather than having to go back to the REST API to catch up.
+//
+
+pub type Events = Arc<[IndexEvent]>;
+
+pub struct EventBus {
+    sender: broadcast::Sender<Events>,
+    history: Mutex<History>,
+}
+
+#[derive(Default)]
+struct History {
+    batches: VecDeque<Events>,
+    // Latest slot events have been let go of from
+    dropped: Option<u64>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        EventBus {
+            sender,
+            history: Mutex::new(History::default()),
+        }
+    }
+
+    pub async fn publish(&self, events: Vec<IndexEvent>) {
+        let Some(latest) = events.iter().map(IndexEvent::slot).max() else {
+            return;
+        };
+        let events = Events::from(events);
+
+        // Sent while holding the history, so a subscriber sees each batch
+        // either in its backlog or live, never both or neither
+        let mut history = self.history.lock().await;
+        history.batches.push_back(events.clone());
+        let _ = self.sender.send(events);
+
+        while let Some(slot) = history
+            .batches
+            .front()
+            .map(|events| latest_slot(events))
+            .filter(|slot| slot + HISTORY_SLOTS < latest)
+        {
+            history.batches.pop_front();
+            history.dropped = history.dropped.max(Some(slot));
+        }
+    }
+
+    // Latest slot published so far
+    pub async fn latest_slot(&self) -> Option<u64> {
+        let history = self.history.lock().await;
+
+        history.batches.back().map(|events| latest_slot(events))
+    }
+
+    // Anything still held from `from_slot` on, along with everything after
+    pub async fn subscribe(
+        &self,
+        from_slot: Option<u64>,
+    ) -> (Vec<Events>, broadcast::Receiver<Events>) {
+        let history = self.history.lock().await;
+
+        self.subscribe_with(&history, from_slot)
+    }
+
+    // Like subscribe, but only if nothing from `from_slot` on has been let go
+    // of yet, so the backlog has everything a subscriber that was following
+    // up to then missed
+    pub async fn resume(
+        &self,
+        from_slot: u64,
+    ) -> Option<(Vec<Events>, broadcast::Receiver<Events>)> {
+        let history = self.history.lock().await;
+        if history.dropped.is_some_and(|dropped| dropped >= from_slot) {
+            return None;
+        }
+
+        Some(self.subscribe_with(&history, Some(from_slot)))
+    }
+
+    fn subscribe_with(
+        &self,
+        history: &History,
+        from_slot: Option<u64>,
+    ) -> (Vec<Events>, broadcast::Receiver<Events>) {
+        let receiver = self.sender.subscribe();
+
+        let Some(from_slot) = from_slot else {
+            return (Vec::new(), receiver);
+        };
+
+        // Batches that are partly from before `from_slot` are cut down to size
+        let backlog = history
+            .batches
+            .iter()
+            .filter(|events| latest_slot(events) >= from_slot)
+            .map(
+                |events| match events.iter().all(|event| event.slot() >= from_slot) {
+                    true => events.clone(),
+                    false => events
+                        .iter()
+                        .filter(|event| event.slot() >= from_slot)
+                        .cloned()
+                        .collect(),
+                },
+            )
+            .collect();
+
+        (backlog, receiver)
+    }
+}
+
+fn latest_slot(events: &[IndexEvent]) -> u64 {
+    events
+        .iter()
+        .map(IndexEvent::slot)
+        .max()
+        .unwrap_or_default()
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rollbacks(slots: impl Iterator<Item = u64>) -> Vec<IndexEvent> {
+        slots
+            .map(|slot| IndexEvent::Rollback {
+                slot,
+                blockhash: format!("block-{slot}"),
+                signatures: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn blocks_go_out_whole() {
+        let bus = EventBus::new();
+        let (_, mut receiver) = bus.subscribe(None).await;
+
+        // More events than there's room for batches
+        let events = rollbacks((0..CHANNEL_CAPACITY as u64 * 4).map(|_| 1));
+        bus.publish(events.clone()).await;
+
+        assert_eq!(*receiver.recv().await.unwrap(), events[..]);
+    }
+
+    #[tokio::test]
+    async fn resumes_from_what_is_held() {
+        let bus = EventBus::new();
+        bus.publish(rollbacks(1..=4)).await;
+        bus.publish(rollbacks(5..=6)).await;
+
+        // Batches from before the slot resumed from are cut down to size
+        let (backlog, _) = bus.resume(3).await.unwrap();
+        let slots: Vec<Vec<u64>> = backlog
+            .iter()
+            .map(|events| events.iter().map(IndexEvent::slot).collect())
+            .collect();
+        assert_eq!(slots, [vec![3, 4], vec![5, 6]]);
+
+        bus.publish(rollbacks([4 + HISTORY_SLOTS + 1].into_iter()))
+            .await;
+        assert!(bus.resume(4).await.is_none());
+        assert_eq!(bus.resume(5).await.unwrap().0.len(), 2);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::common::{account::Account, block::Block, transaction::Transaction};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+//
+// What the indexer announces once a block has been committed to the store.
+// Every event carries the slot it came from, which doubles as the cursor for
+// picking a stream back up where it left off, and a key that's the same every
+// time the same event is announced, for consumers to drop repeats with.
+//
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IndexEvent {
+    Block {
+        slot: u64,
+        block: Block,
+    },
+    Transaction {
+        slot: u64,
+        transaction: Box<Transaction>,
+    },
+    Balance {
+        slot: u64,
+        account: String,
+        signature: String,
+        pre_balance: u64,
+        balance: u64,
+    },
+    // Full state of a watched account
+    Account {
+        slot: u64,
+        account: Account,
+    },
+    // A block already announced turned out not to be on the canonical chain,
+    // taking its transactions with it
+    Rollback {
+        slot: u64,
+        blockhash: String,
+        signatures: Vec<String>,
+    },
+}
+
+impl IndexEvent {
+    // The block, then each stored transaction followed by the balances it
+    // changed
+    pub fn from_block(block: &Block, transactions: &[Transaction]) -> Vec<IndexEvent> {
+        let mut events = vec![IndexEvent::Block {
+            slot: block.slot,
+            block: block.clone(),
+        }];
+
+        for transaction in transactions {
+            events.push(IndexEvent::Transaction {
+                slot: transaction.slot,
+                transaction: Box::new(transaction.clone()),
+            });
+
+            let balances = transaction
+                .accounts
+                .iter()
+                .zip(&transaction.pre_balances)
+                .zip(&transaction.balances);
+
+            for ((account, pre_balance), balance) in balances {
+                if pre_balance != balance {
+                    events.push(IndexEvent::Balance {
+                        slot: transaction.slot,
+                        account: account.clone(),
+                        signature: transaction.id.clone(),
+                        pre_balance: *pre_balance,
+                        balance: *balance,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+
+    // Only accounts we have the full state of, which is where the slot
+    // comes from
+    pub fn from_account(account: &Account) -> Option<IndexEvent> {
+        Some(IndexEvent::Account {
+            slot: account.state.as_ref()?.slot,
+            account: account.clone(),
+        })
+    }
+
+    pub fn slot(&self) -> u64 {
+        match self {
+            IndexEvent::Block { slot, .. }
+            | IndexEvent::Transaction { slot, .. }
+            | IndexEvent::Balance { slot, .. }
+            | IndexEvent::Account { slot, .. }
+            | IndexEvent::Rollback { slot, .. } => *slot,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IndexEvent::Block { .. } => "block",
+            IndexEvent::Transaction { .. } => "transaction",
+            IndexEvent::Balance { .. } => "balance",
+            IndexEvent::Account { .. } => "account",
+            IndexEvent::Rollback { .. } => "rollback",
+        }
+    }
+
+    pub fn key(&self) -> String {
+        match self {
+            IndexEvent::Block { block, .. } => format!("block:{}", block.id),
+            IndexEvent::Transaction { transaction, .. } => {
+                format!("transaction:{}", transaction.id)
+            }
+            IndexEvent::Balance {
+                account, signature, ..
+            } => format!("balance:{signature}:{account}"),
+            // Accounts can be written more than once a slot
+            IndexEvent::Account { slot, account } => format!(
+                "account:{}:{slot}:{}",
+                account.id,
+                bs58::encode(Sha256::digest(
+                    serde_json::to_vec(account).unwrap_or_default()
+                ))
+                .into_string()
+            ),
+            IndexEvent::Rollback { blockhash, .. } => format!("rollback:{blockhash}"),
+        }
+    }
+}
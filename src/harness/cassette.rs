@@ -0,0 +1,69 @@
+use crate::harness::errors::HarnessError;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+use tokio::{
+    fs::{self, File},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+//
+// A cassette is an NDJSON log of the traffic between the indexer and the
+// cluster, in the order it happened. Websocket traffic is grouped into
+// sessions, one per connection, since several can be open at once.
+//
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Exchange {
+    // A JSON-RPC request over HTTP along with its response
+    Rpc { request: Value, response: Value },
+    Connect { session: u64 },
+    // Sent by the indexer
+    Sent { session: u64, message: Value },
+    // Sent by the cluster
+    Received { session: u64, message: Value },
+    // The cluster hung up
+    Disconnect { session: u64 },
+}
+
+pub struct CassetteWriter {
+    file: Mutex<File>,
+}
+
+impl CassetteWriter {
+    pub async fn create(path: &Path) -> Result<Self, HarnessError> {
+        Ok(CassetteWriter {
+            file: Mutex::new(File::create(path).await?),
+        })
+    }
+
+    pub async fn record(&self, exchange: &Exchange) {
+        let mut line = json!(exchange).to_string();
+        line.push('\n');
+
+        let mut file = self.file.lock().await;
+        if let Err(error) = file.write_all(line.as_bytes()).await {
+            println!("Error recording to cassette: {error}");
+        }
+        let _ = file.flush().await;
+    }
+}
+
+pub async fn load(path: &Path) -> Result<Vec<Exchange>, HarnessError> {
+    fs::read_to_string(path)
+        .await?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+// Requests are matched on what they ask for rather than their id, which
+// differs from run to run
+pub fn request_key(request: &Value) -> String {
+    json!([request["method"], request["params"]]).to_string()
+}
@@ -0,0 +1,40 @@
+use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum HarnessError {
+    #[error("Cassette file error")]
+    FileError,
+    #[error("Invalid cassette")]
+    InvalidCassette,
+    #[error("No recorded response")]
+    NoRecording,
+    #[error("Upstream RPC error")]
+    UpstreamError,
+    #[error("Websocket error")]
+    WebsocketError,
+}
+
+impl From<std::io::Error> for HarnessError {
+    fn from(_error: std::io::Error) -> Self {
+        HarnessError::FileError
+    }
+}
+
+impl From<serde_json::Error> for HarnessError {
+    fn from(_error: serde_json::Error) -> Self {
+        HarnessError::InvalidCassette
+    }
+}
+
+impl From<reqwest::Error> for HarnessError {
+    fn from(_error: reqwest::Error) -> Self {
+        HarnessError::UpstreamError
+    }
+}
+
+impl From<tungstenite::Error> for HarnessError {
+    fn from(_error: tungstenite::Error) -> Self {
+        HarnessError::WebsocketError
+    }
+}
@@ -0,0 +1,5 @@
+pub mod cassette;
+pub mod errors;
+pub mod record;
+pub mod replay;
+pub mod server;
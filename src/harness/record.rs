@@ -0,0 +1,91 @@
+use crate::harness::{
+    cassette::{CassetteWriter, Exchange},
+    errors::HarnessError,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::{net::TcpStream, select};
+use tokio_tungstenite::{connect_async, tungstenite::Message, WebSocketStream};
+
+// Passes traffic through to the cluster, writing down everything it sees
+pub struct Recorder {
+    rpc_url: String,
+    websocket_url: String,
+    client: reqwest::Client,
+    cassette: CassetteWriter,
+    sessions: AtomicU64,
+}
+
+impl Recorder {
+    pub fn new(rpc_url: String, websocket_url: String, cassette: CassetteWriter) -> Self {
+        Recorder {
+            rpc_url,
+            websocket_url,
+            client: reqwest::Client::new(),
+            cassette,
+            sessions: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn rpc(&self, request: Value) -> Result<Value, HarnessError> {
+        let response: Value = self
+            .client
+            .post(&self.rpc_url)
+            .json(&request)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        self.cassette
+            .record(&Exchange::Rpc {
+                request,
+                response: response.clone(),
+            })
+            .await;
+
+        Ok(response)
+    }
+
+    pub async fn websocket(&self, client: WebSocketStream<TcpStream>) -> Result<(), HarnessError> {
+        let session = self.sessions.fetch_add(1, Ordering::Relaxed);
+        let (upstream, _) = connect_async(&self.websocket_url).await?;
+
+        self.cassette.record(&Exchange::Connect { session }).await;
+
+        let (mut client_tx, mut client_rx) = client.split();
+        let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+        loop {
+            select! {
+                message = client_rx.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(message) = serde_json::from_str(&text) {
+                            self.cassette.record(&Exchange::Sent { session, message }).await;
+                        }
+                        upstream_tx.send(Message::Text(text)).await?;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                message = upstream_rx.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(message) = serde_json::from_str(&text) {
+                            self.cassette.record(&Exchange::Received { session, message }).await;
+                        }
+                        client_tx.send(Message::Text(text)).await?;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        self.cassette.record(&Exchange::Disconnect { session }).await;
+                        break;
+                    }
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use crate::harness::{
+    cassette::{request_key, Exchange},
+    errors::HarnessError,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tokio::{net::TcpStream, sync::Mutex};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+//
+// Plays a cassette back in place of the cluster. RPC requests get the
+// responses recorded for the same request, in order, repeating the last one
+// once they run out. Each websocket connection is matched to an unused
+// recorded session by its first message and then follows that session's
+// script, including hanging up where the cluster did.
+//
+
+#[derive(Default)]
+struct RecordedResponses {
+    remaining: VecDeque<Value>,
+    last: Option<Value>,
+}
+
+enum Step {
+    Sent(Value),
+    Received(Value),
+    Disconnect,
+}
+
+pub struct Replayer {
+    rpc: Mutex<HashMap<String, RecordedResponses>>,
+    sessions: Mutex<Vec<Option<Vec<Step>>>>,
+}
+
+impl Replayer {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        let mut rpc: HashMap<String, RecordedResponses> = HashMap::new();
+        let mut sessions: BTreeMap<u64, Vec<Step>> = BTreeMap::new();
+
+        for exchange in exchanges {
+            match exchange {
+                Exchange::Rpc { request, response } => rpc
+                    .entry(request_key(&request))
+                    .or_default()
+                    .remaining
+                    .push_back(response),
+                Exchange::Connect { session } => {
+                    sessions.entry(session).or_default();
+                }
+                Exchange::Sent { session, message } => sessions
+                    .entry(session)
+                    .or_default()
+                    .push(Step::Sent(message)),
+                Exchange::Received { session, message } => sessions
+                    .entry(session)
+                    .or_default()
+                    .push(Step::Received(message)),
+                Exchange::Disconnect { session } => {
+                    sessions.entry(session).or_default().push(Step::Disconnect)
+                }
+            }
+        }
+
+        Replayer {
+            rpc: Mutex::new(rpc),
+            sessions: Mutex::new(sessions.into_values().map(Some).collect()),
+        }
+    }
+
+    pub async fn rpc(&self, request: &Value) -> Result<Value, HarnessError> {
+        let mut rpc = self.rpc.lock().await;
+        let key = request_key(request);
+
+        let response = rpc.get_mut(&key).and_then(|responses| {
+            if let Some(response) = responses.remaining.pop_front() {
+                responses.last = Some(response);
+            }
+            responses.last.clone()
+        });
+
+        match response {
+            // Hand edited cassettes can hold anything, so only objects get the
+            // request's id put back
+            Some(Value::Object(mut response)) => {
+                response.insert("id".to_string(), request["id"].clone());
+                Ok(Value::Object(response))
+            }
+            Some(_) => Err(HarnessError::InvalidCassette),
+            None => {
+                println!("No recorded response for {key}");
+                Err(HarnessError::NoRecording)
+            }
+        }
+    }
+
+    pub async fn websocket(&self, client: WebSocketStream<TcpStream>) -> Result<(), HarnessError> {
+        let (mut client_tx, mut client_rx) = client.split();
+
+        let Some(first) = next_request(&mut client_rx).await else {
+            return Ok(());
+        };
+
+        let Some(script) = self.take_session(&first).await else {
+            println!("No recorded websocket session for {first}");
+            return Ok(());
+        };
+
+        // The first step is the request we've already matched on
+        let mut request_id = first["id"].clone();
+
+        for step in script.into_iter().skip(1) {
+            match step {
+                Step::Sent(_) => match next_request(&mut client_rx).await {
+                    Some(request) => request_id = request["id"].clone(),
+                    None => return Ok(()),
+                },
+                Step::Received(mut message) => {
+                    // Responses carry the id of the request, notifications don't
+                    if message.get("id").is_some() {
+                        message["id"] = request_id.clone();
+                    }
+                    client_tx.send(Message::Text(message.to_string())).await?;
+                }
+                Step::Disconnect => {
+                    client_tx.close().await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        // Nothing more was recorded, so just wait for the indexer to hang up
+        while next_request(&mut client_rx).await.is_some() {}
+
+        Ok(())
+    }
+
+    async fn take_session(&self, first: &Value) -> Option<Vec<Step>> {
+        let key = request_key(first);
+
+        self.sessions
+            .lock()
+            .await
+            .iter_mut()
+            .find(|session| {
+                matches!(
+                    session.as_ref().and_then(|script| script.first()),
+                    Some(Step::Sent(message)) if request_key(message) == key
+                )
+            })?
+            .take()
+    }
+}
+
+async fn next_request<S>(client: &mut S) -> Option<Value>
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    while let Some(Ok(message)) = client.next().await {
+        match message {
+            Message::Text(text) => {
+                if let Ok(request) = serde_json::from_str(&text) {
+                    return Some(request);
+                }
+            }
+            Message::Close(_) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn replayer(response: Value) -> Replayer {
+        Replayer::new(vec![Exchange::Rpc {
+            request: json!({ "jsonrpc": "2.0", "id": 1, "method": "getSlot" }),
+            response,
+        }])
+    }
+
+    #[tokio::test]
+    async fn responses_take_the_request_id() {
+        let replayer = replayer(json!({ "jsonrpc": "2.0", "result": 42, "id": 1 }));
+        let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "getSlot" });
+
+        let response = replayer.rpc(&request).await.unwrap();
+        assert_eq!(response, json!({ "jsonrpc": "2.0", "result": 42, "id": 7 }));
+    }
+
+    #[tokio::test]
+    async fn unusable_recordings_are_an_error() {
+        let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "getSlot" });
+
+        let replayer = replayer(json!([{ "jsonrpc": "2.0", "result": 42, "id": 1 }]));
+        assert_eq!(
+            replayer.rpc(&request).await,
+            Err(HarnessError::InvalidCassette)
+        );
+
+        let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "getEpochInfo" });
+        assert_eq!(replayer.rpc(&request).await, Err(HarnessError::NoRecording));
+    }
+}
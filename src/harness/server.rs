@@ -0,0 +1,217 @@
+use crate::harness::{
+    cassette::{self, CassetteWriter},
+    record::Recorder,
+    replay::Replayer,
+};
+
+use serde_json::{json, Value};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+use tokio_tungstenite::accept_async;
+
+use axum::{
+    extract::State,
+    routing::{post, Router},
+    Json,
+};
+
+use tokio::{
+    join,
+    net::{TcpListener, TcpStream},
+    select, spawn,
+    sync::broadcast::Sender,
+    task::JoinHandle,
+};
+
+// Ports are picked by the OS, so several harnesses can run side by side
+const HARNESS_ADDRESS: &str = "127.0.0.1:0";
+
+//
+// Stands in for the cluster so the indexer can be run against recorded
+// traffic. When enabled the indexer talks to the harness rather than the
+// cluster, and the harness either passes everything through while recording
+// it to a cassette, or plays a cassette back without any network at all.
+//
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HarnessMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl FromStr for HarnessMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.split_once(':') {
+            Some(("record", path)) if !path.is_empty() => Ok(HarnessMode::Record(path.into())),
+            Some(("replay", path)) if !path.is_empty() => Ok(HarnessMode::Replay(path.into())),
+            _ => Err(format!("Unknown harness mode: {mode}")),
+        }
+    }
+}
+
+enum Backend {
+    Record(Recorder),
+    Replay(Replayer),
+}
+
+impl Backend {
+    async fn rpc(&self, request: Value) -> Value {
+        let response = match self {
+            Backend::Record(recorder) => recorder.rpc(request.clone()).await,
+            Backend::Replay(replayer) => replayer.rpc(&request).await,
+        };
+
+        match response {
+            Ok(response) => response,
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32603, "message": error.to_string() },
+                "id": request["id"],
+            }),
+        }
+    }
+
+    async fn websocket(&self, stream: TcpStream) {
+        let client = match accept_async(stream).await {
+            Ok(client) => client,
+            Err(error) => {
+                println!("Harness websocket handshake failed: {error}");
+                return;
+            }
+        };
+
+        let result = match self {
+            Backend::Record(recorder) => recorder.websocket(client).await,
+            Backend::Replay(replayer) => replayer.websocket(client).await,
+        };
+
+        if let Err(error) = result {
+            println!("Harness websocket error: {error}");
+        }
+    }
+}
+
+pub struct Harness {
+    task: Option<JoinHandle<()>>,
+    mode: Option<HarnessMode>,
+    // Where to record from
+    rpc_url: String,
+    websocket_url: String,
+    // Where the harness ended up listening, once started
+    addresses: Option<(SocketAddr, SocketAddr)>,
+    tx: Sender<()>,
+}
+
+impl Harness {
+    pub fn new(
+        mode: Option<HarnessMode>,
+        rpc_url: String,
+        websocket_url: String,
+        tx: Sender<()>,
+    ) -> Self {
+        Harness {
+            task: None,
+            mode,
+            rpc_url,
+            websocket_url,
+            addresses: None,
+            tx,
+        }
+    }
+
+    // The RPC and websocket URLs to reach the harness on, once it's running
+    pub fn urls(&self) -> Option<(String, String)> {
+        self.addresses.map(|(rpc_address, websocket_address)| {
+            (
+                format!("http://{rpc_address}"),
+                format!("ws://{websocket_address}"),
+            )
+        })
+    }
+
+    pub async fn start(&mut self) {
+        if self.task.is_some() {
+            println!("Harness already running");
+            return;
+        }
+
+        let Some(mode) = &self.mode else {
+            return;
+        };
+
+        println!("Starting harness...");
+
+        let backend = Arc::new(match mode {
+            HarnessMode::Record(path) => Backend::Record(Recorder::new(
+                self.rpc_url.clone(),
+                self.websocket_url.clone(),
+                CassetteWriter::create(path)
+                    .await
+                    .expect("Error creating cassette"),
+            )),
+            HarnessMode::Replay(path) => Backend::Replay(Replayer::new(
+                cassette::load(path).await.expect("Error loading cassette"),
+            )),
+        });
+
+        // Bound up front so the indexer can't beat us to it
+        let rpc_listener = TcpListener::bind(HARNESS_ADDRESS)
+            .await
+            .expect("Error binding harness RPC port");
+        let websocket_listener = TcpListener::bind(HARNESS_ADDRESS)
+            .await
+            .expect("Error binding harness websocket port");
+        self.addresses = rpc_listener
+            .local_addr()
+            .ok()
+            .zip(websocket_listener.local_addr().ok());
+
+        self.task = Some(spawn(async move {
+            let routes = Router::new()
+                .route("/", post(rpc))
+                .with_state(backend.clone());
+
+            let websockets = async {
+                while let Ok((stream, _)) = websocket_listener.accept().await {
+                    spawn({
+                        let backend = backend.clone();
+                        async move { backend.websocket(stream).await }
+                    });
+                }
+            };
+
+            let _ = join!(axum::serve(rpc_listener, routes), websockets);
+        }));
+
+        if let Some((rpc_url, websocket_url)) = self.urls() {
+            println!("Harness started on {rpc_url} and {websocket_url}")
+        }
+    }
+
+    pub async fn stop(mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Stopping harness...");
+            task.abort();
+            println!("Harness stopped!");
+        }
+    }
+
+    pub async fn wait(mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Harness running");
+            let mut rx = self.tx.subscribe();
+
+            select! {
+                _ = task => println!("Harness stopped on its own"),
+                _ = rx.recv() => println!("Harness interrupted"),
+            }
+        }
+
+        self.stop().await;
+    }
+}
+
+async fn rpc(State(backend): State<Arc<Backend>>, Json(request): Json<Value>) -> Json<Value> {
+    Json(backend.rpc(request).await)
+}
@@ -0,0 +1,277 @@
+use crate::{
+    common::account::Account,
+    database::{common::DbQuery, server::DbConnection},
+    events::{bus::EventBus, event::IndexEvent},
+    indexer::errors::IndexerError,
+};
+
+use futures_util::{
+    stream::{select_all, BoxStream},
+    StreamExt,
+};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+};
+
+use tokio::{
+    select,
+    sync::{broadcast::Sender, Mutex},
+    task::JoinHandle,
+    time::sleep,
+};
+
+const RECONNECT_DELAY: u64 = 1000;
+// Most accounts getMultipleAccounts takes at once
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+//
+// Keeps the full state of watched accounts up to date, rather than just the
+// balances seen in passing in transactions. Watched programs and addresses are
+// seeded over RPC and then followed over pubsub, starting over whenever the
+// subscriptions are lost.
+//
+
+pub struct AccountIndexer {
+    task: Option<JoinHandle<()>>,
+    db_connection: Arc<Mutex<DbConnection>>,
+    rpc_url: String,
+    websocket_url: String,
+    programs: Vec<String>,
+    accounts: Vec<String>,
+    events: Arc<EventBus>,
+    tx: Sender<()>,
+}
+
+impl AccountIndexer {
+    pub fn new(
+        db_connection: Arc<Mutex<DbConnection>>,
+        rpc_url: String,
+        websocket_url: String,
+        programs: Vec<String>,
+        accounts: Vec<String>,
+        events: Arc<EventBus>,
+        tx: Sender<()>,
+    ) -> Self {
+        AccountIndexer {
+            task: None,
+            db_connection,
+            rpc_url,
+            websocket_url,
+            programs,
+            accounts,
+            events,
+            tx,
+        }
+    }
+
+    pub async fn start(&mut self) {
+        if self.task.is_some() {
+            println!("Account indexer already running");
+            return;
+        }
+
+        if self.programs.is_empty() && self.accounts.is_empty() {
+            return;
+        }
+
+        println!("Starting account indexer...");
+
+        self.task = Some(tokio::spawn({
+            let db_connection = self.db_connection.clone();
+            let rpc_url = self.rpc_url.clone();
+            let websocket_url = self.websocket_url.clone();
+            let programs = self.programs.clone();
+            let accounts = self.accounts.clone();
+            let events = self.events.clone();
+
+            async move {
+                if let Err(error) = watch_accounts(
+                    db_connection,
+                    &events,
+                    &rpc_url,
+                    &websocket_url,
+                    &programs,
+                    &accounts,
+                )
+                .await
+                {
+                    println!("Account indexer error: {error}");
+                }
+            }
+        }));
+
+        println!("Account indexer started")
+    }
+
+    pub async fn stop(mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Stopping account indexer...");
+            task.abort();
+            println!("Account indexer stopped!");
+        }
+    }
+
+    pub async fn wait(mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Account indexer running");
+            let mut rx = self.tx.subscribe();
+
+            select! {
+                _ = task => println!("Account indexer stopped on its own"),
+                _ = rx.recv() => println!("Account indexer interrupted"),
+            }
+        }
+
+        self.stop().await;
+    }
+}
+
+async fn watch_accounts(
+    db_connection: Arc<Mutex<DbConnection>>,
+    events: &EventBus,
+    rpc_url: &str,
+    websocket_url: &str,
+    programs: &[String],
+    accounts: &[String],
+) -> Result<(), IndexerError> {
+    let programs = parse_pubkeys(programs)?;
+    let accounts = parse_pubkeys(accounts)?;
+    let rpc_client = RpcClient::new(rpc_url.to_string());
+
+    // Seeded again after reconnecting, to pick up writes made in between
+    loop {
+        if let Err(error) = follow_accounts(
+            &db_connection,
+            events,
+            &rpc_client,
+            websocket_url,
+            &programs,
+            &accounts,
+        )
+        .await
+        {
+            println!("Account subscriptions lost ({error}), reconnecting...");
+            sleep(Duration::from_millis(RECONNECT_DELAY)).await;
+        }
+    }
+}
+
+// Only returns once the subscriptions are lost
+async fn follow_accounts(
+    db_connection: &Mutex<DbConnection>,
+    events: &EventBus,
+    rpc_client: &RpcClient,
+    websocket_url: &str,
+    programs: &[Pubkey],
+    accounts: &[Pubkey],
+) -> Result<(), IndexerError> {
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+    let program_config = RpcProgramAccountsConfig {
+        account_config: account_config.clone(),
+        ..Default::default()
+    };
+
+    // Subscribe before seeding so no writes are missed in between. Anything
+    // older than what we already have is ignored by the database.
+    let pubsub_client = PubsubClient::new(websocket_url).await?;
+    let mut subscriptions: Vec<BoxStream<(String, u64, UiAccount)>> = Vec::new();
+
+    for program in programs {
+        let (notifications, _) = pubsub_client
+            .program_subscribe(program, Some(program_config.clone()))
+            .await?;
+        subscriptions.push(
+            notifications
+                .map(|response| {
+                    let account = response.value;
+                    (account.pubkey, response.context.slot, account.account)
+                })
+                .boxed(),
+        );
+    }
+
+    for account in accounts {
+        let (notifications, _) = pubsub_client
+            .account_subscribe(account, Some(account_config.clone()))
+            .await?;
+        let id = account.to_string();
+        subscriptions.push(
+            notifications
+                .map(move |response| (id.clone(), response.context.slot, response.value))
+                .boxed(),
+        );
+    }
+
+    let slot = rpc_client.get_slot().await?;
+
+    for program in programs {
+        for (id, account) in rpc_client
+            .get_program_accounts_with_config(program, program_config.clone())
+            .await?
+        {
+            insert_account(
+                db_connection,
+                events,
+                Account::from_sdk_account(id.to_string(), &account, slot),
+            )
+            .await;
+        }
+    }
+
+    for accounts in accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let response = rpc_client
+            .get_multiple_accounts_with_commitment(accounts, CommitmentConfig::confirmed())
+            .await?;
+
+        for (id, account) in accounts.iter().zip(response.value) {
+            if let Some(account) = account {
+                insert_account(
+                    db_connection,
+                    events,
+                    Account::from_sdk_account(id.to_string(), &account, response.context.slot),
+                )
+                .await;
+            }
+        }
+    }
+
+    let mut notifications = select_all(subscriptions);
+    while let Some((id, slot, account)) = notifications.next().await {
+        if let Some(account) = Account::from_ui_account(id, &account, slot) {
+            insert_account(db_connection, events, account).await;
+        }
+    }
+
+    Err(IndexerError::WebsocketClientError)
+}
+
+async fn insert_account(db_connection: &Mutex<DbConnection>, events: &EventBus, account: Account) {
+    println!("Found account state: {:?}", account.id);
+
+    let _ = db_connection
+        .lock()
+        .await
+        .query(&DbQuery::InsertAccount(account.clone()))
+        .await;
+
+    if let Some(event) = IndexEvent::from_account(&account) {
+        events.publish(vec![event]).await;
+    }
+}
+
+fn parse_pubkeys(pubkeys: &[String]) -> Result<Vec<Pubkey>, IndexerError> {
+    pubkeys
+        .iter()
+        .map(|pubkey| Pubkey::from_str(pubkey).map_err(|_| IndexerError::InvalidAddress))
+        .collect()
+}
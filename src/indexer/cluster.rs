@@ -0,0 +1,37 @@
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+//
+// How far along the cluster itself is, as last reported by the source, so the
+// API can tell how far behind the index is. Sources that aren't following a
+// cluster, like replaying from disk, never report anything.
+//
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct ClusterTip {
+    pub slot: Option<u64>,
+    pub finalized_slot: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct ClusterState {
+    tip: RwLock<ClusterTip>,
+}
+
+impl ClusterState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub async fn tip(&self) -> ClusterTip {
+        *self.tip.read().await
+    }
+
+    // Only ever moves forward, as notifications can arrive out of order
+    pub async fn update(&self, slot: u64, finalized_slot: Option<u64>) {
+        let mut tip = self.tip.write().await;
+
+        tip.slot = tip.slot.max(Some(slot));
+        tip.finalized_slot = tip.finalized_slot.max(finalized_slot);
+    }
+}
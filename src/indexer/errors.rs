@@ -1,6 +1,7 @@
 use solana_client::client_error::ClientError;
 use solana_pubsub_client::nonblocking::pubsub_client::PubsubClientError;
 use thiserror::Error;
+use tokio_tungstenite::tungstenite;
 
 #[derive(Debug, Clone, PartialEq, Error)]
 pub enum IndexerError {
@@ -8,6 +9,10 @@ pub enum IndexerError {
     WebsocketClientError,
     #[error("Client error")]
     ClientError,
+    #[error("Invalid address")]
+    InvalidAddress,
+    #[error("File error")]
+    FileError,
 }
 
 impl From<PubsubClientError> for IndexerError {
@@ -21,3 +26,15 @@ impl From<ClientError> for IndexerError {
         IndexerError::ClientError
     }
 }
+
+impl From<tungstenite::Error> for IndexerError {
+    fn from(_error: tungstenite::Error) -> Self {
+        IndexerError::WebsocketClientError
+    }
+}
+
+impl From<std::io::Error> for IndexerError {
+    fn from(_error: std::io::Error) -> Self {
+        IndexerError::FileError
+    }
+}
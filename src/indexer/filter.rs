@@ -0,0 +1,113 @@
+use crate::common::transaction::Transaction;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::RwLock;
+
+//
+// Decides which transactions get stored. With no include lists everything is
+// included; otherwise a transaction has to touch one of the included accounts
+// or invoke one of the included programs. Excludes always win.
+//
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexFilter {
+    pub include_accounts: BTreeSet<String>,
+    pub exclude_accounts: BTreeSet<String>,
+    pub include_programs: BTreeSet<String>,
+    pub exclude_programs: BTreeSet<String>,
+    pub success_only: bool,
+    pub min_fee: Option<u64>,
+}
+
+impl IndexFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        if self.success_only && transaction.err.is_some() {
+            return false;
+        }
+
+        if self
+            .min_fee
+            .is_some_and(|min_fee| transaction.fees < min_fee)
+        {
+            return false;
+        }
+
+        let program_ids = transaction.program_ids();
+
+        let excluded = transaction
+            .accounts
+            .iter()
+            .any(|account| self.exclude_accounts.contains(account))
+            || program_ids
+                .iter()
+                .any(|program_id| self.exclude_programs.contains(*program_id));
+        if excluded {
+            return false;
+        }
+
+        if self.include_accounts.is_empty() && self.include_programs.is_empty() {
+            return true;
+        }
+
+        transaction
+            .accounts
+            .iter()
+            .any(|account| self.include_accounts.contains(account))
+            || program_ids
+                .iter()
+                .any(|program_id| self.include_programs.contains(*program_id))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FilterCounters {
+    pub matched: u64,
+    pub dropped: u64,
+}
+
+//
+// Shared between the indexer and the API server so the filter can be swapped
+// out while the indexer is running
+//
+
+#[derive(Debug, Default)]
+pub struct FilterState {
+    filter: RwLock<IndexFilter>,
+    matched: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl FilterState {
+    pub fn new(filter: IndexFilter) -> Self {
+        FilterState {
+            filter: RwLock::new(filter),
+            ..Default::default()
+        }
+    }
+
+    // A copy, so a block in progress doesn't hold up changes to the filter
+    pub async fn filter(&self) -> IndexFilter {
+        self.filter.read().await.clone()
+    }
+
+    pub async fn set_filter(&self, filter: IndexFilter) {
+        *self.filter.write().await = filter;
+    }
+
+    pub fn record(&self, matched: u64, dropped: u64) {
+        self.matched.fetch_add(matched, Ordering::Relaxed);
+        self.dropped.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    pub fn counters(&self) -> FilterCounters {
+        FilterCounters {
+            matched: self.matched.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
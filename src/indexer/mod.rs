@@ -1,2 +1,5 @@
+pub mod accounts;
+pub mod cluster;
 pub mod errors;
+pub mod filter;
 pub mod server;
@@ -1,43 +1,61 @@
 use crate::{
-    common::{account::Account, block::Block, transaction::Transaction},
+    common::{account::Account, block::Block, transaction::Transaction, vote::VoteTransaction},
+    config::settings::VotePolicy,
     database::{common::DbQuery, server::DbConnection},
-    indexer::errors::IndexerError,
+    decoder::registry::DecoderRegistry,
+    events::{bus::EventBus, event::IndexEvent},
+    indexer::{cluster::ClusterState, errors::IndexerError, filter::FilterState},
+    source::common::{Source, SourceUpdate},
 };
 
-use futures_util::StreamExt;
-use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
-use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_transaction_status::UiConfirmedBlock;
-use std::sync::Arc;
-
-use solana_client::{
-    client_error::ClientErrorKind, nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig,
-    rpc_request::RpcError,
-};
+use std::{collections::VecDeque, sync::Arc};
 
 use tokio::{
-    select,
-    sync::{broadcast::Sender, Mutex},
+    join, select,
+    sync::{broadcast::Sender, mpsc, Mutex},
     task::JoinHandle,
-    time::sleep,
 };
 
-const WEBSOCKET_URL: &str = "ws://api.testnet.solana.com";
-const RPC_URL: &str = "http://api.testnet.solana.com";
-const SLOT_TIME: u64 = 400;
-const RETRIES_PER_SLOT: u64 = 3;
+// Updates a source can get ahead of the indexer by before it has to wait
+const SOURCE_BUFFER: usize = 64;
+// Blocks remembered for noticing when one drops off the canonical chain
+const RECENT_BLOCKS: usize = 64;
+
+struct RecentBlock {
+    id: String,
+    slot: u64,
+    signatures: Vec<String>,
+}
+
+//
+// Everything the indexer shares with the rest of the process, as it's handed
+// blocks to store
+//
+
+#[derive(Clone)]
+pub struct IndexerContext {
+    pub db_connection: Arc<Mutex<DbConnection>>,
+    pub decoders: Arc<DecoderRegistry>,
+    pub vote_policy: VotePolicy,
+    pub filter: Arc<FilterState>,
+    pub cluster: Arc<ClusterState>,
+    pub events: Arc<EventBus>,
+}
 
 pub struct Indexer {
     task: Option<JoinHandle<()>>,
-    db_connection: Arc<Mutex<DbConnection>>,
+    source: Arc<dyn Source>,
+    context: IndexerContext,
     tx: Sender<()>,
 }
 
 impl Indexer {
-    pub fn new(db_connection: Arc<Mutex<DbConnection>>, tx: Sender<()>) -> Self {
+    pub fn new(source: Arc<dyn Source>, context: IndexerContext, tx: Sender<()>) -> Self {
         Indexer {
             task: None,
-            db_connection,
+            source,
+            context,
             tx,
         }
     }
@@ -51,69 +69,49 @@ impl Indexer {
         println!("Starting indexer...");
 
         self.task = Some(tokio::spawn({
-            let db_connection = self.db_connection.clone();
+            let source = self.source.clone();
+            let context = self.context.clone();
 
             async move {
-                let rpc_client = RpcClient::new(RPC_URL.to_string());
-                let pubsub_client = Arc::new(
-                    PubsubClient::new(WEBSOCKET_URL)
-                        .await
-                        .expect("Error subscribing to websocket"),
-                );
-                let (mut slot_notifications, _) = pubsub_client
-                    .slot_subscribe()
-                    .await
-                    .expect("Error subscribing to slot notifications");
-
-                while let Some(slot_info) = slot_notifications.next().await {
-                    let db_connection = db_connection.clone();
-
-                    loop {
-                        let encoded_block = rpc_client
-                            .get_block_with_config(
-                                slot_info.slot - 100,
-                                RpcBlockConfig {
-                                    max_supported_transaction_version: Some(0),
-                                    commitment: Some(CommitmentConfig {
-                                        commitment: CommitmentLevel::Confirmed,
-                                    }),
-                                    ..Default::default()
-                                },
-                            )
-                            .await;
-
-                        match encoded_block {
-                            Ok(encoded_block) => {
-                                // TODO: Handle reorgs
-
-                                let _ = process_block(db_connection, &encoded_block).await;
-
-                                // Done. Move to the next slot
-                                break;
+                let (updates_tx, mut updates) = mpsc::channel(SOURCE_BUFFER);
+
+                let producer = async {
+                    if let Err(error) = source.run(updates_tx).await {
+                        println!("Source error: {error}");
+                    }
+                };
+
+                let consumer = async {
+                    let mut recent = VecDeque::new();
+
+                    while let Some(update) = updates.recv().await {
+                        match update {
+                            SourceUpdate::Block { slot, block } => {
+                                let _ = process_block(&context, &mut recent, slot, &block).await;
                             }
-                            Err(error) => match error.kind() {
-                                ClientErrorKind::RpcError(RpcError::RpcResponseError {
-                                    code: -32007,
-                                    ..
-                                })
-                                | ClientErrorKind::RpcError(RpcError::RpcResponseError {
-                                    code: -32009,
-                                    ..
-                                }) => {
-                                    // Skip slot as it will never be availble to us
-                                    break;
-                                }
-                                _ => {
-                                    // Wait for the block to arrive
-                                    sleep(tokio::time::Duration::from_millis(
-                                        SLOT_TIME / RETRIES_PER_SLOT,
-                                    ))
+                            SourceUpdate::Account(account) => {
+                                println!("Found account state: {:?}", account.id);
+
+                                let _ = context
+                                    .db_connection
+                                    .lock()
                                     .await
+                                    .query(&DbQuery::InsertAccount(account.clone()))
+                                    .await;
+
+                                if let Some(event) = IndexEvent::from_account(&account) {
+                                    context.events.publish(vec![event]).await;
                                 }
-                            },
+                            }
+                            SourceUpdate::Tip {
+                                slot,
+                                finalized_slot,
+                            } => context.cluster.update(slot, finalized_slot).await,
                         }
                     }
-                }
+                };
+
+                join!(producer, consumer);
             }
         }));
 
@@ -144,16 +142,29 @@ impl Indexer {
 }
 
 async fn process_block(
-    db_connection: Arc<Mutex<DbConnection>>,
+    context: &IndexerContext,
+    recent: &mut VecDeque<RecentBlock>,
+    slot: u64,
     confirmed_block: &UiConfirmedBlock,
 ) -> Result<(), IndexerError> {
-    let block = Block::from(confirmed_block.clone());
+    let IndexerContext {
+        db_connection,
+        decoders,
+        vote_policy,
+        filter: filter_state,
+        events,
+        ..
+    } = context;
+
+    let block = Block::new(slot, confirmed_block.clone());
     println!("Found block: {:?}", block.id);
 
+    let rollbacks = roll_back(db_connection, recent, &block).await;
+
     let _ = db_connection
         .lock()
         .await
-        .query(&DbQuery::InsertBlock(block))
+        .query(&DbQuery::InsertBlock(block.clone()))
         .await;
 
     let txs = match &confirmed_block.transactions {
@@ -161,18 +172,62 @@ async fn process_block(
         None => Vec::new(),
     };
 
+    let filter = filter_state.filter().await;
+    let mut votes_seen = 0;
+    let (mut matched, mut dropped) = (0, 0);
+    let mut stored = Vec::new();
+
     for transaction in txs {
-        let transaction = Transaction::from(transaction.clone());
+        let mut transaction = match Transaction::try_from(transaction) {
+            Ok(transaction) => Transaction {
+                slot: block.slot,
+                ..transaction
+            },
+            Err(error) => {
+                println!("Skipping transaction in block {:?}: {error}", block.id);
+                continue;
+            }
+        };
+
+        if !filter.matches(&transaction) {
+            dropped += 1;
+            continue;
+        }
+        matched += 1;
+
+        if transaction.is_vote() {
+            votes_seen += 1;
+
+            match vote_policy {
+                VotePolicy::Store => {}
+                VotePolicy::Skip => continue,
+                VotePolicy::Sample(every) => {
+                    if (votes_seen - 1) % every != 0 {
+                        continue;
+                    }
+                }
+                VotePolicy::Compact => {
+                    let _ = db_connection
+                        .lock()
+                        .await
+                        .query(&DbQuery::InsertVote(VoteTransaction::from(&transaction)))
+                        .await;
+                    continue;
+                }
+            }
+        }
+
+        decoders.decode_transaction(&mut transaction);
         println!("Found transaction: {:?}", transaction.id);
 
         let _ = db_connection
             .lock()
             .await
-            .query(&DbQuery::InsertTransaction(transaction.clone()))
+            .query(&DbQuery::InsertTransaction(Box::new(transaction.clone())))
             .await;
 
         for (account, balance) in transaction.accounts.iter().zip(transaction.balances.iter()) {
-            let account = Account::new(account.to_string(), *balance);
+            let account = Account::new(account.to_string(), *balance, transaction.slot);
             println!("Found account: {:?}", account.id);
 
             let _ = db_connection
@@ -181,7 +236,373 @@ async fn process_block(
                 .query(&DbQuery::InsertAccount(account))
                 .await;
         }
+
+        stored.push(transaction);
+    }
+
+    filter_state.record(matched, dropped);
+
+    recent.push_back(RecentBlock {
+        id: block.id.clone(),
+        slot: block.slot,
+        signatures: stored
+            .iter()
+            .map(|transaction| transaction.id.clone())
+            .collect(),
+    });
+    if recent.len() > RECENT_BLOCKS {
+        recent.pop_front();
     }
 
+    let mut announced = rollbacks;
+    announced.extend(IndexEvent::from_block(&block, &stored));
+    events.publish(announced).await;
+
     Ok(())
 }
+
+//
+// A block building on something other than the last block we saw orphans
+// every block after its parent. Whatever the orphans stored is taken out
+// before the rollback is announced, so nobody acting on it reads them back.
+//
+
+async fn roll_back(
+    db_connection: &Mutex<DbConnection>,
+    recent: &mut VecDeque<RecentBlock>,
+    block: &Block,
+) -> Vec<IndexEvent> {
+    let Some(parent) = recent
+        .iter()
+        .rposition(|recent| recent.id == block.previous_blockhash)
+    else {
+        return Vec::new();
+    };
+
+    let mut rollbacks = Vec::new();
+    for orphan in recent.drain(parent + 1..) {
+        println!("Rolling back block: {:?}", orphan.id);
+
+        let _ = db_connection
+            .lock()
+            .await
+            .query(&DbQuery::RemoveBlock(orphan.id.clone()))
+            .await;
+
+        rollbacks.push(IndexEvent::Rollback {
+            slot: orphan.slot,
+            blockhash: orphan.id,
+            signatures: orphan.signatures,
+        });
+    }
+
+    rollbacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        common::{block::BlockStats, transaction::VOTE_PROGRAM_ID},
+        database::errors::DbError,
+        indexer::filter::IndexFilter,
+        source::synthetic::{ChainGenerator, SyntheticConfig},
+    };
+    use serde_json::json;
+    use solana_transaction_status::{
+        EncodedTransaction, EncodedTransactionWithStatusMeta, UiLoadedAddresses, UiMessage,
+    };
+
+    fn context() -> IndexerContext {
+        context_with(VotePolicy::Store)
+    }
+
+    fn context_with(vote_policy: VotePolicy) -> IndexerContext {
+        IndexerContext {
+            db_connection: Arc::new(Mutex::new(DbConnection::new())),
+            decoders: Arc::new(DecoderRegistry::new()),
+            vote_policy,
+            filter: Arc::new(FilterState::new(IndexFilter::default())),
+            cluster: Arc::new(ClusterState::new()),
+            events: Arc::new(EventBus::new()),
+        }
+    }
+
+    fn generator(options: &str) -> ChainGenerator {
+        ChainGenerator::new(options.parse::<SyntheticConfig>().unwrap())
+    }
+
+    #[tokio::test]
+    async fn stores_what_the_block_holds() {
+        let context = context();
+        let mut recent = VecDeque::new();
+
+        let chain = generator("seed=11,tps=50,fork_rate=0,error_rate=0.2,v0_rate=0.5,blocks=20");
+        let (mut failed, mut loaded) = (0, 0);
+
+        for (slot, block) in chain {
+            process_block(&context, &mut recent, slot, &block)
+                .await
+                .unwrap();
+
+            let mut db_connection = context.db_connection.lock().await;
+            let stats: BlockStats = db_connection
+                .query(&DbQuery::GetBlockStats(block.blockhash.clone()))
+                .await
+                .unwrap()
+                .into();
+            let stored: Vec<Transaction> = db_connection
+                .query(&DbQuery::GetSlotTransactions(slot))
+                .await
+                .unwrap()
+                .into();
+
+            let transactions = block.transactions.unwrap();
+            assert_eq!(stats.slot, slot);
+            assert_eq!(stats.transaction_count, transactions.len());
+            assert_eq!(stored.len(), transactions.len());
+
+            for transaction in transactions {
+                let EncodedTransaction::Json(encoded) = &transaction.transaction else {
+                    panic!("Expected a json transaction");
+                };
+                let UiMessage::Raw(message) = &encoded.message else {
+                    panic!("Expected a raw message");
+                };
+                let meta = transaction.meta.as_ref().unwrap();
+                let stored = stored
+                    .iter()
+                    .find(|stored| stored.id == encoded.signatures[0])
+                    .unwrap();
+
+                assert_eq!(stored.slot, slot);
+                assert_eq!(stored.err.is_some(), meta.err.is_some());
+                failed += meta.err.is_some() as usize;
+
+                // The recipient of a v0 transfer comes from the lookup table
+                let loaded_addresses: Option<_> = meta.loaded_addresses.clone().into();
+                if let Some(UiLoadedAddresses { writable, .. }) = loaded_addresses {
+                    assert_eq!(stored.accounts.len(), message.account_keys.len() + 1);
+                    assert_eq!(stored.instructions[0].accounts[1], writable[0]);
+                    loaded += 1;
+                }
+            }
+        }
+
+        assert!(failed > 0);
+        assert!(loaded > 0);
+    }
+
+    #[tokio::test]
+    async fn forks_are_rolled_back() {
+        let chain: Vec<_> = generator("seed=5,tps=20,fork_rate=0.3,blocks=60").collect();
+
+        // Walking back from the last block only ever passes canonical ones
+        let mut canonical = Vec::new();
+        let mut next = chain.last().map(|(_, block)| block.blockhash.clone());
+        for (slot, block) in chain.iter().rev() {
+            if next.as_ref() == Some(&block.blockhash) {
+                next = Some(block.previous_blockhash.clone());
+                canonical.insert(0, (*slot, block.clone()));
+            }
+        }
+        let forks: Vec<_> = chain
+            .iter()
+            .filter(|(_, block)| !canonical.iter().any(|(_, kept)| kept == block))
+            .collect();
+        assert!(!forks.is_empty());
+
+        let forked = context();
+        let mut recent = VecDeque::new();
+        for (slot, block) in &chain {
+            process_block(&forked, &mut recent, *slot, block)
+                .await
+                .unwrap();
+        }
+
+        let straight = context();
+        let mut recent = VecDeque::new();
+        for (slot, block) in &canonical {
+            process_block(&straight, &mut recent, *slot, block)
+                .await
+                .unwrap();
+        }
+
+        let mut db_connection = forked.db_connection.lock().await;
+        for (slot, fork) in &forks {
+            let block = db_connection
+                .query(&DbQuery::GetBlock(fork.blockhash.clone()))
+                .await;
+            assert_eq!(block, Err(DbError::BlockNotFound));
+
+            let transactions: Vec<Transaction> = db_connection
+                .query(&DbQuery::GetSlotTransactions(*slot))
+                .await
+                .unwrap()
+                .into();
+            assert!(transactions.is_empty());
+        }
+
+        // Nothing the forks stored is left to tell the two apart
+        let accounts: Vec<String> = chain
+            .iter()
+            .flat_map(|(_, block)| block.transactions.clone().unwrap_or_default())
+            .flat_map(|transaction| Transaction::try_from(transaction).unwrap().accounts)
+            .collect();
+        let mut straight_connection = straight.db_connection.lock().await;
+        for query in [
+            DbQuery::GetSlots(0, u64::MAX),
+            DbQuery::GetAccounts(accounts),
+        ] {
+            assert_eq!(
+                db_connection.query(&query).await,
+                straight_connection.query(&query).await
+            );
+        }
+
+        let (events, _) = forked.events.subscribe(Some(0)).await;
+        let rolled_back: Vec<_> = events
+            .iter()
+            .flat_map(|events| events.iter())
+            .filter_map(|event| match event {
+                IndexEvent::Rollback { blockhash, .. } => Some(blockhash),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rolled_back.len(), forks.len());
+    }
+
+    #[tokio::test]
+    async fn skips_transactions_that_cant_be_indexed() {
+        let context = context();
+        let block: UiConfirmedBlock = serde_json::from_str(include_str!(
+            "../../tests/fixtures/blocks/mixed_encodings.json"
+        ))
+        .unwrap();
+        let slot = block.parent_slot + 1;
+
+        // Binary, legacy binary, parsed and meta-less ones are left out
+        process_block(&context, &mut VecDeque::new(), slot, &block)
+            .await
+            .unwrap();
+
+        let mut db_connection = context.db_connection.lock().await;
+        let stats: BlockStats = db_connection
+            .query(&DbQuery::GetBlockStats(block.blockhash.clone()))
+            .await
+            .unwrap()
+            .into();
+        assert_eq!(stats.transaction_count, 5);
+        assert_eq!(stats.indexed_transaction_count, 1);
+    }
+
+    fn key(seed: u8) -> String {
+        bs58::encode([seed; 32]).into_string()
+    }
+
+    // Paid for by one key and signed by another, as validators usually do.
+    // `accounts` index into [payer, authority, vote account, slot hashes,
+    // clock, vote program].
+    fn vote(seed: u8, tag: u32, accounts: &[u8]) -> EncodedTransactionWithStatusMeta {
+        let mut data = tag.to_le_bytes().to_vec();
+        data.extend([0; 16]);
+
+        serde_json::from_value(json!({
+            "transaction": {
+                "signatures": [bs58::encode([seed; 64]).into_string()],
+                "message": {
+                    "accountKeys": [key(1), key(2), key(3), key(4), key(5), VOTE_PROGRAM_ID],
+                    "header": {
+                        "numRequiredSignatures": 2,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 3,
+                    },
+                    "recentBlockhash": key(6),
+                    "instructions": [{
+                        "programIdIndex": 5,
+                        "accounts": accounts,
+                        "data": bs58::encode(data).into_string(),
+                        "stackHeight": null,
+                    }],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [10_000, 1, 1, 1, 1, 1],
+                "postBalances": [5000, 1, 1, 1, 1, 1],
+                "innerInstructions": [],
+                "logMessages": [],
+                "preTokenBalances": [],
+                "postTokenBalances": [],
+                "rewards": [],
+            },
+            "version": "legacy",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn votes_follow_the_policy() {
+        let (slot, mut block) = generator("seed=2,tps=20,fork_rate=0,blocks=1")
+            .next()
+            .unwrap();
+        let others = block.transactions.as_ref().unwrap().len();
+
+        // Alternately Vote, with the authority 4th, and TowerSync, with it 2nd
+        let votes: Vec<_> = (0..5)
+            .map(|seed| match seed % 2 {
+                0 => vote(seed, 2, &[2, 3, 4, 1]),
+                _ => vote(seed, 14, &[2, 1]),
+            })
+            .collect();
+        let ids: Vec<String> = votes
+            .iter()
+            .map(|vote| Transaction::try_from(vote.clone()).unwrap().id)
+            .collect();
+        block.transactions.as_mut().unwrap().extend(votes);
+
+        for (vote_policy, stored_votes) in [
+            (VotePolicy::Store, 5),
+            (VotePolicy::Skip, 0),
+            (VotePolicy::Sample(2), 3),
+            (VotePolicy::Compact, 0),
+        ] {
+            let context = context_with(vote_policy);
+            process_block(&context, &mut VecDeque::new(), slot, &block)
+                .await
+                .unwrap();
+
+            let mut db_connection = context.db_connection.lock().await;
+            let stats: BlockStats = db_connection
+                .query(&DbQuery::GetBlockStats(block.blockhash.clone()))
+                .await
+                .unwrap()
+                .into();
+            let stored: Vec<Transaction> = db_connection
+                .query(&DbQuery::GetSlotTransactions(slot))
+                .await
+                .unwrap()
+                .into();
+
+            // Counted whatever happens to them
+            assert_eq!(stats.vote_transaction_count, 5, "{vote_policy:?}");
+            assert_eq!(stored.len(), others + stored_votes, "{vote_policy:?}");
+
+            for id in &ids {
+                let vote = db_connection.query(&DbQuery::GetVote(id.clone())).await;
+                match vote_policy {
+                    VotePolicy::Compact => {
+                        let vote: VoteTransaction = vote.unwrap().into();
+                        assert_eq!((vote.slot, vote.err), (slot, None));
+                        assert_eq!(vote.vote_account, key(3));
+                        assert_eq!(vote.authority, key(2));
+                    }
+                    _ => assert_eq!(vote, Err(DbError::VoteNotFound)),
+                }
+            }
+        }
+    }
+}
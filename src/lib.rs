@@ -0,0 +1,11 @@
+pub mod api_server;
+pub mod common;
+pub mod config;
+pub mod database;
+pub mod decoder;
+pub mod events;
+pub mod harness;
+pub mod indexer;
+pub mod sinks;
+pub mod source;
+pub mod webhooks;
@@ -1,10 +1,13 @@
-mod api_server;
-mod common;
-mod database;
-mod indexer;
-
-use crate::{
-    api_server::server::ApiServer, database::server::DbConnection, indexer::server::Indexer,
+use solforge::{
+    api_server::server::ApiServer, config::settings::Settings, database::server::DbConnection,
+    decoder::registry::DecoderRegistry, events::bus::EventBus, harness::server::Harness,
+    indexer::accounts::AccountIndexer, indexer::cluster::ClusterState,
+    indexer::filter::FilterState, indexer::server::Indexer, indexer::server::IndexerContext,
+    sinks::common::Sink, sinks::common::SinkKind, sinks::exporter::SinkExporter,
+    sinks::file::FileSink, sinks::nats::NatsSink, source::common::Source,
+    source::common::SourceKind, source::file::FileSource, source::geyser::GeyserSource,
+    source::rpc::RpcSource, source::synthetic::SyntheticSource,
+    webhooks::dispatcher::WebhookDispatcher, webhooks::registry::WebhookRegistry,
 };
 
 use std::sync::Arc;
@@ -12,22 +15,125 @@ use tokio::{join, signal::ctrl_c, sync::broadcast, sync::Mutex};
 
 #[tokio::main]
 async fn main() {
+    let settings = Settings::from_env();
     let db_connection = Arc::new(Mutex::new(DbConnection::new()));
 
     let (tx, _) = broadcast::channel(1);
     let shutdown = shutdown(tx.clone());
 
-    let mut api_server = ApiServer::new(db_connection.clone(), tx.clone());
+    let filter = Arc::new(FilterState::new(settings.filter.clone()));
+    let cluster = Arc::new(ClusterState::new());
+    let events = Arc::new(EventBus::new());
+    let webhooks = Arc::new(WebhookRegistry::new(settings.webhook_allow_private));
+
+    let mut api_server = ApiServer::new(
+        db_connection.clone(),
+        filter.clone(),
+        cluster.clone(),
+        events.clone(),
+        webhooks.clone(),
+        settings.admin_token.clone(),
+        tx.clone(),
+    );
     api_server.start().await;
 
-    let mut indexer = Indexer::new(db_connection.clone(), tx.clone());
+    let sinks = settings
+        .sinks
+        .iter()
+        .map(|sink| -> Arc<dyn Sink> {
+            match sink {
+                SinkKind::File(path) => Arc::new(FileSink::new(path.clone())),
+                SinkKind::Nats { address, subject } => {
+                    Arc::new(NatsSink::new(address.clone(), subject.clone()))
+                }
+            }
+        })
+        .collect();
+    let mut sink_exporter = SinkExporter::new(sinks, events.clone(), tx.clone());
+    sink_exporter.start().await;
+
+    let mut webhook_dispatcher = WebhookDispatcher::new(webhooks, events.clone(), tx.clone());
+    webhook_dispatcher.start().await;
+
+    let mut harness = Harness::new(
+        settings.harness.clone(),
+        settings.rpc_url.clone(),
+        settings.websocket_url.clone(),
+        tx.clone(),
+    );
+    harness.start().await;
+
+    // With the harness in the way, everything goes through it
+    let (rpc_url, websocket_url) = match harness.urls() {
+        Some(urls) => urls,
+        None => (settings.rpc_url.clone(), settings.websocket_url.clone()),
+    };
+
+    let mut decoders = DecoderRegistry::new();
+    if let Some(idl_dir) = &settings.idl_dir {
+        // Instructions from programs without an IDL are still indexed, just
+        // left undecoded, so a bad IDL directory isn't worth refusing to start
+        if let Err(error) = decoders.load_idls(idl_dir) {
+            println!("Error loading IDLs from {}: {}", idl_dir.display(), error);
+        }
+    }
+    let decoders = Arc::new(decoders);
+
+    let source: Arc<dyn Source> = match &settings.source {
+        SourceKind::Rpc => Arc::new(RpcSource::new(rpc_url.clone(), websocket_url.clone())),
+        SourceKind::Geyser(url) => Arc::new(GeyserSource::new(
+            url.clone(),
+            settings.watch_accounts.clone(),
+            settings.watch_programs.clone(),
+        )),
+        SourceKind::File(path) => Arc::new(FileSource::new(path.clone())),
+        SourceKind::Synthetic(config) => Arc::new(SyntheticSource::new(config.clone())),
+    };
+
+    let context = IndexerContext {
+        db_connection: db_connection.clone(),
+        decoders,
+        vote_policy: settings.vote_policy,
+        filter,
+        cluster,
+        events: events.clone(),
+    };
+    let mut indexer = Indexer::new(source, context, tx.clone());
     indexer.start().await;
 
+    // A Geyser feed carries watched accounts itself, and replays have no
+    // cluster to subscribe to, so only blocks over RPC need the pubsub side
+    let (watch_programs, watch_accounts) = match &settings.source {
+        SourceKind::Rpc => (
+            settings.watch_programs.clone(),
+            settings.watch_accounts.clone(),
+        ),
+        _ => (Vec::new(), Vec::new()),
+    };
+    let mut account_indexer = AccountIndexer::new(
+        db_connection.clone(),
+        rpc_url,
+        websocket_url,
+        watch_programs,
+        watch_accounts,
+        events,
+        tx.clone(),
+    );
+    account_indexer.start().await;
+
     // TODO: separate into different processes rather than separate tasks within
     // the same process. That way the indexer can run by itself without needing
     // to serve the API, or they can even run on multiple machines for scaleout etc.
 
-    join!(api_server.wait(), indexer.wait(), shutdown);
+    join!(
+        api_server.wait(),
+        indexer.wait(),
+        account_indexer.wait(),
+        harness.wait(),
+        webhook_dispatcher.wait(),
+        sink_exporter.wait(),
+        shutdown
+    );
 }
 
 async fn shutdown(tx: broadcast::Sender<()>) {
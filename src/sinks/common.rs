@@ -0,0 +1,50 @@
+use crate::{events::bus::Events, sinks::errors::SinkError};
+
+use async_trait::async_trait;
+use std::{path::PathBuf, str::FromStr};
+
+const DEFAULT_NATS_SUBJECT: &str = "solforge";
+
+//
+// Where indexed events get exported to for downstream consumers. Every event
+// goes out with its key, which stays the same if the event is sent again, so
+// consumers (or the broker) can drop repeats.
+//
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    // Only returns once the events have been handed off for good, in the
+    // batches they came off the bus in
+    async fn publish(&self, batches: &[Events]) -> Result<(), SinkError>;
+
+    fn name(&self) -> String;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SinkKind {
+    // Append events to a file, one JSON object per line
+    File(PathBuf),
+    // Publish each event to <subject>.<type> on a NATS server
+    Nats { address: String, subject: String },
+}
+
+impl FromStr for SinkKind {
+    type Err = String;
+
+    fn from_str(sink: &str) -> Result<Self, Self::Err> {
+        match sink.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(SinkKind::File(PathBuf::from(path))),
+            Some(("nats", target)) if !target.is_empty() => {
+                let (address, subject) = target
+                    .split_once('/')
+                    .unwrap_or((target, DEFAULT_NATS_SUBJECT));
+
+                Ok(SinkKind::Nats {
+                    address: address.to_string(),
+                    subject: subject.to_string(),
+                })
+            }
+            _ => Err(format!("Unknown sink: {sink}")),
+        }
+    }
+}
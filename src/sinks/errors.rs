@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SinkError {
+    #[error("Sink I/O error")]
+    IoError,
+    #[error("Error serializing event")]
+    InvalidEvent,
+    #[error("Unexpected reply from the broker")]
+    UnexpectedReply,
+    #[error("Broker error: {0}")]
+    Rejected(String),
+    #[error("Timed out waiting for the broker to ack")]
+    Timeout,
+    #[error("Events from slot {0} on were dropped before they could be exported")]
+    EventsDropped(u64),
+}
+
+impl From<std::io::Error> for SinkError {
+    fn from(_error: std::io::Error) -> Self {
+        SinkError::IoError
+    }
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(_error: serde_json::Error) -> Self {
+        SinkError::InvalidEvent
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    events::{
+        bus::{EventBus, Events},
+        event::IndexEvent,
+    },
+    sinks::{common::Sink, errors::SinkError},
+};
+
+use futures_util::future::join_all;
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    select, spawn,
+    sync::broadcast::{
+        error::{RecvError, TryRecvError},
+        Receiver, Sender,
+    },
+    task::JoinHandle,
+    time::sleep,
+};
+
+// Events sent to a sink in one go, unless a single block has more
+const MAX_BATCH: usize = 512;
+// Doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+//
+// Feeds every event off the bus to each sink. Each sink is fed on its own, so
+// one that's down doesn't hold up the others. A batch is retried until the
+// sink takes it, and a sink that falls behind the bus in the meantime picks up
+// again from the last slot it saw. Some events can be sent twice, but never
+// none: if the bus has already let go of what a sink missed, that sink is
+// stopped rather than carrying on with a gap. The rest of the server, and the
+// other sinks, keep going.
+//
+
+pub struct SinkExporter {
+    task: Option<JoinHandle<()>>,
+    sinks: Vec<Arc<dyn Sink>>,
+    events: Arc<EventBus>,
+    tx: Sender<()>,
+}
+
+impl SinkExporter {
+    pub fn new(sinks: Vec<Arc<dyn Sink>>, events: Arc<EventBus>, tx: Sender<()>) -> Self {
+        SinkExporter {
+            task: None,
+            sinks,
+            events,
+            tx,
+        }
+    }
+
+    pub async fn start(&mut self) {
+        if self.task.is_some() {
+            println!("Sink exporter already running");
+            return;
+        }
+
+        if self.sinks.is_empty() {
+            return;
+        }
+
+        println!("Starting sink exporter...");
+
+        let mut exports = Vec::new();
+        for sink in &self.sinks {
+            // Subscribed before returning, so nothing published after start is
+            // missed
+            let (_, receiver) = self.events.subscribe(None).await;
+            exports.push(export(sink.clone(), self.events.clone(), receiver));
+        }
+
+        self.task = Some(spawn(async move {
+            join_all(exports).await;
+        }));
+
+        println!("Sink exporter started");
+    }
+
+    pub async fn stop(&mut self) {
+        if let Some(task) = &self.task.take() {
+            println!("Stopping sink exporter...");
+            task.abort();
+            println!("Sink exporter stopped!")
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Sink exporter running");
+            let mut rx = self.tx.subscribe();
+
+            select! {
+                _ = task => println!("Sink exporter stopped on its own"),
+                _ = rx.recv() => println!("Sink exporter interrupted"),
+            }
+        }
+
+        self.stop().await;
+    }
+}
+
+async fn export(sink: Arc<dyn Sink>, events: Arc<EventBus>, receiver: Receiver<Events>) {
+    if let Err(error) = export_all(sink.as_ref(), &events, receiver).await {
+        println!("Sink {} stopped: {error}", sink.name());
+    }
+}
+
+async fn export_all(
+    sink: &dyn Sink,
+    events: &EventBus,
+    mut receiver: Receiver<Events>,
+) -> Result<(), SinkError> {
+    let mut last_slot = None;
+
+    loop {
+        let mut received = vec![receiver.recv().await];
+        let mut count = received[0].as_ref().map_or(0, |events| events.len());
+
+        // Whatever else is already waiting goes along with it
+        while count < MAX_BATCH && received.last().is_some_and(Result::is_ok) {
+            match receiver.try_recv() {
+                Ok(events) => {
+                    count += events.len();
+                    received.push(Ok(events));
+                }
+                Err(TryRecvError::Lagged(skipped)) => {
+                    received.push(Err(RecvError::Lagged(skipped)));
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let mut batch = Vec::new();
+        let mut closed = false;
+
+        for received in received {
+            match received {
+                Ok(received) => {
+                    last_slot = received.last().map(IndexEvent::slot).or(last_slot);
+                    batch.push(received);
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    println!(
+                        "Sink {} skipped {skipped} batches, catching up",
+                        sink.name()
+                    );
+
+                    let from_slot = last_slot.unwrap_or_default();
+                    let Some((backlog, resubscribed)) = events.resume(from_slot).await else {
+                        println!(
+                            "Sink {} can't catch up, events from slot {from_slot} are gone",
+                            sink.name()
+                        );
+                        return Err(SinkError::EventsDropped(from_slot));
+                    };
+                    receiver = resubscribed;
+                    batch.extend(backlog);
+                }
+                Err(RecvError::Closed) => closed = true,
+            }
+        }
+
+        let mut backoff = INITIAL_BACKOFF;
+        while !batch.is_empty() {
+            match sink.publish(&batch).await {
+                Ok(()) => break,
+                Err(error) => {
+                    println!("Sink {} error, retrying: {error}", sink.name());
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+
+        if closed {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_trait::async_trait;
+    use std::collections::HashSet;
+    use tokio::{
+        sync::{broadcast, Mutex, Semaphore},
+        time::timeout,
+    };
+
+    // Holds on to the first batch until let go of, so the bus runs ahead
+    struct HeldSink {
+        held: Semaphore,
+        keys: Mutex<HashSet<String>>,
+    }
+
+    #[async_trait]
+    impl Sink for HeldSink {
+        async fn publish(&self, batches: &[Events]) -> Result<(), SinkError> {
+            let _permit = self.held.acquire().await;
+            self.keys.lock().await.extend(
+                batches
+                    .iter()
+                    .flat_map(|events| events.iter().map(IndexEvent::key)),
+            );
+            Ok(())
+        }
+
+        fn name(&self) -> String {
+            "held".to_string()
+        }
+    }
+
+    // Far more events than a subscriber can fall behind by, over `slots` slots
+    async fn run(slots: u64) -> (Arc<HeldSink>, SinkExporter, broadcast::Receiver<()>) {
+        let sink = Arc::new(HeldSink {
+            held: Semaphore::new(0),
+            keys: Mutex::new(HashSet::new()),
+        });
+        let events = Arc::new(EventBus::new());
+        let (tx, rx) = broadcast::channel(1);
+
+        let mut exporter = SinkExporter::new(vec![sink.clone()], events.clone(), tx);
+        exporter.start().await;
+
+        for id in 0..10_000 {
+            events
+                .publish(vec![IndexEvent::Rollback {
+                    slot: 1 + id * slots / 10_000,
+                    blockhash: id.to_string(),
+                    signatures: Vec::new(),
+                }])
+                .await;
+        }
+        sink.held.add_permits(1);
+
+        (sink, exporter, rx)
+    }
+
+    #[tokio::test]
+    async fn catches_up_from_what_the_bus_holds() {
+        let (sink, _exporter, mut rx) = run(10).await;
+
+        let caught_up = async {
+            while sink.keys.lock().await.len() < 10_000 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), caught_up).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn stops_the_sink_when_events_were_dropped() {
+        let (sink, mut exporter, mut rx) = run(10_000).await;
+
+        // The only sink stopping finishes the exporter, without shutting
+        // anything else down
+        let task = exporter.task.take().unwrap();
+        timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(sink.keys.lock().await.len() < 10_000);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    events::bus::Events,
+    sinks::{common::Sink, errors::SinkError},
+};
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::path::PathBuf;
+
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+//
+// Appends events to a file as NDJSON, for trying out consumers locally without
+// a broker. Each line holds the key, the topic it would have gone to and the
+// event itself.
+//
+
+pub struct FileSink {
+    path: PathBuf,
+    // Opened on first use, and again after an error
+    file: Mutex<Option<File>>,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        FileSink {
+            path,
+            file: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for FileSink {
+    async fn publish(&self, batches: &[Events]) -> Result<(), SinkError> {
+        let mut lines = Vec::new();
+        for event in batches.iter().flat_map(|events| events.iter()) {
+            let record = json!({
+                "key": event.key(),
+                "topic": event.kind(),
+                "event": event,
+            });
+            serde_json::to_writer(&mut lines, &record)?;
+            lines.push(b'\n');
+        }
+
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            *file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await?,
+            );
+        }
+
+        let Some(open) = file.as_mut() else {
+            return Err(SinkError::IoError);
+        };
+
+        // Synced before returning, so nothing acknowledged is lost on a crash
+        let written = match open.write_all(&lines).await {
+            Ok(()) => open.sync_data().await,
+            Err(error) => Err(error),
+        };
+        if written.is_err() {
+            *file = None;
+        }
+
+        Ok(written?)
+    }
+
+    fn name(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+}
@@ -0,0 +1,5 @@
+pub mod common;
+pub mod errors;
+pub mod exporter;
+pub mod file;
+pub mod nats;
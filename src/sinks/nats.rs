@@ -0,0 +1,364 @@
+use crate::{
+    events::bus::Events,
+    sinks::{common::Sink, errors::SinkError},
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::Mutex,
+    time::timeout,
+};
+
+//
+// Publishes events to a NATS JetStream stream, speaking the text protocol
+// directly over TCP. Each event goes to <subject>.<type> with its key in the
+// Nats-Msg-Id header, which JetStream uses to drop duplicates.
+//
+// Every message is published with a reply subject in our own inbox, where
+// JetStream answers once it has stored the message. A batch only counts as
+// delivered once each of its messages has been acked, so subjects that no
+// stream captures, and messages JetStream turns down, are errors.
+//
+
+// How long JetStream gets to ack a batch
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static NEXT_INBOX: AtomicU64 = AtomicU64::new(0);
+
+pub struct NatsSink {
+    address: String,
+    subject: String,
+    // Connected on first use, and again after an error
+    connection: Mutex<Option<Connection>>,
+}
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    // Acks come back to <inbox>.<n>, one n per message
+    inbox: String,
+    next_reply: u64,
+}
+
+// What JetStream replies to a published message with
+#[derive(Deserialize)]
+struct PubAck {
+    stream: Option<String>,
+    error: Option<PubAckError>,
+}
+
+#[derive(Deserialize)]
+struct PubAckError {
+    description: String,
+}
+
+impl Connection {
+    async fn connect(address: &str) -> Result<Self, SinkError> {
+        let (reader, writer) = TcpStream::connect(address).await?.into_split();
+        let mut connection = Connection {
+            reader: BufReader::new(reader),
+            writer,
+            inbox: format!(
+                "_INBOX.solforge.{}.{}",
+                std::process::id(),
+                NEXT_INBOX.fetch_add(1, Ordering::Relaxed)
+            ),
+            next_reply: 0,
+        };
+
+        // The server introduces itself first
+        if !connection.read_line().await?.starts_with("INFO ") {
+            return Err(SinkError::UnexpectedReply);
+        }
+
+        // With no_responders, publishing to a subject no stream captures is
+        // answered straight away rather than never
+        let options = json!({
+            "verbose": false,
+            "pedantic": false,
+            "headers": true,
+            "no_responders": true,
+            "name": "solforge",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        let subscribe = format!("CONNECT {options}\r\nSUB {}.* 1\r\n", connection.inbox);
+        connection.writer.write_all(subscribe.as_bytes()).await?;
+        connection.writer.flush().await?;
+
+        Ok(connection)
+    }
+
+    // Publishes each message with its own reply subject, and waits for all of
+    // them to be acked
+    async fn publish(&mut self, messages: &[(String, String, Vec<u8>)]) -> Result<(), SinkError> {
+        let mut bytes = Vec::new();
+        let mut pending = HashMap::new();
+
+        for (subject, headers, payload) in messages {
+            let reply = format!("{}.{}", self.inbox, self.next_reply);
+            self.next_reply += 1;
+
+            bytes.extend(
+                format!(
+                    "HPUB {subject} {reply} {} {}\r\n{headers}",
+                    headers.len(),
+                    headers.len() + payload.len(),
+                )
+                .as_bytes(),
+            );
+            bytes.extend(payload);
+            bytes.extend(b"\r\n");
+            pending.insert(reply, subject.as_str());
+        }
+
+        self.writer.write_all(&bytes).await?;
+        self.writer.flush().await?;
+
+        timeout(ACK_TIMEOUT, self.acks(pending))
+            .await
+            .map_err(|_| SinkError::Timeout)?
+    }
+
+    async fn acks(&mut self, mut pending: HashMap<String, &str>) -> Result<(), SinkError> {
+        while !pending.is_empty() {
+            let line = self.read_line().await?;
+            let arguments: Vec<&str> = line.split(' ').collect();
+            let reply = arguments.get(1).copied().unwrap_or_default();
+
+            match arguments[0] {
+                // MSG <reply> <sid> <bytes>
+                "MSG" => {
+                    let payload = self.read_payload(number(arguments.last())?).await?;
+
+                    if pending.remove(reply).is_some() {
+                        check_ack(&payload)?;
+                    }
+                }
+                // HMSG <reply> <sid> <header bytes> <total bytes>, where the
+                // headers carry a 503 status when no stream captures the subject
+                "HMSG" => {
+                    let headers = number(arguments.iter().rev().nth(1))?;
+                    let payload = self.read_payload(number(arguments.last())?).await?;
+                    let (headers, body) = payload.split_at(headers.min(payload.len()));
+
+                    if let Some(subject) = pending.remove(reply) {
+                        if headers.starts_with(b"NATS/1.0 503") {
+                            return Err(SinkError::Rejected(format!(
+                                "No stream captures {subject}"
+                            )));
+                        }
+                        check_ack(body)?;
+                    }
+                }
+                "PING" => self.writer.write_all(b"PONG\r\n").await?,
+                "PONG" | "+OK" | "INFO" => (),
+                "-ERR" => return Err(SinkError::Rejected(line[4..].trim().to_string())),
+                _ => return Err(SinkError::UnexpectedReply),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn read_line(&mut self) -> Result<String, SinkError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(SinkError::IoError);
+        }
+
+        Ok(line.trim_end().to_string())
+    }
+
+    // Payloads are followed by a CRLF that isn't counted in their length
+    async fn read_payload(&mut self, length: usize) -> Result<Vec<u8>, SinkError> {
+        let mut payload = vec![0; length + 2];
+        self.reader.read_exact(&mut payload).await?;
+        payload.truncate(length);
+
+        Ok(payload)
+    }
+}
+
+fn check_ack(payload: &[u8]) -> Result<(), SinkError> {
+    match serde_json::from_slice::<PubAck>(payload) {
+        Ok(PubAck {
+            error: Some(error), ..
+        }) => Err(SinkError::Rejected(error.description)),
+        Ok(PubAck {
+            stream: Some(_), ..
+        }) => Ok(()),
+        _ => Err(SinkError::UnexpectedReply),
+    }
+}
+
+fn number(argument: Option<&&str>) -> Result<usize, SinkError> {
+    argument
+        .and_then(|argument| argument.parse().ok())
+        .ok_or(SinkError::UnexpectedReply)
+}
+
+impl NatsSink {
+    pub fn new(address: String, subject: String) -> Self {
+        NatsSink {
+            address,
+            subject,
+            connection: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for NatsSink {
+    async fn publish(&self, batches: &[Events]) -> Result<(), SinkError> {
+        let mut messages = Vec::new();
+        for event in batches.iter().flat_map(|events| events.iter()) {
+            messages.push((
+                format!("{}.{}", self.subject, event.kind()),
+                format!("NATS/1.0\r\nNats-Msg-Id: {}\r\n\r\n", event.key()),
+                serde_json::to_vec(event)?,
+            ));
+        }
+
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            *connection = Some(Connection::connect(&self.address).await?);
+        }
+
+        let Some(open) = connection.as_mut() else {
+            return Err(SinkError::IoError);
+        };
+
+        let published = open.publish(&messages).await;
+        if published.is_err() {
+            *connection = None;
+        }
+
+        published
+    }
+
+    fn name(&self) -> String {
+        format!("NATS {}/{}", self.address, self.subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event::IndexEvent;
+
+    use tokio::{net::TcpListener, spawn, sync::mpsc};
+
+    // A NATS server that answers every published message with `reply`, given
+    // the subject it was published to, and passes on what was published
+    async fn server(
+        reply: fn(&str) -> String,
+    ) -> (String, mpsc::UnboundedReceiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (sender, published) = mpsc::unbounded_channel();
+
+        spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(reader);
+            writer.write_all(b"INFO {}\r\n").await.unwrap();
+
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let arguments: Vec<&str> = line.split_whitespace().collect();
+
+                if arguments[0] == "HPUB" {
+                    let (subject, inbox) = (arguments[1], arguments[2]);
+                    let mut payload = vec![0; arguments[4].parse::<usize>().unwrap() + 2];
+                    reader.read_exact(&mut payload).await.unwrap();
+
+                    let headers = String::from_utf8_lossy(&payload).to_string();
+                    let _ = sender.send((subject.to_string(), headers));
+
+                    let reply = reply(subject);
+                    let message = match reply.strip_prefix("NATS/1.0") {
+                        Some(_) => format!(
+                            "HMSG {inbox} 1 {} {}\r\n{reply}\r\n",
+                            reply.len(),
+                            reply.len()
+                        ),
+                        None => format!("MSG {inbox} 1 {}\r\n{reply}\r\n", reply.len()),
+                    };
+                    writer.write_all(message.as_bytes()).await.unwrap();
+                }
+                line.clear();
+            }
+        });
+
+        (address, published)
+    }
+
+    fn events() -> Vec<Events> {
+        let events: Events = (1..=3)
+            .map(|slot| IndexEvent::Rollback {
+                slot,
+                blockhash: format!("block-{slot}"),
+                signatures: Vec::new(),
+            })
+            .collect();
+
+        vec![events]
+    }
+
+    #[tokio::test]
+    async fn waits_for_acks() {
+        let (address, mut published) =
+            server(|_| r#"{"stream":"solforge","seq":1}"#.to_string()).await;
+        let sink = NatsSink::new(address, "solforge".to_string());
+
+        assert_eq!(sink.publish(&events()).await, Ok(()));
+
+        for slot in 1..=3 {
+            let (subject, message) = published.recv().await.unwrap();
+            assert_eq!(subject, "solforge.rollback");
+            assert!(message.starts_with(&format!(
+                "NATS/1.0\r\nNats-Msg-Id: rollback:block-{slot}\r\n"
+            )));
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_when_not_stored() {
+        let (address, _) = server(|_| {
+            r#"{"error":{"code":503,"err_code":10077,"description":"maximum messages exceeded"}}"#
+                .to_string()
+        })
+        .await;
+        let sink = NatsSink::new(address, "solforge".to_string());
+
+        assert_eq!(
+            sink.publish(&events()).await,
+            Err(SinkError::Rejected("maximum messages exceeded".to_string()))
+        );
+
+        let (address, _) = server(|_| "NATS/1.0 503\r\n\r\n".to_string()).await;
+        let sink = NatsSink::new(address, "solforge".to_string());
+
+        assert_eq!(
+            sink.publish(&events()).await,
+            Err(SinkError::Rejected(
+                "No stream captures solforge.rollback".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    common::account::Account, indexer::errors::IndexerError, source::synthetic::SyntheticConfig,
+};
+
+use async_trait::async_trait;
+use solana_transaction_status::UiConfirmedBlock;
+use std::{path::PathBuf, str::FromStr};
+use tokio::sync::mpsc::Sender;
+
+//
+// Where the indexer gets its data from. A source pushes whole blocks (and
+// account updates, if it has them) to the indexer, which stores them the same
+// way regardless of where they came from. Sources following a cluster also
+// report how far along it is.
+//
+
+#[derive(Debug)]
+pub enum SourceUpdate {
+    Block {
+        slot: u64,
+        block: Box<UiConfirmedBlock>,
+    },
+    Account(Account),
+    Tip {
+        slot: u64,
+        finalized_slot: Option<u64>,
+    },
+}
+
+#[async_trait]
+pub trait Source: Send + Sync {
+    // Runs until the source is exhausted or the indexer stops listening
+    async fn run(&self, updates: Sender<SourceUpdate>) -> Result<(), IndexerError>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SourceKind {
+    // Poll getBlock over RPC for every slot
+    #[default]
+    Rpc,
+    // Consume a Geyser-style stream of slots, transactions and accounts
+    Geyser(String),
+    // Replay blocks saved to disk
+    File(PathBuf),
+    // Generate a made up chain locally
+    Synthetic(SyntheticConfig),
+}
+
+impl FromStr for SourceKind {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source.split_once(':') {
+            None if source == "rpc" => Ok(SourceKind::Rpc),
+            None if source == "synthetic" => Ok(SourceKind::Synthetic(Default::default())),
+            Some(("synthetic", options)) => Ok(SourceKind::Synthetic(options.parse()?)),
+            Some(("geyser", url)) if !url.is_empty() => Ok(SourceKind::Geyser(url.to_string())),
+            Some(("file", path)) if !path.is_empty() => Ok(SourceKind::File(PathBuf::from(path))),
+            _ => Err(format!("Unknown source: {source}")),
+        }
+    }
+}
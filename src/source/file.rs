@@ -0,0 +1,251 @@
+use crate::{
+    indexer::errors::IndexerError,
+    source::common::{Source, SourceUpdate},
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use solana_transaction_status::UiConfirmedBlock;
+use std::path::{Path, PathBuf};
+
+use tokio::{
+    fs::{self, File},
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc::Sender,
+};
+
+//
+// Replays blocks saved to disk, without touching the network. Either a single
+// file or a directory of them. Files whose names start with a slot number, like
+// `1000.json` or `1000-1999.ndjson`, are replayed in slot order, and the rest
+// after them in file name order. A .json file holds
+// one block, anything else holds one per line. Blocks can be bare
+// `UiConfirmedBlock`s or whole `getBlock` responses, and need to have been
+// fetched with the JSON transaction encoding.
+//
+// A block doesn't say which slot it's in, so it can be wrapped as
+// `{"slot": ..., "block": ...}` to give it. Otherwise it's taken to be in the
+// slot after its parent, which is wrong for blocks following skipped slots.
+//
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecordedBlock {
+    Slotted {
+        slot: u64,
+        block: Box<UiConfirmedBlock>,
+    },
+    Block(Box<UiConfirmedBlock>),
+    Response(RecordedResponse),
+}
+
+// Strict, so that anything else that happens to be an object isn't mistaken
+// for a skipped slot
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)]
+struct RecordedResponse {
+    jsonrpc: String,
+    id: Value,
+    // Skipped slots come back with a null result, or an error instead
+    #[serde(default)]
+    result: Option<Box<UiConfirmedBlock>>,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: PathBuf) -> Self {
+        FileSource { path }
+    }
+}
+
+#[async_trait]
+impl Source for FileSource {
+    async fn run(&self, updates: Sender<SourceUpdate>) -> Result<(), IndexerError> {
+        let paths = match fs::metadata(&self.path).await?.is_dir() {
+            true => {
+                let mut paths = Vec::new();
+                let mut entries = fs::read_dir(&self.path).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_type().await?.is_file() {
+                        paths.push(entry.path());
+                    }
+                }
+                sort_by_slot(&mut paths);
+                paths
+            }
+            false => vec![self.path.clone()],
+        };
+
+        for path in paths {
+            if !replay_file(&path, &updates).await? {
+                // Nobody's listening anymore
+                return Ok(());
+            }
+        }
+
+        println!("Finished replaying {:?}", self.path);
+
+        Ok(())
+    }
+}
+
+// By the slot each file name starts with, so that 999.json comes before
+// 1000.json, then by name
+fn sort_by_slot(paths: &mut [PathBuf]) {
+    paths.sort_by_cached_key(|path| (leading_slot(path).unwrap_or(u64::MAX), path.clone()));
+}
+
+fn leading_slot(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let digits = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..digits].parse().ok()
+}
+
+// Returns whether the indexer is still listening
+async fn replay_file(path: &Path, updates: &Sender<SourceUpdate>) -> Result<bool, IndexerError> {
+    println!("Replaying {path:?}");
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        let block = fs::read_to_string(path).await?;
+        return replay_block(&block, path, 1, updates).await;
+    }
+
+    let mut lines = BufReader::new(File::open(path).await?).lines();
+    let mut line_number = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !replay_block(&line, path, line_number, updates).await? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+async fn replay_block(
+    block: &str,
+    path: &Path,
+    line_number: usize,
+    updates: &Sender<SourceUpdate>,
+) -> Result<bool, IndexerError> {
+    let (slot, block) = match serde_json::from_str(block) {
+        Ok(RecordedBlock::Slotted { slot, block }) => (slot, block),
+        Ok(RecordedBlock::Block(block))
+        | Ok(RecordedBlock::Response(RecordedResponse {
+            result: Some(block),
+            ..
+        })) => (block.parent_slot + 1, block),
+        Ok(RecordedBlock::Response(RecordedResponse { result: None, .. })) => return Ok(true),
+        Err(error) => {
+            println!("Skipping unreadable block at {path:?}:{line_number}: {error}");
+            return Ok(true);
+        }
+    };
+
+    Ok(updates
+        .send(SourceUpdate::Block { slot, block })
+        .await
+        .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::sync::mpsc;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/blocks")
+            .join(name)
+    }
+
+    async fn replay(path: PathBuf) -> Vec<(u64, String)> {
+        let (updates, mut received) = mpsc::channel(16);
+        FileSource::new(path).run(updates).await.unwrap();
+
+        let mut blocks = Vec::new();
+        while let Some(update) = received.recv().await {
+            if let SourceUpdate::Block { slot, block } = update {
+                blocks.push((slot, block.blockhash));
+            }
+        }
+        blocks
+    }
+
+    #[tokio::test]
+    async fn replays_every_kind_of_recording() {
+        let blocks = replay(fixture("replay.ndjson")).await;
+        let slots: Vec<u64> = blocks.iter().map(|(slot, _)| *slot).collect();
+
+        // Given, then guessed from the parent
+        assert_eq!(slots, [1005, 1006, 1007]);
+    }
+
+    #[tokio::test]
+    async fn replays_a_single_block() {
+        let blocks = replay(fixture("mixed_encodings.json")).await;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, 2001);
+    }
+
+    #[test]
+    fn files_are_sorted_by_leading_slot() {
+        let mut paths: Vec<PathBuf> = [
+            "notes.ndjson",
+            "1000-1999.ndjson",
+            "999.json",
+            "10000.json",
+            "backfill.json",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        sort_by_slot(&mut paths);
+
+        assert_eq!(
+            paths,
+            [
+                "999.json",
+                "1000-1999.ndjson",
+                "10000.json",
+                "backfill.json",
+                "notes.ndjson"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn only_responses_count_as_skipped_slots() {
+        let parse = serde_json::from_str::<RecordedBlock>;
+
+        assert!(matches!(
+            parse(r#"{"jsonrpc": "2.0", "result": null, "id": 1}"#),
+            Ok(RecordedBlock::Response(RecordedResponse {
+                result: None,
+                ..
+            }))
+        ));
+        assert!(parse(r#"{"slot": 1008, "status": "confirmed"}"#).is_err());
+        assert!(parse(r#"{"result": null}"#).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    common::account::Account,
+    indexer::errors::IndexerError,
+    source::common::{Source, SourceUpdate},
+};
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use solana_account_decoder::UiAccount;
+use solana_transaction_status::{EncodedTransactionWithStatusMeta, Rewards, UiConfirmedBlock};
+use std::collections::BTreeMap;
+use tokio::{sync::mpsc::Sender, time::sleep};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const RECONNECT_DELAY: u64 = 1000;
+// Blocks still missing pieces this far behind the newest slot are given up on
+const MAX_PENDING_SLOTS: u64 = 150;
+
+//
+// Consumes a Geyser-style feed, modelled on Yellowstone gRPC but carried as
+// JSON over a websocket so it's easy to stand up a local mock. Slots,
+// transactions and block metadata arrive separately and out of order, so
+// they're pieced back together into whole blocks before being handed on. A
+// block only goes out once it has every transaction its metadata counts, even
+// if it's confirmed before then, and one still missing pieces after
+// MAX_PENDING_SLOTS is logged and dropped.
+//
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GeyserUpdate {
+    Slot {
+        slot: u64,
+        status: SlotStatus,
+    },
+    Transaction {
+        slot: u64,
+        index: u64,
+        transaction: Box<EncodedTransactionWithStatusMeta>,
+    },
+    BlockMeta(BlockMeta),
+    Account {
+        slot: u64,
+        pubkey: String,
+        account: UiAccount,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SlotStatus {
+    Processed,
+    Confirmed,
+    Finalized,
+    Dead,
+}
+
+#[derive(Deserialize)]
+struct BlockMeta {
+    slot: u64,
+    blockhash: String,
+    parent_slot: u64,
+    parent_blockhash: String,
+    block_time: Option<i64>,
+    block_height: Option<u64>,
+    rewards: Option<Rewards>,
+    executed_transaction_count: u64,
+}
+
+#[derive(Default)]
+struct PendingBlock {
+    transactions: Vec<(u64, EncodedTransactionWithStatusMeta)>,
+    meta: Option<BlockMeta>,
+}
+
+impl PendingBlock {
+    fn is_complete(&self) -> bool {
+        self.meta
+            .as_ref()
+            .is_some_and(|meta| self.transactions.len() as u64 >= meta.executed_transaction_count)
+    }
+
+    // What's still missing, for the log when a block is given up on
+    fn missing(&self) -> String {
+        match &self.meta {
+            Some(meta) => format!(
+                "has {} of {} transactions",
+                self.transactions.len(),
+                meta.executed_transaction_count
+            ),
+            None => format!(
+                "has {} transactions but no block meta",
+                self.transactions.len()
+            ),
+        }
+    }
+
+    fn into_block(mut self) -> Option<(u64, UiConfirmedBlock)> {
+        let meta = self.meta?;
+        self.transactions.sort_by_key(|(index, _)| *index);
+
+        let block = UiConfirmedBlock {
+            previous_blockhash: meta.parent_blockhash,
+            blockhash: meta.blockhash,
+            parent_slot: meta.parent_slot,
+            transactions: Some(
+                self.transactions
+                    .into_iter()
+                    .map(|(_, transaction)| transaction)
+                    .collect(),
+            ),
+            signatures: None,
+            rewards: meta.rewards,
+            num_reward_partitions: None,
+            block_time: meta.block_time,
+            block_height: meta.block_height,
+        };
+
+        Some((meta.slot, block))
+    }
+}
+
+pub struct GeyserSource {
+    url: String,
+    // Account updates to ask for, by address and by owning program
+    accounts: Vec<String>,
+    owners: Vec<String>,
+}
+
+impl GeyserSource {
+    pub fn new(url: String, accounts: Vec<String>, owners: Vec<String>) -> Self {
+        GeyserSource {
+            url,
+            accounts,
+            owners,
+        }
+    }
+
+    // Returns Ok once nobody is listening for updates anymore
+    async fn follow(&self, updates: &Sender<SourceUpdate>) -> Result<(), IndexerError> {
+        let (mut stream, _) = connect_async(&self.url).await?;
+
+        stream
+            .send(Message::Text(
+                json!({
+                    "slots": true,
+                    "transactions": true,
+                    "blocks_meta": true,
+                    "accounts": self.accounts,
+                    "owners": self.owners,
+                })
+                .to_string(),
+            ))
+            .await?;
+
+        let mut pending: BTreeMap<u64, PendingBlock> = BTreeMap::new();
+
+        while let Some(message) = stream.next().await {
+            let update = match message? {
+                Message::Text(text) => serde_json::from_str::<GeyserUpdate>(&text),
+                Message::Binary(data) => serde_json::from_slice::<GeyserUpdate>(&data),
+                _ => continue,
+            };
+
+            let update = match update {
+                Ok(update) => update,
+                Err(error) => {
+                    println!("Skipping unreadable Geyser update: {error}");
+                    continue;
+                }
+            };
+
+            let finished = match update {
+                GeyserUpdate::Slot { slot, status } => {
+                    let kept = pending.split_off(&slot.saturating_sub(MAX_PENDING_SLOTS));
+                    for (slot, block) in std::mem::replace(&mut pending, kept) {
+                        println!("Giving up on block {slot}, it {}", block.missing());
+                    }
+
+                    let tip = SourceUpdate::Tip {
+                        slot,
+                        finalized_slot: matches!(status, SlotStatus::Finalized).then_some(slot),
+                    };
+                    if updates.send(tip).await.is_err() {
+                        return Ok(());
+                    }
+
+                    // Complete blocks have gone out already, and the rest wait
+                    // for their missing pieces however confirmed they are
+                    if let SlotStatus::Dead = status {
+                        pending.remove(&slot);
+                    }
+                    None
+                }
+                GeyserUpdate::Transaction {
+                    slot,
+                    index,
+                    transaction,
+                } => {
+                    let block = pending.entry(slot).or_default();
+                    block.transactions.push((index, *transaction));
+                    take_if_complete(&mut pending, slot)
+                }
+                GeyserUpdate::BlockMeta(meta) => {
+                    let slot = meta.slot;
+                    pending.entry(slot).or_default().meta = Some(meta);
+                    take_if_complete(&mut pending, slot)
+                }
+                GeyserUpdate::Account {
+                    slot,
+                    pubkey,
+                    account,
+                } => {
+                    if let Some(account) = Account::from_ui_account(pubkey, &account, slot) {
+                        if updates.send(SourceUpdate::Account(account)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    None
+                }
+            };
+
+            if let Some((slot, block)) = finished {
+                if updates
+                    .send(SourceUpdate::Block {
+                        slot,
+                        block: Box::new(block),
+                    })
+                    .await
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(IndexerError::WebsocketClientError)
+    }
+}
+
+#[async_trait]
+impl Source for GeyserSource {
+    async fn run(&self, updates: Sender<SourceUpdate>) -> Result<(), IndexerError> {
+        loop {
+            match self.follow(&updates).await {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    println!("Geyser stream lost ({error}), reconnecting...");
+                    sleep(tokio::time::Duration::from_millis(RECONNECT_DELAY)).await;
+                }
+            }
+        }
+    }
+}
+
+fn take_if_complete(
+    pending: &mut BTreeMap<u64, PendingBlock>,
+    slot: u64,
+) -> Option<(u64, UiConfirmedBlock)> {
+    if pending.get(&slot)?.is_complete() {
+        pending.remove(&slot)?.into_block()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::synthetic::{ChainGenerator, SyntheticConfig};
+
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::{net::TcpListener, spawn, sync::mpsc, time::timeout};
+    use tokio_tungstenite::accept_async;
+
+    fn blocks() -> Vec<(u64, UiConfirmedBlock)> {
+        let config = "seed=5,tps=20,fork_rate=0,skip_rate=0,blocks=5";
+        ChainGenerator::new(config.parse::<SyntheticConfig>().unwrap()).collect()
+    }
+
+    // Newest first, so they come in backwards
+    fn transactions(slot: u64, block: &UiConfirmedBlock) -> Vec<Value> {
+        let transactions = block.transactions.clone().unwrap_or_default();
+        transactions
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(index, transaction)| {
+                json!({
+                    "type": "transaction",
+                    "slot": slot,
+                    "index": index,
+                    "transaction": transaction,
+                })
+            })
+            .collect()
+    }
+
+    fn meta(slot: u64, block: &UiConfirmedBlock) -> Value {
+        json!({
+            "type": "block_meta",
+            "slot": slot,
+            "blockhash": block.blockhash,
+            "parent_slot": block.parent_slot,
+            "parent_blockhash": block.previous_blockhash,
+            "block_time": block.block_time,
+            "block_height": block.block_height,
+            "rewards": block.rewards,
+            "executed_transaction_count": block.transactions.as_ref().map_or(0, Vec::len),
+        })
+    }
+
+    fn status(slot: u64, status: &str) -> Value {
+        json!({ "type": "slot", "slot": slot, "status": status })
+    }
+
+    // Serves each script to one connection, then closes it
+    async fn mock(scripts: Vec<Vec<Value>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        spawn(async move {
+            for script in scripts {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = accept_async(stream).await.unwrap();
+                let _subscription = stream.next().await;
+
+                for update in script {
+                    stream
+                        .send(Message::Text(update.to_string()))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn only_whole_blocks_come_out() {
+        let blocks = blocks();
+        let [(a, block_a), (b, block_b), (c, block_c), (d, block_d), (e, block_e)] = &blocks[..]
+        else {
+            panic!("five blocks");
+        };
+        assert!(blocks
+            .iter()
+            .all(|(_, block)| block.transactions.as_ref().unwrap().len() > 1));
+
+        let mut first = vec![status(*a, "processed")];
+        // Transactions ahead of their meta, and backwards
+        first.extend(transactions(*a, block_a));
+        first.extend([meta(*a, block_a), status(*a, "confirmed")]);
+        // Dead before its meta turns up
+        first.extend(transactions(*b, block_b).into_iter().take(1));
+        first.extend([status(*b, "dead"), meta(*b, block_b)]);
+        // Confirmed while still short of transactions
+        first.push(meta(*c, block_c));
+        first.extend(transactions(*c, block_c).into_iter().skip(1));
+        first.extend([status(*c, "confirmed"), status(*c, "finalized")]);
+        // Dropped before its meta
+        first.extend(transactions(*d, block_d));
+
+        let mut second = vec![meta(*e, block_e)];
+        second.extend(transactions(*e, block_e));
+
+        let url = mock(vec![first, second]).await;
+        let source = GeyserSource::new(url, Vec::new(), Vec::new());
+        let (sender, mut receiver) = mpsc::channel(100);
+        let running = spawn(async move { source.run(sender).await });
+
+        let mut received = Vec::new();
+        let until_e = async {
+            while let Some(update) = receiver.recv().await {
+                if let SourceUpdate::Block { slot, block } = update {
+                    received.push((slot, *block));
+                    if slot == *e {
+                        break;
+                    }
+                }
+            }
+        };
+        timeout(Duration::from_secs(5), until_e).await.unwrap();
+        running.abort();
+
+        assert_eq!(received, vec![(*a, block_a.clone()), (*e, block_e.clone())]);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod common;
+pub mod file;
+pub mod geyser;
+pub mod rpc;
+pub mod synthetic;
@@ -0,0 +1,138 @@
+use crate::{
+    indexer::errors::IndexerError,
+    source::common::{Source, SourceUpdate},
+};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+
+use solana_client::{
+    client_error::ClientErrorKind, nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig,
+    rpc_request::RpcError,
+};
+
+use tokio::{sync::mpsc::Sender, time::sleep};
+
+const SLOT_TIME: u64 = 400;
+const RETRIES_PER_SLOT: u64 = 3;
+const RECONNECT_DELAY: u64 = 1000;
+// How far behind the newest slot blocks are fetched, so they've been produced
+// and confirmed by the time they're asked for
+const SLOTS_BEHIND: u64 = 100;
+
+// Follows slot notifications and fetches each block with getBlock
+pub struct RpcSource {
+    rpc_url: String,
+    websocket_url: String,
+}
+
+impl RpcSource {
+    pub fn new(rpc_url: String, websocket_url: String) -> Self {
+        RpcSource {
+            rpc_url,
+            websocket_url,
+        }
+    }
+
+    // Returns Ok once nobody is listening for updates anymore
+    async fn follow(
+        &self,
+        rpc_client: &RpcClient,
+        updates: &Sender<SourceUpdate>,
+    ) -> Result<(), IndexerError> {
+        let pubsub_client = PubsubClient::new(&self.websocket_url).await?;
+        let (mut slot_notifications, _) = pubsub_client.slot_subscribe().await?;
+
+        while let Some(slot_info) = slot_notifications.next().await {
+            // The root is as good as finalized
+            let tip = SourceUpdate::Tip {
+                slot: slot_info.slot,
+                finalized_slot: Some(slot_info.root),
+            };
+            if updates.send(tip).await.is_err() {
+                return Ok(());
+            }
+
+            // A young cluster doesn't have anything that far back yet
+            let Some(slot) = slot_info.slot.checked_sub(SLOTS_BEHIND) else {
+                continue;
+            };
+
+            loop {
+                let encoded_block = rpc_client
+                    .get_block_with_config(
+                        slot,
+                        RpcBlockConfig {
+                            max_supported_transaction_version: Some(0),
+                            commitment: Some(CommitmentConfig {
+                                commitment: CommitmentLevel::Confirmed,
+                            }),
+                            ..Default::default()
+                        },
+                    )
+                    .await;
+
+                match encoded_block {
+                    Ok(encoded_block) => {
+                        // TODO: Handle reorgs
+
+                        if updates
+                            .send(SourceUpdate::Block {
+                                slot,
+                                block: Box::new(encoded_block),
+                            })
+                            .await
+                            .is_err()
+                        {
+                            return Ok(());
+                        }
+
+                        // Done. Move to the next slot
+                        break;
+                    }
+                    Err(error) => match error.kind() {
+                        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                            code: -32007,
+                            ..
+                        })
+                        | ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                            code: -32009,
+                            ..
+                        }) => {
+                            // Skip slot as it will never be availble to us
+                            break;
+                        }
+                        _ => {
+                            // Wait for the block to arrive
+                            sleep(tokio::time::Duration::from_millis(
+                                SLOT_TIME / RETRIES_PER_SLOT,
+                            ))
+                            .await
+                        }
+                    },
+                }
+            }
+        }
+
+        Err(IndexerError::WebsocketClientError)
+    }
+}
+
+#[async_trait]
+impl Source for RpcSource {
+    async fn run(&self, updates: Sender<SourceUpdate>) -> Result<(), IndexerError> {
+        let rpc_client = RpcClient::new(self.rpc_url.clone());
+
+        loop {
+            match self.follow(&rpc_client, &updates).await {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    println!("Slot subscription lost ({error}), reconnecting...");
+                    sleep(tokio::time::Duration::from_millis(RECONNECT_DELAY)).await;
+                }
+            }
+        }
+    }
+}
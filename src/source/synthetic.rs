@@ -0,0 +1,598 @@
+use crate::{
+    common::transaction::SYSTEM_PROGRAM_ID,
+    indexer::errors::IndexerError,
+    source::common::{Source, SourceUpdate},
+};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use solana_transaction_status::UiConfirmedBlock;
+use std::{str::FromStr, time::Duration};
+use tokio::{sync::mpsc::Sender, time::sleep};
+
+const GENESIS_TIME: i64 = 1_700_000_000;
+const SLOT_TIME: u64 = 400;
+const FEE: u64 = 5000;
+const INITIAL_BALANCE: u64 = 1_000_000_000_000;
+const TRANSFER_COMPUTE_UNITS: u64 = 150;
+const FAILED_COMPUTE_UNITS: u64 = 300;
+const LOOKUP_TABLE_SIZE: usize = 256;
+
+//
+// Generates a made up but internally consistent chain: blocks link up by hash
+// and height, balances carry over from block to block, and transfers that
+// can't be afforded fail the way the system program would fail them. The same
+// seed always gives the same chain, so it doubles as a fixture for tests of
+// process_block and as a local load source.
+//
+// Forks are emitted as a block built on the tip that the next canonical block
+// then ignores, building on the tip itself in a later slot.
+//
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntheticConfig {
+    pub seed: u64,
+    pub tps: u64,
+    // Chances per slot
+    pub fork_rate: f64,
+    pub skip_rate: f64,
+    // Chances per transaction
+    pub error_rate: f64,
+    pub v0_rate: f64,
+    // Size of the pool of accounts transferring between each other
+    pub accounts: usize,
+    // Stop after this many canonical blocks
+    pub blocks: Option<u64>,
+    // Time between slots, or zero to go as fast as the indexer can keep up
+    pub interval_ms: u64,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        SyntheticConfig {
+            seed: 0,
+            tps: 100,
+            fork_rate: 0.01,
+            skip_rate: 0.05,
+            error_rate: 0.05,
+            v0_rate: 0.2,
+            accounts: 1000,
+            blocks: None,
+            interval_ms: SLOT_TIME,
+        }
+    }
+}
+
+// Comma separated key=value pairs, e.g. "seed=7,tps=2000,fork_rate=0.1"
+impl FromStr for SyntheticConfig {
+    type Err = String;
+
+    fn from_str(options: &str) -> Result<Self, Self::Err> {
+        let mut config = SyntheticConfig::default();
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value: {option}"))?;
+
+            match key {
+                "seed" => config.seed = parse(key, value)?,
+                "tps" => config.tps = parse(key, value)?,
+                "fork_rate" => config.fork_rate = parse(key, value)?,
+                "skip_rate" => config.skip_rate = parse(key, value)?,
+                "error_rate" => config.error_rate = parse(key, value)?,
+                "v0_rate" => config.v0_rate = parse(key, value)?,
+                "accounts" => config.accounts = parse(key, value)?,
+                "blocks" => config.blocks = Some(parse(key, value)?),
+                "interval_ms" => config.interval_ms = parse(key, value)?,
+                _ => return Err(format!("Unknown synthetic option: {key}")),
+            }
+        }
+
+        if config.accounts < 2 {
+            return Err("Need at least 2 accounts".to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {key}: {value}"))
+}
+
+// SplitMix64, so the chain for a seed never changes under us
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    fn base58<const N: usize>(&mut self) -> String {
+        let mut bytes = [0; N];
+        for chunk in bytes.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+        bs58::encode(bytes).into_string()
+    }
+}
+
+#[derive(Clone)]
+struct Tip {
+    slot: u64,
+    blockhash: String,
+    block_height: u64,
+}
+
+pub struct ChainGenerator {
+    config: SyntheticConfig,
+    rng: Rng,
+    accounts: Vec<String>,
+    balances: Vec<u64>,
+    leader: String,
+    // A table holds at most 256 addresses, so there's one for each 256 accounts
+    lookup_tables: Vec<String>,
+    slot: u64,
+    blocks: u64,
+    tip: Tip,
+    // Only the tip can be forked, so everything up to it is final
+    finalized_slot: u64,
+    // Canonical block held back while a fork is emitted ahead of it
+    queued: Option<(u64, UiConfirmedBlock)>,
+    // Thousandths of a transaction owed to later blocks, so low rates still
+    // come out right on average
+    owed_transactions: u64,
+}
+
+impl ChainGenerator {
+    pub fn new(config: SyntheticConfig) -> Self {
+        let mut rng = Rng(config.seed);
+
+        let accounts = (0..config.accounts).map(|_| rng.base58::<32>()).collect();
+        let genesis = Tip {
+            slot: 0,
+            blockhash: rng.base58::<32>(),
+            block_height: 0,
+        };
+
+        ChainGenerator {
+            // The leader's balance goes on the end
+            balances: vec![INITIAL_BALANCE; config.accounts + 1],
+            accounts,
+            leader: rng.base58::<32>(),
+            lookup_tables: (0..config.accounts.div_ceil(LOOKUP_TABLE_SIZE))
+                .map(|_| rng.base58::<32>())
+                .collect(),
+            slot: 0,
+            blocks: 0,
+            tip: genesis,
+            finalized_slot: 0,
+            queued: None,
+            owed_transactions: 0,
+            rng,
+            config,
+        }
+    }
+
+    // The newest slot produced so far, including any held back
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    pub fn finalized_slot(&self) -> u64 {
+        self.finalized_slot
+    }
+
+    fn advance_slot(&mut self) {
+        loop {
+            self.slot += 1;
+            if !self.rng.chance(self.config.skip_rate) {
+                break;
+            }
+        }
+    }
+
+    fn transactions_per_block(&mut self) -> u64 {
+        self.owed_transactions += self.config.tps * SLOT_TIME;
+        let transactions = self.owed_transactions / 1000;
+        self.owed_transactions %= 1000;
+        transactions
+    }
+
+    fn block(&mut self, slot: u64, parent: &Tip, balances: &mut [u64]) -> (UiConfirmedBlock, Tip) {
+        let transactions: Vec<Value> = (0..self.transactions_per_block())
+            .filter_map(|_| self.transaction(balances))
+            .collect();
+
+        // Half the fees are burnt, the leader gets the rest
+        let fees = transactions.len() as u64 * FEE;
+        let leader_balance = balances.last_mut().expect("Missing leader balance");
+        *leader_balance += fees / 2;
+        let tip = Tip {
+            slot,
+            blockhash: self.rng.base58::<32>(),
+            block_height: parent.block_height + 1,
+        };
+
+        let block = json!({
+            "previousBlockhash": parent.blockhash,
+            "blockhash": tip.blockhash,
+            "parentSlot": parent.slot,
+            "transactions": transactions,
+            "rewards": [{
+                "pubkey": self.leader,
+                "lamports": fees / 2,
+                "postBalance": leader_balance,
+                "rewardType": "Fee",
+                "commission": null,
+            }],
+            "blockTime": GENESIS_TIME + (slot * SLOT_TIME / 1000) as i64,
+            "blockHeight": tip.block_height,
+        });
+
+        let block = serde_json::from_value(block).expect("Error generating block");
+        (block, tip)
+    }
+
+    // None for payers that can't cover the fee, as the leader would drop those
+    // rather than include them
+    fn transaction(&mut self, balances: &mut [u64]) -> Option<Value> {
+        let accounts = self.accounts.len() as u64;
+        let payer = self.rng.below(accounts) as usize;
+        let recipient = (payer + 1 + self.rng.below(accounts - 1) as usize) % accounts as usize;
+
+        if balances[payer] < FEE {
+            return None;
+        }
+
+        // Failures are transfers of more than the payer has left
+        let available = balances[payer].saturating_sub(FEE);
+        let failed = self.rng.chance(self.config.error_rate) || available == 0;
+        let amount = match failed {
+            true => available + 1 + self.rng.below(INITIAL_BALANCE),
+            false => 1 + self.rng.below(available.min(INITIAL_BALANCE / 1000)),
+        };
+
+        let pre = (balances[payer], balances[recipient]);
+        balances[payer] -= FEE;
+        if !failed {
+            balances[payer] -= amount;
+            balances[recipient] += amount;
+        }
+        let post = (balances[payer], balances[recipient]);
+
+        let mut data = vec![2, 0, 0, 0];
+        data.extend(amount.to_le_bytes());
+        let data = bs58::encode(data).into_string();
+
+        let (err, status, log, compute_units) = match failed {
+            true => {
+                let err = json!({ "InstructionError": [0, { "Custom": 1 }] });
+                (
+                    err.clone(),
+                    json!({ "Err": err }),
+                    format!("Program {SYSTEM_PROGRAM_ID} failed: custom program error: 0x1"),
+                    FAILED_COMPUTE_UNITS,
+                )
+            }
+            false => (
+                Value::Null,
+                json!({ "Ok": null }),
+                format!("Program {SYSTEM_PROGRAM_ID} success"),
+                TRANSFER_COMPUTE_UNITS,
+            ),
+        };
+
+        let log_messages = json!([format!("Program {SYSTEM_PROGRAM_ID} invoke [1]"), log]);
+        let header = json!({
+            "numRequiredSignatures": 1,
+            "numReadonlySignedAccounts": 0,
+            "numReadonlyUnsignedAccounts": 1,
+        });
+        let signature = self.rng.base58::<64>();
+        let recent_blockhash = self.tip.blockhash.clone();
+
+        // v0 transactions load the recipient from a lookup table, which puts
+        // it after the static keys
+        let transaction = match self.rng.chance(self.config.v0_rate) {
+            true => json!({
+                "transaction": {
+                    "signatures": [signature],
+                    "message": {
+                        "accountKeys": [self.accounts[payer], SYSTEM_PROGRAM_ID],
+                        "header": header,
+                        "recentBlockhash": recent_blockhash,
+                        "instructions": [{
+                            "programIdIndex": 1,
+                            "accounts": [0, 2],
+                            "data": data,
+                            "stackHeight": null,
+                        }],
+                        "addressTableLookups": [{
+                            "accountKey": self.lookup_tables[recipient / LOOKUP_TABLE_SIZE],
+                            "writableIndexes": [recipient % LOOKUP_TABLE_SIZE],
+                            "readonlyIndexes": [],
+                        }],
+                    },
+                },
+                "meta": {
+                    "err": err,
+                    "status": status,
+                    "fee": FEE,
+                    "preBalances": [pre.0, 1, pre.1],
+                    "postBalances": [post.0, 1, post.1],
+                    "innerInstructions": [],
+                    "logMessages": log_messages,
+                    "preTokenBalances": [],
+                    "postTokenBalances": [],
+                    "rewards": [],
+                    "loadedAddresses": {
+                        "writable": [self.accounts[recipient]],
+                        "readonly": [],
+                    },
+                    "computeUnitsConsumed": compute_units,
+                },
+                "version": 0,
+            }),
+            false => json!({
+                "transaction": {
+                    "signatures": [signature],
+                    "message": {
+                        "accountKeys": [
+                            self.accounts[payer],
+                            self.accounts[recipient],
+                            SYSTEM_PROGRAM_ID,
+                        ],
+                        "header": header,
+                        "recentBlockhash": recent_blockhash,
+                        "instructions": [{
+                            "programIdIndex": 2,
+                            "accounts": [0, 1],
+                            "data": data,
+                            "stackHeight": null,
+                        }],
+                    },
+                },
+                "meta": {
+                    "err": err,
+                    "status": status,
+                    "fee": FEE,
+                    "preBalances": [pre.0, pre.1, 1],
+                    "postBalances": [post.0, post.1, 1],
+                    "innerInstructions": [],
+                    "logMessages": log_messages,
+                    "preTokenBalances": [],
+                    "postTokenBalances": [],
+                    "rewards": [],
+                    "computeUnitsConsumed": compute_units,
+                },
+                "version": "legacy",
+            }),
+        };
+
+        Some(transaction)
+    }
+}
+
+impl Iterator for ChainGenerator {
+    // Blocks come with the slot they were produced in
+    type Item = (u64, UiConfirmedBlock);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(block) = self.queued.take() {
+            return Some(block);
+        }
+
+        if self
+            .config
+            .blocks
+            .is_some_and(|blocks| self.blocks >= blocks)
+        {
+            return None;
+        }
+
+        let tip = self.tip.clone();
+        self.finalized_slot = tip.slot;
+        self.advance_slot();
+
+        // The fork's balance changes are thrown away along with it
+        let fork = match tip.slot > 0 && self.rng.chance(self.config.fork_rate) {
+            true => {
+                let mut balances = self.balances.clone();
+                let (fork, _) = self.block(self.slot, &tip, &mut balances);
+                let fork_slot = self.slot;
+                self.advance_slot();
+                Some((fork_slot, fork))
+            }
+            false => None,
+        };
+
+        let mut balances = std::mem::take(&mut self.balances);
+        let (block, new_tip) = self.block(self.slot, &tip, &mut balances);
+        self.balances = balances;
+        self.tip = new_tip;
+        self.blocks += 1;
+
+        match fork {
+            Some(fork) => {
+                self.queued = Some((self.slot, block));
+                Some(fork)
+            }
+            None => Some((self.slot, block)),
+        }
+    }
+}
+
+pub struct SyntheticSource {
+    config: SyntheticConfig,
+}
+
+impl SyntheticSource {
+    pub fn new(config: SyntheticConfig) -> Self {
+        SyntheticSource { config }
+    }
+}
+
+#[async_trait]
+impl Source for SyntheticSource {
+    async fn run(&self, updates: Sender<SourceUpdate>) -> Result<(), IndexerError> {
+        let mut generator = ChainGenerator::new(self.config.clone());
+
+        while let Some((slot, block)) = generator.next() {
+            let tip = SourceUpdate::Tip {
+                slot: generator.slot(),
+                finalized_slot: Some(generator.finalized_slot()),
+            };
+
+            let block = SourceUpdate::Block {
+                slot,
+                block: Box::new(block),
+            };
+
+            for update in [block, tip] {
+                if updates.send(update).await.is_err() {
+                    // Nobody's listening anymore
+                    return Ok(());
+                }
+            }
+
+            if self.config.interval_ms > 0 {
+                sleep(Duration::from_millis(self.config.interval_ms)).await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::{HashMap, HashSet};
+
+    fn config(options: &str) -> SyntheticConfig {
+        options.parse().unwrap()
+    }
+
+    fn transaction_count(block: &UiConfirmedBlock) -> usize {
+        block.transactions.as_ref().map_or(0, Vec::len)
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_chain() {
+        let config = config("seed=7,tps=50,fork_rate=0.2,blocks=50");
+
+        let chain: Vec<_> = ChainGenerator::new(config.clone()).collect();
+        assert_eq!(
+            chain,
+            ChainGenerator::new(config.clone()).collect::<Vec<_>>()
+        );
+
+        let other: Vec<_> = ChainGenerator::new(SyntheticConfig { seed: 8, ..config }).collect();
+        assert_ne!(chain, other);
+    }
+
+    #[test]
+    fn blocks_link_up_by_hash_height_and_slot() {
+        let generator = ChainGenerator::new(config(
+            "seed=3,tps=10,fork_rate=0.2,skip_rate=0.2,blocks=200",
+        ));
+
+        // Blockhash to slot and height, starting from genesis
+        let mut seen: HashMap<String, (u64, u64)> = HashMap::new();
+        let mut forks = 0;
+        let mut last_slot = 0;
+
+        for (slot, block) in generator {
+            let (parent_slot, parent_height) = match seen.get(&block.previous_blockhash) {
+                Some(parent) => *parent,
+                None if seen.is_empty() => (0, 0),
+                None => panic!("Block {} has an unknown parent", block.blockhash),
+            };
+
+            assert_eq!(block.parent_slot, parent_slot);
+            assert_eq!(block.block_height, Some(parent_height + 1));
+            assert!(slot > parent_slot);
+            assert!(slot > last_slot);
+
+            // A fork and the canonical block after it share a parent
+            if seen
+                .values()
+                .any(|(_, height)| *height == parent_height + 1)
+            {
+                forks += 1;
+            }
+
+            seen.insert(block.blockhash, (slot, parent_height + 1));
+            last_slot = slot;
+        }
+
+        assert!(forks > 0);
+    }
+
+    #[test]
+    fn low_rates_still_produce_transactions() {
+        let generator = ChainGenerator::new(config("tps=1,fork_rate=0,skip_rate=0,blocks=50"));
+
+        // 0.4 a slot
+        let transactions: usize = generator.map(|(_, block)| transaction_count(&block)).sum();
+        assert_eq!(transactions, 20);
+    }
+
+    #[test]
+    fn lookup_table_entries_are_one_account_each() {
+        let generator = ChainGenerator::new(config(
+            "seed=5,tps=500,accounts=600,v0_rate=1,fork_rate=0,blocks=20",
+        ));
+
+        // Table and index to the address loaded from it
+        let mut entries: HashMap<(String, u64), String> = HashMap::new();
+        for (_, block) in generator {
+            for transaction in block.transactions.unwrap() {
+                let transaction = serde_json::to_value(transaction).unwrap();
+                let lookup = &transaction["transaction"]["message"]["addressTableLookups"][0];
+                let key = (
+                    lookup["accountKey"].as_str().unwrap().to_string(),
+                    lookup["writableIndexes"][0].as_u64().unwrap(),
+                );
+                let address = transaction["meta"]["loadedAddresses"]["writable"][0]
+                    .as_str()
+                    .unwrap()
+                    .to_string();
+
+                assert_eq!(entries.entry(key).or_insert(address.clone()), &address);
+            }
+        }
+
+        // 600 accounts take three tables
+        let tables: HashSet<&String> = entries.keys().map(|(table, _)| table).collect();
+        assert_eq!(tables.len(), 3);
+    }
+
+    #[test]
+    fn payers_short_of_the_fee_are_left_out() {
+        let mut generator = ChainGenerator::new(config("accounts=2,fork_rate=0,blocks=10"));
+        generator.balances = vec![FEE - 1, FEE - 1, 0];
+
+        for (_, block) in generator {
+            assert_eq!(transaction_count(&block), 0);
+            assert_eq!(block.rewards.unwrap()[0].lamports, 0);
+        }
+    }
+}
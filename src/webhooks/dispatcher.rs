@@ -0,0 +1,579 @@
+use crate::{
+    events::{
+        bus::{EventBus, Events},
+        event::IndexEvent,
+    },
+    webhooks::{
+        registry::{now, Delivery, DeliveryAttempt, Webhook, WebhookRegistry},
+        target::PublicResolver,
+    },
+};
+
+use hmac::{Hmac, Mac};
+use reqwest::{header::CONTENT_TYPE, redirect::Policy, Client, StatusCode};
+use serde_json::json;
+use sha2::Sha256;
+use std::{collections::HashMap, fmt::Write, sync::Arc, time::Duration};
+
+use tokio::{
+    select, spawn,
+    sync::{
+        broadcast::{self, error::RecvError, Sender},
+        mpsc::{self, error::TrySendError},
+        Semaphore,
+    },
+    task::JoinHandle,
+    time::sleep,
+};
+
+pub const SIGNATURE_HEADER: &str = "x-solforge-signature";
+pub const DELIVERY_HEADER: &str = "x-solforge-delivery";
+
+const MAX_ATTEMPTS: u32 = 6;
+// Doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// Deliveries in flight at once to each webhook
+const MAX_IN_FLIGHT: usize = 8;
+// Deliveries waiting on each webhook, beyond which they're dead lettered
+const QUEUE_SIZE: usize = 1024;
+
+//
+// Follows the event bus and POSTs every transaction a webhook is interested in
+// to it. Every webhook has its own queue, so a slow or dead receiver only holds
+// up its own deliveries, and once its queue is full further deliveries go
+// straight to the dead letter queue. Failed deliveries are retried with
+// backoff and end up in the dead letter queue once they run out of attempts.
+//
+// Delivery is at least once: after falling behind the bus the dispatcher picks
+// up again from the last slot it saw, which can send some deliveries twice. If
+// the bus has already let go of some of what was missed, there's nothing left
+// to deliver or dead letter from, so the gap is logged and the dispatcher
+// carries on with whatever the bus still holds.
+//
+
+pub struct WebhookDispatcher {
+    task: Option<JoinHandle<()>>,
+    registry: Arc<WebhookRegistry>,
+    events: Arc<EventBus>,
+    tx: Sender<()>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(registry: Arc<WebhookRegistry>, events: Arc<EventBus>, tx: Sender<()>) -> Self {
+        WebhookDispatcher {
+            task: None,
+            registry,
+            events,
+            tx,
+        }
+    }
+
+    pub async fn start(&mut self) {
+        if self.task.is_some() {
+            println!("Webhook dispatcher already running");
+            return;
+        }
+
+        println!("Starting webhook dispatcher...");
+
+        let Some(mut redeliveries) = self.registry.take_redeliveries().await else {
+            println!("Webhook dispatcher already started once");
+            return;
+        };
+        // Everything published once started gets delivered. Catching up after
+        // lagging starts from the latest slot before subscribing, for when
+        // nothing has been received by then.
+        let started_at = self.events.latest_slot().await;
+        let (_, mut receiver) = self.events.subscribe(None).await;
+
+        self.task = Some(spawn({
+            let registry = self.registry.clone();
+            let events = self.events.clone();
+
+            async move {
+                let client = client(registry.allow_private_targets());
+                let mut queues = HashMap::new();
+                let mut last_slot = started_at;
+
+                loop {
+                    let deliveries = select! {
+                        received = receiver.recv() => match received {
+                            Ok(received) => {
+                                last_slot = received.last().map(IndexEvent::slot).or(last_slot);
+                                deliveries(&registry, &received).await
+                            }
+                            Err(RecvError::Lagged(skipped)) => {
+                                println!("Webhook dispatcher skipped {skipped} batches, catching up");
+
+                                let (backlog, resubscribed) = catch_up(&events, last_slot).await;
+                                receiver = resubscribed;
+
+                                let mut deliveries = Vec::new();
+                                for events in backlog {
+                                    deliveries.extend(self::deliveries(&registry, &events).await);
+                                }
+                                deliveries
+                            }
+                            Err(RecvError::Closed) => break,
+                        },
+                        Some(delivery) = redeliveries.recv() => {
+                            match registry.webhook(delivery.webhook_id).await {
+                                Some(webhook) => vec![(webhook, delivery)],
+                                None => Vec::new(),
+                            }
+                        }
+                    };
+
+                    // Queues of unregistered webhooks have been given up on
+                    queues.retain(|_, queue: &mut mpsc::Sender<Delivery>| !queue.is_closed());
+
+                    for (webhook, delivery) in deliveries {
+                        let queue = queues
+                            .entry(webhook.id)
+                            .or_insert_with(|| queue(client.clone(), registry.clone(), webhook.id));
+
+                        match queue.try_send(delivery) {
+                            Ok(()) | Err(TrySendError::Closed(_)) => (),
+                            Err(TrySendError::Full(delivery)) => {
+                                let error = "Too many deliveries queued for the webhook";
+                                registry.dead_letter(delivery, 0, error.to_string()).await;
+                            }
+                        }
+                    }
+                }
+            }
+        }));
+
+        println!("Webhook dispatcher started");
+    }
+
+    pub async fn stop(&mut self) {
+        if let Some(task) = &self.task.take() {
+            println!("Stopping webhook dispatcher...");
+            task.abort();
+            println!("Webhook dispatcher stopped!")
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        if let Some(task) = self.task.take() {
+            println!("Webhook dispatcher running");
+            let mut rx = self.tx.subscribe();
+
+            select! {
+                _ = task => println!("Webhook dispatcher stopped on its own"),
+                _ = rx.recv() => println!("Webhook dispatcher interrupted"),
+            }
+        }
+
+        self.stop().await;
+    }
+}
+
+// Whatever was missed since `last_slot`, or as much of it as the bus still has
+async fn catch_up(
+    events: &EventBus,
+    last_slot: Option<u64>,
+) -> (Vec<Events>, broadcast::Receiver<Events>) {
+    let from_slot = last_slot.unwrap_or_default();
+
+    match events.resume(from_slot).await {
+        Some(resumed) => resumed,
+        None => {
+            println!(
+                "Webhook dispatcher lost events from slot {from_slot}, they're gone from the bus"
+            );
+            events.subscribe(Some(from_slot)).await
+        }
+    }
+}
+
+// Deliveries to one webhook, a few at a time. Stops once the webhook has been
+// unregistered.
+fn queue(
+    client: Client,
+    registry: Arc<WebhookRegistry>,
+    webhook_id: u64,
+) -> mpsc::Sender<Delivery> {
+    let (queue, mut deliveries) = mpsc::channel(QUEUE_SIZE);
+
+    spawn(async move {
+        let permits = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+
+        while let Some(delivery) = deliveries.recv().await {
+            let Some(webhook) = registry.webhook(webhook_id).await else {
+                break;
+            };
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                break;
+            };
+
+            spawn({
+                let client = client.clone();
+                let registry = registry.clone();
+
+                async move {
+                    deliver(&client, &registry, &webhook, delivery).await;
+                    drop(permit);
+                }
+            });
+        }
+    });
+
+    queue
+}
+
+// Redirects aren't followed and, unless private targets are allowed, names
+// only resolve to public addresses
+fn client(allow_private_targets: bool) -> Client {
+    let mut client = Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(Policy::none());
+    if !allow_private_targets {
+        client = client.dns_resolver(Arc::new(PublicResolver));
+    }
+
+    client.build().expect("Error creating webhook client")
+}
+
+// One per webhook each transaction among the events matches
+async fn deliveries(registry: &WebhookRegistry, events: &[IndexEvent]) -> Vec<(Webhook, Delivery)> {
+    let mut deliveries = Vec::new();
+
+    for event in events {
+        let IndexEvent::Transaction { slot, transaction } = event else {
+            continue;
+        };
+
+        for webhook in registry.matching(transaction).await {
+            let id = format!("{}-{}", webhook.id, transaction.id);
+            let payload = json!({
+                "delivery_id": id,
+                "webhook_id": webhook.id,
+                "slot": slot,
+                "transaction": transaction,
+            });
+
+            let delivery = Delivery {
+                id,
+                webhook_id: webhook.id,
+                payload,
+            };
+            deliveries.push((webhook, delivery));
+        }
+    }
+
+    deliveries
+}
+
+async fn deliver(
+    client: &Client,
+    registry: &WebhookRegistry,
+    webhook: &Webhook,
+    delivery: Delivery,
+) {
+    let body = delivery.payload.to_string();
+    let signature = sign(&webhook.secret, &body);
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = client
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(DELIVERY_HEADER, &delivery.id)
+            .body(body.clone())
+            .send()
+            .await;
+
+        let (status, error) = match response {
+            Ok(response) if response.status().is_success() => (Some(response.status()), None),
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("Receiver responded with {}", response.status())),
+            ),
+            Err(error) => (None, Some(error.to_string())),
+        };
+
+        registry
+            .log(
+                webhook.id,
+                DeliveryAttempt {
+                    delivery_id: delivery.id.clone(),
+                    attempt,
+                    timestamp: now(),
+                    status: status.map(|status| status.as_u16()),
+                    error: error.clone(),
+                    delivered: error.is_none(),
+                },
+            )
+            .await;
+
+        let Some(error) = error else {
+            return;
+        };
+
+        // The receiver turning the payload down won't change on a retry
+        if attempt == MAX_ATTEMPTS || !status.is_none_or(retryable) {
+            registry.dead_letter(delivery, attempt, error).await;
+            return;
+        }
+
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+// HMAC-SHA256 of the body, as sha256=<hex>
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold("sha256=".to_string(), |mut signature, byte| {
+            let _ = write!(signature, "{byte:02x}");
+            signature
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::transaction::Transaction,
+        source::synthetic::{ChainGenerator, SyntheticConfig},
+        webhooks::registry::{WebhookFilter, WebhookRegistration},
+    };
+
+    use axum::{http::HeaderMap, routing::post, Router};
+    use serde_json::Value;
+    use std::{collections::VecDeque, future::pending};
+    use tokio::{net::TcpListener, sync::Mutex, time::timeout};
+
+    // Answers deliveries with each of `statuses` in turn, then the last one
+    // from then on, passing on what it was sent. With no statuses it never
+    // answers at all.
+    async fn receiver(
+        statuses: &[StatusCode],
+    ) -> (String, mpsc::UnboundedReceiver<(HeaderMap, String)>) {
+        let (sender, received) = mpsc::unbounded_channel();
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses.to_vec())));
+        let routes = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| async move {
+                let _ = sender.send((headers, body));
+
+                let mut statuses = statuses.lock().await;
+                let status = match statuses.len() {
+                    0 => None,
+                    1 => statuses.front().copied(),
+                    _ => statuses.pop_front(),
+                };
+                drop(statuses);
+
+                match status {
+                    Some(status) => status,
+                    None => pending().await,
+                }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        spawn(async move { axum::serve(listener, routes).await });
+
+        (url, received)
+    }
+
+    fn transactions() -> Vec<Transaction> {
+        let config = "seed=3,tps=20,fork_rate=0,error_rate=0,v0_rate=0,blocks=5";
+        ChainGenerator::new(config.parse::<SyntheticConfig>().unwrap())
+            .flat_map(|(slot, block)| {
+                block
+                    .transactions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |transaction| {
+                        let mut transaction = Transaction::try_from(transaction).unwrap();
+                        transaction.slot = slot;
+                        transaction
+                    })
+            })
+            .collect()
+    }
+
+    // Registers a webhook for the transactions' payer, then publishes them
+    async fn dispatch(
+        url: String,
+        transactions: &[Transaction],
+    ) -> (Arc<WebhookRegistry>, Webhook) {
+        let registry = Arc::new(WebhookRegistry::new(true));
+        let events = Arc::new(EventBus::new());
+        let (tx, _) = broadcast::channel(1);
+
+        let webhook = registry
+            .register(WebhookRegistration {
+                url,
+                filter: WebhookFilter {
+                    addresses: [transactions[0].accounts[0].clone()].into(),
+                    ..Default::default()
+                },
+                secret: "shh".to_string(),
+            })
+            .await;
+
+        let mut dispatcher = WebhookDispatcher::new(registry.clone(), events.clone(), tx);
+        dispatcher.start().await;
+        events
+            .publish(
+                transactions
+                    .iter()
+                    .map(|transaction| IndexEvent::Transaction {
+                        slot: transaction.slot,
+                        transaction: Box::new(transaction.clone()),
+                    })
+                    .collect(),
+            )
+            .await;
+
+        (registry, webhook)
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payloads() {
+        let (url, mut received) = receiver(&[StatusCode::OK]).await;
+        let transaction = transactions().remove(0);
+        let (registry, webhook) = dispatch(url, std::slice::from_ref(&transaction)).await;
+
+        let (headers, body) = timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        let delivery_id = format!("{}-{}", webhook.id, transaction.id);
+
+        assert_eq!(headers[SIGNATURE_HEADER], sign("shh", &body));
+        assert_eq!(headers[DELIVERY_HEADER], delivery_id.as_str());
+        assert_eq!(payload["delivery_id"], delivery_id.as_str());
+        assert_eq!(payload["slot"], transaction.slot);
+        assert_eq!(payload["transaction"]["id"], transaction.id.as_str());
+
+        // Logged once the receiver has answered
+        for _ in 0..50 {
+            if let Some(attempt) = registry.deliveries(webhook.id).await.first() {
+                assert!(attempt.delivered);
+                assert_eq!(attempt.status, Some(200));
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Delivery wasn't logged");
+    }
+
+    #[tokio::test]
+    async fn rejected_deliveries_are_dead_lettered() {
+        let (url, mut received) = receiver(&[StatusCode::BAD_REQUEST]).await;
+        let transaction = transactions().remove(0);
+        let (registry, webhook) = dispatch(url, &[transaction]).await;
+
+        timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        for _ in 0..50 {
+            if let Some(dead_letter) = registry.dead_letters().await.first() {
+                assert_eq!(dead_letter.delivery.webhook_id, webhook.id);
+                assert_eq!(dead_letter.attempts, 1);
+                assert_eq!(registry.deliveries(webhook.id).await.len(), 1);
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Delivery wasn't dead lettered");
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried() {
+        let statuses = [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::OK,
+        ];
+        let (url, mut received) = receiver(&statuses).await;
+        let transaction = transactions().remove(0);
+        let (registry, webhook) = dispatch(url, &[transaction]).await;
+
+        // Backing off 500ms, then 1s
+        for _ in statuses {
+            timeout(Duration::from_secs(5), received.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        for _ in 0..50 {
+            let attempts = registry.deliveries(webhook.id).await;
+            if attempts.len() == statuses.len() {
+                let logged: Vec<_> = attempts
+                    .iter()
+                    .map(|attempt| (attempt.attempt, attempt.status, attempt.delivered))
+                    .collect();
+                assert_eq!(
+                    logged,
+                    // Newest first
+                    [
+                        (3, Some(200), true),
+                        (2, Some(429), false),
+                        (1, Some(500), false)
+                    ]
+                );
+                assert!(registry.dead_letters().await.is_empty());
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Delivery attempts weren't logged");
+    }
+
+    #[tokio::test]
+    async fn full_queues_are_dead_lettered() {
+        // Never answers, so deliveries pile up behind the ones in flight
+        let (url, _received) = receiver(&[]).await;
+
+        let transaction = transactions().remove(0);
+        let overflow = 100;
+        let transactions: Vec<Transaction> = (0..MAX_IN_FLIGHT + QUEUE_SIZE + overflow)
+            .map(|index| Transaction {
+                id: format!("{}-{index}", transaction.id),
+                ..transaction.clone()
+            })
+            .collect();
+        let (registry, webhook) = dispatch(url, &transactions).await;
+
+        for _ in 0..50 {
+            let dead_letters = registry.dead_letters().await;
+            // One more can be taken off the queue while it waits to be sent
+            if dead_letters.len() >= overflow - 1 {
+                assert!(dead_letters.len() <= overflow);
+                assert!(dead_letters.iter().all(|dead_letter| {
+                    dead_letter.delivery.webhook_id == webhook.id && dead_letter.attempts == 0
+                }));
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Overflowing deliveries weren't dead lettered");
+    }
+}
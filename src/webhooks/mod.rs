@@ -0,0 +1,3 @@
+pub mod dispatcher;
+pub mod registry;
+pub mod target;
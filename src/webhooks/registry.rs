@@ -0,0 +1,250 @@
+use crate::common::transaction::Transaction;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc, Mutex, RwLock};
+
+// Attempts kept per webhook for the delivery log
+const DELIVERY_LOG_SIZE: usize = 100;
+// Deliveries that gave up, oldest dropped first
+const DEAD_LETTER_QUEUE_SIZE: usize = 1000;
+
+//
+// Webhooks registered through the admin API, along with what happened to the
+// deliveries made to them. Kept in memory like the rest of the store, so they
+// have to be registered again after a restart.
+//
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookStatus {
+    #[default]
+    Any,
+    Success,
+    Failed,
+}
+
+// A transaction matches when it touches one of the addresses or invokes one
+// of the programs, and has the wanted status
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookFilter {
+    pub addresses: BTreeSet<String>,
+    pub programs: BTreeSet<String>,
+    pub status: WebhookStatus,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        let status = match self.status {
+            WebhookStatus::Any => true,
+            WebhookStatus::Success => transaction.err.is_none(),
+            WebhookStatus::Failed => transaction.err.is_some(),
+        };
+
+        status
+            && (transaction
+                .accounts
+                .iter()
+                .any(|account| self.addresses.contains(account))
+                || transaction
+                    .program_ids()
+                    .iter()
+                    .any(|program_id| self.programs.contains(*program_id)))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebhookRegistration {
+    pub url: String,
+    pub filter: WebhookFilter,
+    // Payloads are signed with this, so receivers can tell they came from us
+    pub secret: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    pub filter: WebhookFilter,
+    #[serde(skip)]
+    pub secret: String,
+    pub created_at: u64,
+}
+
+// What gets sent to a webhook. The id stays the same across retries, so
+// receivers can drop anything they've already seen.
+#[derive(Clone, Debug, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: u64,
+    pub payload: Value,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeliveryAttempt {
+    pub delivery_id: String,
+    pub attempt: u32,
+    pub timestamp: u64,
+    // Missing if the receiver couldn't be reached at all
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub delivered: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadLetter {
+    pub delivery: Delivery,
+    pub attempts: u32,
+    pub error: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug)]
+pub struct WebhookRegistry {
+    webhooks: RwLock<BTreeMap<u64, Webhook>>,
+    deliveries: Mutex<BTreeMap<u64, VecDeque<DeliveryAttempt>>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+    next_id: AtomicU64,
+    // Dead letters handed back to the dispatcher to try again
+    redeliveries: mpsc::UnboundedSender<Delivery>,
+    redeliveries_rx: Mutex<Option<mpsc::UnboundedReceiver<Delivery>>>,
+    // Whether webhooks can point at loopback and private network addresses
+    allow_private_targets: bool,
+}
+
+impl WebhookRegistry {
+    pub fn new(allow_private_targets: bool) -> Self {
+        let (redeliveries, redeliveries_rx) = mpsc::unbounded_channel();
+
+        WebhookRegistry {
+            webhooks: RwLock::new(BTreeMap::new()),
+            deliveries: Mutex::new(BTreeMap::new()),
+            dead_letters: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+            redeliveries,
+            redeliveries_rx: Mutex::new(Some(redeliveries_rx)),
+            allow_private_targets,
+        }
+    }
+
+    pub fn allow_private_targets(&self) -> bool {
+        self.allow_private_targets
+    }
+
+    pub async fn register(&self, registration: WebhookRegistration) -> Webhook {
+        let webhook = Webhook {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            url: registration.url,
+            filter: registration.filter,
+            secret: registration.secret,
+            created_at: now(),
+        };
+
+        self.webhooks
+            .write()
+            .await
+            .insert(webhook.id, webhook.clone());
+
+        webhook
+    }
+
+    pub async fn unregister(&self, id: u64) -> Option<Webhook> {
+        let webhook = self.webhooks.write().await.remove(&id)?;
+
+        self.deliveries.lock().await.remove(&id);
+        self.dead_letters
+            .lock()
+            .await
+            .retain(|dead_letter| dead_letter.delivery.webhook_id != id);
+
+        Some(webhook)
+    }
+
+    pub async fn webhooks(&self) -> Vec<Webhook> {
+        self.webhooks.read().await.values().cloned().collect()
+    }
+
+    pub async fn webhook(&self, id: u64) -> Option<Webhook> {
+        self.webhooks.read().await.get(&id).cloned()
+    }
+
+    pub async fn matching(&self, transaction: &Transaction) -> Vec<Webhook> {
+        self.webhooks
+            .read()
+            .await
+            .values()
+            .filter(|webhook| webhook.filter.matches(transaction))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn log(&self, webhook_id: u64, attempt: DeliveryAttempt) {
+        let mut deliveries = self.deliveries.lock().await;
+        let log = deliveries.entry(webhook_id).or_default();
+
+        log.push_back(attempt);
+        if log.len() > DELIVERY_LOG_SIZE {
+            log.pop_front();
+        }
+    }
+
+    // Most recent first
+    pub async fn deliveries(&self, webhook_id: u64) -> Vec<DeliveryAttempt> {
+        self.deliveries
+            .lock()
+            .await
+            .get(&webhook_id)
+            .map(|log| log.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn dead_letter(&self, delivery: Delivery, attempts: u32, error: String) {
+        let mut dead_letters = self.dead_letters.lock().await;
+
+        dead_letters.push_back(DeadLetter {
+            delivery,
+            attempts,
+            error,
+            timestamp: now(),
+        });
+        if dead_letters.len() > DEAD_LETTER_QUEUE_SIZE {
+            dead_letters.pop_front();
+        }
+    }
+
+    pub async fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().await.iter().cloned().collect()
+    }
+
+    // Takes a delivery off the dead letter queue and has it sent again
+    pub async fn redeliver(&self, delivery_id: &str) -> Option<Delivery> {
+        let mut dead_letters = self.dead_letters.lock().await;
+        let index = dead_letters
+            .iter()
+            .position(|dead_letter| dead_letter.delivery.id == delivery_id)?;
+        let delivery = dead_letters.remove(index)?.delivery;
+
+        let _ = self.redeliveries.send(delivery.clone());
+
+        Some(delivery)
+    }
+
+    // Only the one dispatcher gets to pick up redeliveries
+    pub async fn take_redeliveries(&self) -> Option<mpsc::UnboundedReceiver<Delivery>> {
+        self.redeliveries_rx.lock().await.take()
+    }
+}
+
+// Milliseconds since the epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,151 @@
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    Url,
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::lookup_host;
+
+//
+// Webhook urls come from whoever can reach the admin API, so unless private
+// targets are explicitly allowed they have to point at a public address.
+// Otherwise the indexer could be used to POST at anything on its own network.
+// Names are checked when registering, and again every time they're resolved
+// for a delivery, so they can't be pointed somewhere private later on.
+//
+
+pub async fn check_target(url: &Url, allow_private: bool) -> Result<(), String> {
+    if !["http", "https"].contains(&url.scheme()) {
+        return Err(format!("Invalid webhook url: {url}"));
+    }
+
+    let Some(host) = url.host_str() else {
+        return Err(format!("Invalid webhook url: {url}"));
+    };
+    if allow_private {
+        return Ok(());
+    }
+
+    // Addresses resolve to themselves, with IPv6 ones losing their brackets
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or_default();
+    let addresses: Vec<IpAddr> = lookup_host((host, port))
+        .await
+        .map_err(|error| format!("Error resolving webhook host {host}: {error}"))?
+        .map(|address| address.ip())
+        .collect();
+
+    match addresses.iter().find(|ip| is_private(**ip)) {
+        Some(ip) => Err(format!(
+            "Webhook host {host} is a private address ({ip}), which isn't allowed"
+        )),
+        None => Ok(()),
+    }
+}
+
+// Resolves names for deliveries, leaving out any private addresses
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addresses: Vec<SocketAddr> = lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| !is_private(address.ip()))
+                .collect();
+
+            if addresses.is_empty() {
+                return Err(format!("Webhook host {host} has no public addresses").into());
+            }
+
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Loopback, private, link local and other addresses not reachable from the
+// public internet
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // 0.0.0.0/8 and 100.64.0.0/10, shared address space for carrier NAT
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 unique local and fe80::/10 link local
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn check(url: &str, allow_private: bool) -> Result<(), String> {
+        check_target(&Url::parse(url).unwrap(), allow_private).await
+    }
+
+    #[test]
+    fn private_addresses() {
+        let private = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ];
+        for ip in private {
+            assert!(is_private(ip.parse().unwrap()), "{ip} is private");
+        }
+
+        for ip in ["1.1.1.1", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_private(ip.parse().unwrap()), "{ip} is public");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_public_targets_unless_allowed() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "https://10.0.0.1/hook",
+            "http://localhost/hook",
+        ] {
+            assert!(check(url, false).await.is_err(), "{url} is rejected");
+            assert!(check(url, true).await.is_ok(), "{url} is allowed");
+        }
+
+        assert!(check("https://1.1.1.1/hook", false).await.is_ok());
+        assert!(check("ftp://1.1.1.1/hook", true).await.is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//
+// Replays recorded cluster traffic through the harness to check how the RPC
+// source follows a cluster, without touching the network
+//
+
+use solforge::{
+    harness::server::{Harness, HarnessMode},
+    source::{
+        common::{Source, SourceUpdate},
+        rpc::RpcSource,
+    },
+};
+
+use std::{path::Path, time::Duration};
+use tokio::{
+    spawn,
+    sync::{broadcast, mpsc},
+    time::timeout,
+};
+
+#[derive(Debug, PartialEq)]
+enum Update {
+    Tip(u64, Option<u64>),
+    Block(u64, u64),
+}
+
+// Follows the cassette until the source has sent the given number of updates
+async fn replay(cassette: &str, count: usize) -> Vec<Update> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/cassettes")
+        .join(cassette);
+
+    let (tx, _) = broadcast::channel(1);
+    let mut harness = Harness::new(
+        Some(HarnessMode::Replay(path)),
+        String::new(),
+        String::new(),
+        tx,
+    );
+    harness.start().await;
+    let (rpc_url, websocket_url) = harness.urls().unwrap();
+
+    let (updates, mut received) = mpsc::channel(16);
+    let source = spawn(async move { RpcSource::new(rpc_url, websocket_url).run(updates).await });
+
+    let mut seen = Vec::new();
+    let followed = timeout(Duration::from_secs(10), async {
+        while seen.len() < count {
+            match received.recv().await {
+                Some(SourceUpdate::Tip {
+                    slot,
+                    finalized_slot,
+                }) => seen.push(Update::Tip(slot, finalized_slot)),
+                Some(SourceUpdate::Block { slot, block }) => {
+                    seen.push(Update::Block(slot, block.parent_slot))
+                }
+                Some(SourceUpdate::Account(_)) | None => break,
+            }
+        }
+    })
+    .await;
+
+    source.abort();
+    harness.stop().await;
+
+    assert!(followed.is_ok(), "Only got {seen:?}");
+    seen
+}
+
+#[tokio::test]
+async fn follows_slots_skipping_missing_blocks_and_reconnecting() {
+    let updates = replay("follow.ndjson", 8).await;
+
+    assert_eq!(
+        updates,
+        [
+            Update::Tip(150, Some(118)),
+            Update::Block(50, 49),
+            // Skipped, and missing from long-term storage
+            Update::Tip(151, Some(119)),
+            Update::Tip(152, Some(120)),
+            // Not available on the first try
+            Update::Tip(153, Some(121)),
+            Update::Block(53, 50),
+            // After the cluster hung up
+            Update::Tip(154, Some(122)),
+            Update::Block(54, 53),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn waits_for_a_young_cluster_to_get_far_enough() {
+    let updates = replay("young_cluster.ndjson", 5).await;
+
+    assert_eq!(
+        updates,
+        [
+            Update::Tip(1, Some(0)),
+            Update::Tip(2, Some(0)),
+            Update::Tip(3, Some(0)),
+            Update::Tip(101, Some(69)),
+            Update::Block(1, 0),
+        ]
+    );
+}